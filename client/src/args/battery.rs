@@ -0,0 +1,26 @@
+use crate::args::Matrix;
+use clap::{value_parser, Args};
+
+#[derive(Args)]
+pub struct BatteryArgs
+{
+	/// Which matrix to target
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// Percentage at or below which the gauge flashes
+	#[arg(short, long, default_value_t = 20, value_parser = value_parser!(u8).range(0..=100))]
+	pub low: u8,
+
+	/// Percentage at or below which the gauge flashes faster, along with its outline
+	#[arg(short, long, default_value_t = 5, value_parser = value_parser!(u8).range(0..=100))]
+	pub critical: u8,
+
+	/// Only show this battery, such as "BAT1", instead of every battery combined
+	#[arg(short, long)]
+	pub battery: Option<String>,
+
+	/// Where the daemon should look for power supplies instead of /sys/class/power_supply
+	#[arg(long)]
+	pub sysfs: Option<String>,
+}
@@ -0,0 +1,32 @@
+use crate::args::Matrix;
+use clap::{Args, ValueEnum};
+
+#[derive(Args)]
+pub struct ClockArgs
+{
+	/// Which matrix to target
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// Show the hour on a 12 hour clock instead of a 24 hour one
+	#[arg(long)]
+	pub hour12: bool,
+
+	/// Also show the seconds, which updates the matrix every second instead of every minute
+	#[arg(short, long)]
+	pub seconds: bool,
+
+	/// How the time is drawn
+	#[arg(long, value_enum, default_value_t = ClockStyle::Digits)]
+	pub style: ClockStyle,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ClockStyle
+{
+	/// Hours stacked above the minutes
+	Digits,
+
+	/// Columns of bits for the hours, minutes and seconds
+	Binary,
+}
@@ -0,0 +1,48 @@
+use crate::args::Matrix;
+use clap::{Args, ValueEnum};
+
+#[derive(Args)]
+pub struct EffectArgs
+{
+	/// Which effect to draw
+	#[arg(value_enum)]
+	pub effect: Effect,
+
+	/// Which matrix to target
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// How fast the effect moves, 1 is its normal speed [0.1-10]
+	#[arg(short, long, default_value_t = 1.0)]
+	pub speed: f64,
+
+	/// How much is going on at once [0-1]
+	#[arg(short, long, default_value_t = 0.5)]
+	pub density: f64,
+
+	/// Start from this seed to see the same effect again, otherwise it's different every time
+	#[arg(long)]
+	pub seed: Option<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Effect
+{
+	/// Streaks of digital rain falling down the columns
+	Rain,
+
+	/// Flames rising from the bottom
+	Fire,
+
+	/// Waves of light flowing over each other
+	Plasma,
+
+	/// Stars flying past from the middle
+	Starfield,
+
+	/// Drops landing on water, with the ripples bouncing off the edges
+	Ripple,
+
+	/// Conway's Game of Life, spanning both matrixes of a pair and starting over whenever it settles down
+	Life,
+}
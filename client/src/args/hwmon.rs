@@ -0,0 +1,22 @@
+use crate::args::Matrix;
+use clap::Args;
+
+#[derive(Args)]
+pub struct HwmonArgs
+{
+	/// Which matrix to target
+	#[arg(short, long, value_enum, required_unless_present = "list")]
+	pub matrix: Option<Matrix>,
+
+	/// Names of the sensors from the daemon's config to show, in order [shows all of them by default]
+	#[arg(conflicts_with = "list")]
+	pub sensors: Vec<String>,
+
+	/// List every input of every chip along with its current reading, to help pick sensors for the config
+	#[arg(short, long)]
+	pub list: bool,
+
+	/// Where to look for hardware monitoring chips instead of /sys/class/hwmon
+	#[arg(long)]
+	pub sysfs: Option<String>,
+}
@@ -1,31 +1,50 @@
 mod animate;
+mod battery;
 mod bootloader;
 mod brightness;
+mod clock;
 mod crash;
 mod drawbw;
+mod effect;
 mod flushcols;
+mod hwmon;
+mod network;
+mod nowplaying;
 mod pattern;
 mod render;
 mod sleep;
 mod stagecol;
+mod sysmon;
+mod timer;
 mod version;
+mod visualize;
 
 #[cfg(windows)]
 mod explain;
 
 use clap::{crate_authors, Parser, Subcommand, ValueEnum};
+use shared::ipc::Target;
 
 pub use animate::AnimateArgs;
+pub use battery::BatteryArgs;
 pub use bootloader::BootloaderArgs;
 pub use brightness::BrightnessArgs;
+pub use clock::{ClockArgs, ClockStyle};
 pub use crash::CrashArgs;
 pub use drawbw::DrawBWArgs;
+pub use effect::{Effect, EffectArgs};
 pub use flushcols::FlushColsArgs;
+pub use hwmon::HwmonArgs;
+pub use network::NetworkArgs;
+pub use nowplaying::NowPlayingArgs;
 pub use pattern::PatternArgs;
 pub use render::RenderArgs;
 pub use sleep::SleepArgs;
 pub use stagecol::StageColArgs;
+pub use sysmon::{SysmonArgs, SysmonPanel};
+pub use timer::TimerArgs;
 pub use version::VersionArgs;
+pub use visualize::VisualizeArgs;
 
 #[cfg(windows)]
 pub use explain::ExplainArgs;
@@ -63,6 +82,12 @@ pub enum Commands
 	/// Lists all available LED matrixes on the system
 	List,
 
+	/// Shows everything the daemon knows about each matrix
+	Status,
+
+	/// Prints events from the daemon as JSON lines until interrupted
+	Watch,
+
 	#[cfg(windows)]
 	/// Explain what the "Service Exit Code" means if the daemon dies on Windows
 	Explain(ExplainArgs),
@@ -70,6 +95,37 @@ pub enum Commands
 	/// Render a JPG, PNG, APNG, GIF, WEBP, and various other image types [can also specify a raw byte vector for a single frame]
 	Render(RenderArgs),
 
+	/// Have the daemon show the current time until something else is displayed
+	Clock(ClockArgs),
+
+	/// Have the daemon show CPU, memory and load until something else is displayed
+	Sysmon(SysmonArgs),
+
+	/// Have the daemon show the battery level until something else is displayed
+	Battery(BatteryArgs),
+
+	/// Have the daemon graph network throughput until something else is displayed
+	Network(NetworkArgs),
+
+	/// Have the daemon show the hardware sensors picked in its config until something else is displayed
+	Hwmon(HwmonArgs),
+
+	/// Have the daemon follow an MPRIS player, going back to what was displayed before once playback stops
+	NowPlaying(NowPlayingArgs),
+
+	/// Have the daemon count down, count up, or run pomodoro sessions, or control the timer that's displayed
+	Timer(TimerArgs),
+
+	/// Have the daemon draw a procedural effect until something else is displayed
+	Effect(EffectArgs),
+
+	/// Draw a spectrum of PCM audio from stdin, a FIFO, or a WAV file until the input ends
+	Visualize(VisualizeArgs),
+
+	#[cfg(feature = "dbus")]
+	/// Forward desktop notifications from the session bus to the daemon until interrupted [run this inside the desktop session]
+	Notifications,
+
 	/// Gets and sets the brightness
 	Brightness(BrightnessArgs),
 
@@ -101,6 +157,20 @@ pub enum Commands
 	DrawBW(DrawBWArgs),
 }
 
+impl From<Matrix> for Target
+{
+	fn from(m: Matrix) -> Self
+	{
+		match m
+		{
+			Matrix::Left => Target::Left,
+			Matrix::Right => Target::Right,
+			Matrix::Both => Target::Both,
+			Matrix::Pair => Target::Pair,
+		}
+	}
+}
+
 impl From<MatrixNoPair> for Matrix
 {
 	fn from(m: MatrixNoPair) -> Self
@@ -0,0 +1,63 @@
+use crate::args::Matrix;
+use clap::Args;
+
+#[derive(Args)]
+pub struct NetworkArgs
+{
+	/// Which matrix to target, received traffic shows on the left and transmitted traffic on the right
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// Only show this interface, such as "wlan0", instead of every interface besides loopback combined
+	#[arg(short, long)]
+	pub interface: Option<String>,
+
+	/// How often to sample, in milliseconds
+	#[arg(short('n'), long, default_value_t = 1000)]
+	pub interval: u64,
+
+	/// Bytes per second that fill a whole row, such as 500K or 10M [scales to the busiest sample on screen by default]
+	#[arg(short, long, value_parser = parse_rate)]
+	pub scale: Option<u64>,
+}
+
+fn parse_rate(rate: &str) -> Result<u64, String>
+{
+	let (number, multiplier) = match rate.to_ascii_uppercase().chars().last()
+	{
+		Some('K') => (&rate[..rate.len() - 1], 1_000),
+		Some('M') => (&rate[..rate.len() - 1], 1_000_000),
+		Some('G') => (&rate[..rate.len() - 1], 1_000_000_000),
+		_ => (rate, 1),
+	};
+
+	number.parse::<u64>()
+	      .map_err(|e| e.to_string())?
+	      .checked_mul(multiplier)
+	      .ok_or_else(|| format!("'{}' is too large", rate))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn rates_are_parsed()
+	{
+		assert_eq!(parse_rate("2048"), Ok(2048));
+		assert_eq!(parse_rate("500K"), Ok(500_000));
+		assert_eq!(parse_rate("10m"), Ok(10_000_000));
+		assert_eq!(parse_rate("1G"), Ok(1_000_000_000));
+		assert!(parse_rate("K").is_err());
+		assert!(parse_rate("fast").is_err());
+	}
+
+	#[test]
+	fn overflowing_rates_are_errors()
+	{
+		assert_eq!(parse_rate(&format!("{}G", u64::MAX / 1_000_000_000)), Ok(u64::MAX / 1_000_000_000 * 1_000_000_000));
+		assert!(parse_rate(&format!("{}G", u64::MAX / 1_000_000_000 + 1)).is_err());
+		assert!(parse_rate(&format!("{}K", u64::MAX)).is_err());
+	}
+}
@@ -0,0 +1,22 @@
+use crate::args::Matrix;
+use clap::Args;
+
+#[derive(Args)]
+pub struct NowPlayingArgs
+{
+	/// Which matrix to target
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// Follow this MPRIS player, such as "spotify", instead of whichever one is playing
+	#[arg(short, long)]
+	pub player: Option<String>,
+
+	/// Scroll the artist and title across the middle
+	#[arg(long)]
+	pub marquee: bool,
+
+	/// Session bus the player is on [defaults to DBUS_SESSION_BUS_ADDRESS, since the daemon usually runs outside of the session]
+	#[arg(short, long)]
+	pub address: Option<String>,
+}
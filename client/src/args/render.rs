@@ -1,6 +1,7 @@
 use crate::args::Matrix;
 use clap::Args;
 use clap_num::maybe_hex;
+use shared::image::Rotation;
 
 #[derive(Args)]
 pub struct RenderArgs
@@ -19,4 +20,30 @@ pub struct RenderArgs
 	/// This must be 306 bytes, or 612 bytes when matrix is set to "pair"
 	#[arg(short, long, value_names = ["VAL1", "VAL2"], required = false, num_args = 306..=612, value_parser = maybe_hex::<u8>, verbatim_doc_comment, conflicts_with = "file")]
 	pub raw: Option<Vec<u8>>,
+
+	/// Have the daemon read the file itself, so it can be restored after the daemon restarts
+	/// The daemon must be able to read the file
+	#[arg(short, long, requires = "file", verbatim_doc_comment)]
+	pub daemon: bool,
+
+	/// Rotate the image clockwise by 0, 90, 180 or 270 degrees before scaling it
+	#[arg(long, value_name = "DEGREES", default_value = "0", value_parser = parse_rotation, requires = "file")]
+	pub rotate: Rotation,
+
+	/// Mirror the image left to right
+	#[arg(long, requires = "file")]
+	pub flip_horizontal: bool,
+
+	/// Mirror the image top to bottom
+	#[arg(long, requires = "file")]
+	pub flip_vertical: bool,
+
+	/// Invert the brightness of every pixel
+	#[arg(long, requires = "file")]
+	pub invert: bool,
+}
+
+fn parse_rotation(degrees: &str) -> Result<Rotation, String>
+{
+	Rotation::try_from(degrees.parse::<u16>().map_err(|e| e.to_string())?)
 }
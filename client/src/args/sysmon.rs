@@ -0,0 +1,38 @@
+use crate::args::Matrix;
+use clap::{Args, ValueEnum};
+
+#[derive(Args)]
+pub struct SysmonArgs
+{
+	/// Which matrix to target
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// What to show on the left matrix, or the left half of a pair
+	#[arg(short, long, value_enum, default_value_t = SysmonPanel::Cpu)]
+	pub left: SysmonPanel,
+
+	/// What to show on the right matrix, or the right half of a pair
+	#[arg(short, long, value_enum, default_value_t = SysmonPanel::Memory)]
+	pub right: SysmonPanel,
+
+	/// How often to sample, in milliseconds
+	#[arg(short, long, default_value_t = 1000)]
+	pub interval: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SysmonPanel
+{
+	/// A horizontal bar for each core
+	Cpu,
+
+	/// Overall CPU usage scrolling upwards
+	CpuHistory,
+
+	/// Memory and swap usage side by side
+	Memory,
+
+	/// The 1, 5 and 15 minute load averages, relative to the number of cores
+	Load,
+}
@@ -0,0 +1,125 @@
+use crate::args::Matrix;
+use clap::{value_parser, Args};
+use shared::widget::{MAX_TIMER_CYCLES, MAX_TIMER_SECONDS};
+
+#[derive(Args)]
+pub struct TimerArgs
+{
+	/// How long to count down, or how long each focus session is, such as 25m, 90s, or 1h30m [a plain number is minutes]
+	#[arg(value_parser = parse_duration, required_unless_present_any = ["stopwatch", "pause", "resume", "cancel"])]
+	pub duration: Option<u64>,
+
+	/// Which matrix to target, or which timer to control since each matrix and the pair have their own
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// Count up instead of down
+	#[arg(short, long, conflicts_with_all = ["duration", "pomodoro", "rest", "cycles"])]
+	pub stopwatch: bool,
+
+	/// Alternate between focus sessions and rests
+	#[arg(short, long)]
+	pub pomodoro: bool,
+
+	/// How long each rest between focus sessions is
+	#[arg(short, long, value_parser = parse_duration, default_value = "5m", requires = "pomodoro")]
+	pub rest: u64,
+
+	/// Focus sessions before the timer is done
+	#[arg(short, long, default_value_t = 4, requires = "pomodoro", value_parser = value_parser!(u32).range(1..=MAX_TIMER_CYCLES as i64))]
+	pub cycles: u32,
+
+	/// Pause the timer that's displayed
+	#[arg(long, conflicts_with_all = ["resume", "cancel"])]
+	pub pause: bool,
+
+	/// Resume the timer that's displayed
+	#[arg(long, conflicts_with_all = ["pause", "cancel"])]
+	pub resume: bool,
+
+	/// Stop the timer that's displayed, and go back to what was displayed before it
+	#[arg(long, conflicts_with_all = ["pause", "resume"])]
+	pub cancel: bool,
+}
+
+/// Seconds in a duration made up of hours, minutes, and seconds, such as 1h30m
+fn parse_duration(duration: &str) -> Result<u64, String>
+{
+	let long = || format!("'{}' is too long, timers can be up to {} minutes", duration, MAX_TIMER_SECONDS / 60);
+
+	if let Ok(minutes) = duration.parse::<u64>()
+	{
+		return minutes.checked_mul(60).filter(|s| *s <= MAX_TIMER_SECONDS).ok_or_else(long);
+	}
+
+	let (mut seconds, mut number) = (0, String::new());
+	for c in duration.to_ascii_lowercase().chars()
+	{
+		let unit = match c
+		{
+			'0'..='9' =>
+			{
+				number.push(c);
+				continue;
+			},
+			'h' => 3600,
+			'm' => 60,
+			's' => 1,
+			_ => return Err(format!("unknown unit '{}', use h, m, or s", c)),
+		};
+
+		if number.is_empty()
+		{
+			return Err(format!("'{}' is missing a number before its unit", duration));
+		}
+
+		seconds = number.parse::<u64>()
+		                .ok()
+		                .and_then(|n| n.checked_mul(unit))
+		                .and_then(|n| n.checked_add(seconds))
+		                .ok_or_else(long)?;
+		number.clear();
+	}
+
+	match number.is_empty()
+	{
+		true if seconds <= MAX_TIMER_SECONDS => Ok(seconds),
+		true => Err(long()),
+		false => Err(format!("'{}' is missing a unit at the end", duration)),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn durations_are_parsed()
+	{
+		assert_eq!(parse_duration("25"), Ok(25 * 60));
+		assert_eq!(parse_duration("90s"), Ok(90));
+		assert_eq!(parse_duration("1h30m"), Ok(5400));
+		assert_eq!(parse_duration("1H5M10S"), Ok(3910));
+	}
+
+	#[test]
+	fn malformed_durations_are_errors()
+	{
+		assert!(parse_duration("m").is_err());
+		assert!(parse_duration("1h30").is_err());
+		assert!(parse_duration("5d").is_err());
+		assert!(parse_duration("-5").is_err());
+	}
+
+	#[test]
+	fn overflowing_durations_are_errors()
+	{
+		assert_eq!(parse_duration("999m"), Ok(MAX_TIMER_SECONDS));
+		assert!(parse_duration("1000").is_err());
+		assert!(parse_duration("16h40m").is_err());
+		assert!(parse_duration(&u64::MAX.to_string()).is_err());
+		assert!(parse_duration(&format!("{}h", u64::MAX / 3600 + 1)).is_err());
+		assert!(parse_duration(&format!("{}s1s", u64::MAX)).is_err());
+	}
+}
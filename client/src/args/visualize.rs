@@ -0,0 +1,33 @@
+use crate::args::Matrix;
+use clap::{value_parser, Args};
+
+#[derive(Args)]
+pub struct VisualizeArgs
+{
+	/// Which matrix to target, a pair gets 18 bars instead of 9
+	#[arg(short, long, value_enum)]
+	pub matrix: Matrix,
+
+	/// WAV file or FIFO to read from, otherwise signed 16-bit little endian PCM is read from stdin
+	pub input: Option<String>,
+
+	/// Sample rate of raw PCM input, WAV files use the rate in their header
+	#[arg(short, long, default_value_t = 44100, value_parser = value_parser!(u32).range(1..))]
+	pub rate: u32,
+
+	/// Number of interleaved channels in raw PCM input, WAV files use the count in their header
+	#[arg(short, long, default_value_t = 2, value_parser = value_parser!(u16).range(1..))]
+	pub channels: u16,
+
+	/// Frames drawn per second
+	#[arg(short, long, default_value_t = 30, value_parser = value_parser!(u32).range(1..=120))]
+	pub fps: u32,
+
+	/// Frames a peak stays in place before it starts to fall
+	#[arg(short('p'), long, default_value_t = 15)]
+	pub peak_hold: u32,
+
+	/// How much of the full height bars and peaks fall each frame
+	#[arg(short, long, default_value_t = 0.05)]
+	pub decay: f32,
+}
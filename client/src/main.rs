@@ -1,19 +1,33 @@
 mod args;
-mod image;
-mod ipc;
+#[cfg(feature = "dbus")]
+mod notifications;
+mod visualize;
 
 use crate::{
-	args::{Args, Commands, Matrix, MatrixNoPair},
-	image::read_image,
-	ipc::send_command,
+	args::{Args, ClockStyle, Commands, Effect, Matrix, MatrixNoPair, SysmonPanel},
+	visualize::visualize,
 };
 use clap::Parser;
+use fw16_led_matrix::{Client, Error as ClientError};
 use shared::{
 	find_ports,
-	ipc::{IpcCommand, IpcResponse},
+	hwmon::{hwmon_inputs, HWMON},
+	image::{read_image, ImageOptions},
+	ipc::{ContentKind, IpcCommand, IpcResponse, MatrixStatus, TimerAction},
 	version::Version,
+	widget::{
+		self, BatteryOptions, ClockOptions, EffectOptions, HwmonOptions, NetworkOptions, NowPlayingOptions,
+		SysmonOptions, TimerMode, TimerOptions, Widget,
+	},
+};
+use std::{
+	env::var,
+	error::Error,
+	fs::canonicalize,
+	path::Path,
+	process::exit,
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use std::{error::Error, time::Duration};
 
 fn main() -> Result<(), Box<dyn Error>>
 {
@@ -33,9 +47,20 @@ fn main() -> Result<(), Box<dyn Error>>
 		},
 		Commands::Render(args) =>
 		{
-			if let Some(file) = args.file
+			let options = ImageOptions { rotate: args.rotate,
+			                             flip_horizontal: args.flip_horizontal,
+			                             flip_vertical: args.flip_vertical,
+			                             invert: args.invert };
+
+			if let (Some(file), true) = (&args.file, args.daemon)
+			{
+				// The daemon has its own working directory
+				let path = canonicalize(file)?.to_string_lossy().to_string();
+				Some(IpcCommand::RenderFile { path, target: args.matrix.into(), options })
+			}
+			else if let Some(file) = args.file
 			{
-				let raw = read_image(file, args.matrix == Matrix::Pair)?;
+				let raw = read_image(file, args.matrix == Matrix::Pair, &options)?;
 
 				match args.matrix
 				{
@@ -63,6 +88,197 @@ fn main() -> Result<(), Box<dyn Error>>
 				None
 			}
 		},
+		Commands::Status => Some(IpcCommand::Status),
+		// Events are printed as JSON lines so they're easy to hand to other tools
+		Commands::Watch =>
+		{
+			match Client::connect().and_then(Client::subscribe)
+			{
+				Ok(events) =>
+				{
+					for event in events
+					{
+						match event.map(|e| e.to_json())
+						{
+							Ok(Ok(json)) => println!("{}", json),
+							Ok(Err(e)) => eprintln!("Failed to encode event with error: {}", e),
+							Err(ClientError::Daemon(e)) =>
+							{
+								eprintln!("Daemon encountered an issue with the subscription with error: {}", e);
+								exit(1);
+							},
+							Err(e) =>
+							{
+								eprintln!("Failed to watch for events with error: {}", e);
+								exit(1);
+							},
+						}
+					}
+				},
+				Err(ClientError::Daemon(e)) =>
+				{
+					eprintln!("Daemon encountered an issue with the subscription with error: {}", e);
+					exit(1);
+				},
+				Err(e) =>
+				{
+					eprintln!("Failed to watch for events with error: {}", e);
+					exit(1);
+				},
+			}
+
+			None
+		},
+		Commands::Clock(args) =>
+		{
+			let style = match args.style
+			{
+				ClockStyle::Digits => widget::ClockStyle::Digits,
+				ClockStyle::Binary => widget::ClockStyle::Binary,
+			};
+
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::Clock(ClockOptions { hour12: args.hour12,
+			                                                                    seconds: args.seconds,
+			                                                                    style }) })
+		},
+		Commands::Sysmon(args) =>
+		{
+			let panel = |panel| match panel
+			{
+				SysmonPanel::Cpu => widget::SysmonPanel::Cpu,
+				SysmonPanel::CpuHistory => widget::SysmonPanel::CpuHistory,
+				SysmonPanel::Memory => widget::SysmonPanel::Memory,
+				SysmonPanel::Load => widget::SysmonPanel::Load,
+			};
+
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::Sysmon(SysmonOptions { left: panel(args.left),
+			                                                                      right: panel(args.right),
+			                                                                      interval: args.interval }) })
+		},
+		Commands::Battery(args) =>
+		{
+			// The daemon resolves paths relative to its own working directory, so make sure it gets the full path
+			let sysfs = match args.sysfs
+			{
+				Some(path) => Some(canonicalize(path)?.to_string_lossy().to_string()),
+				None => None,
+			};
+
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::Battery(BatteryOptions { low: args.low,
+			                                                                        critical: args.critical,
+			                                                                        battery: args.battery,
+			                                                                        sysfs }) })
+		},
+		Commands::Network(args) =>
+		{
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::Network(NetworkOptions { interface: args.interface,
+			                                                                        interval: args.interval,
+			                                                                        scale: args.scale }) })
+		},
+		Commands::Hwmon(args) => match (args.list, args.matrix)
+		{
+			(false, Some(matrix)) =>
+			{
+				let sysfs = match args.sysfs
+				{
+					Some(path) => Some(canonicalize(path)?.to_string_lossy().to_string()),
+					None => None,
+				};
+
+				Some(IpcCommand::StartWidget { target: matrix.into(),
+				                               widget: Widget::Hwmon(HwmonOptions { sensors: args.sensors, sysfs }) })
+			},
+			_ =>
+			{
+				for input in hwmon_inputs(Path::new(args.sysfs.as_deref().unwrap_or(HWMON)))?
+				{
+					let reading = input.read().map_or_else(|e| format!("unreadable ({})", e), |r| r.to_string());
+					match input.label
+					{
+						Some(label) => println!("{} {} ({}): {}", input.chip, input.input, label, reading),
+						None => println!("{} {}: {}", input.chip, input.input, reading),
+					}
+				}
+
+				None
+			},
+		},
+		#[cfg(feature = "dbus")]
+		Commands::Notifications =>
+		{
+			if let Err(e) = notifications::forward_notifications()
+			{
+				eprintln!("Failed to forward notifications with error: {}", e);
+				exit(1);
+			}
+
+			None
+		},
+		Commands::NowPlaying(args) =>
+		{
+			let address = args.address.or(var("DBUS_SESSION_BUS_ADDRESS").ok());
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::NowPlaying(NowPlayingOptions { player: args.player,
+			                                                                              marquee: args.marquee,
+			                                                                              address }) })
+		},
+		Commands::Timer(args) => match (args.pause, args.resume, args.cancel)
+		{
+			(true, ..) => Some(IpcCommand::Timer { target: args.matrix.into(), action: TimerAction::Pause }),
+			(_, true, _) => Some(IpcCommand::Timer { target: args.matrix.into(), action: TimerAction::Resume }),
+			(.., true) => Some(IpcCommand::Timer { target: args.matrix.into(), action: TimerAction::Cancel }),
+			_ =>
+			{
+				let mode = match (args.stopwatch, args.pomodoro)
+				{
+					(true, _) => TimerMode::Stopwatch,
+					(_, true) => TimerMode::Pomodoro,
+					_ => TimerMode::Countdown,
+				};
+
+				// The daemon carries on from when the timer was started if it has to display it again
+				let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+				let duration = args.duration.unwrap_or_default();
+				Some(IpcCommand::StartWidget { target: args.matrix.into(),
+				                               widget: Widget::Timer(TimerOptions { mode,
+				                                                                    duration,
+				                                                                    rest: args.rest,
+				                                                                    cycles: args.cycles,
+				                                                                    started: Some(started) }) })
+			},
+		},
+		Commands::Effect(args) =>
+		{
+			let effect = match args.effect
+			{
+				Effect::Rain => widget::Effect::Rain,
+				Effect::Fire => widget::Effect::Fire,
+				Effect::Plasma => widget::Effect::Plasma,
+				Effect::Starfield => widget::Effect::Starfield,
+				Effect::Ripple => widget::Effect::Ripple,
+				Effect::Life => widget::Effect::Life,
+			};
+
+			Some(IpcCommand::StartWidget { target: args.matrix.into(),
+			                               widget: Widget::Effect(EffectOptions { effect,
+			                                                                      speed: args.speed,
+			                                                                      density: args.density,
+			                                                                      seed: args.seed }) })
+		},
+		Commands::Visualize(args) =>
+		{
+			if let Err(e) = visualize(args)
+			{
+				eprintln!("Failed to visualize the audio with error: {}", e);
+				exit(1);
+			}
+
+			None
+		},
 		Commands::Brightness(args) =>
 		{
 			if let Some(set) = args.set
@@ -281,6 +497,12 @@ fn main() -> Result<(), Box<dyn Error>>
 				0xDEAD0005 => "Invalid vector size frame was provided, this shouldn't have crashed the daemon",
 				0xDEAD0006 => "Invalid column number was provided, this shouldn't have crashed the daemon",
 				0xDEAD0007 => "An internal handler error occurred, this shouldn't have crashed the daemon",
+				0xDEAD0008 => "A client was too old for the daemon, this shouldn't have crashed the daemon",
+				0xDEAD0009 => "The daemon was too old for a client, this shouldn't have crashed the daemon",
+				0xDEAD000A => "A client sent an unsupported command, this shouldn't have crashed the daemon",
+				0xDEAD000B => "A response couldn't be understood, this shouldn't have crashed the daemon",
+				0xDEAD000C => "A reply didn't match its request, this shouldn't have crashed the daemon",
+				0xDEAD000D => "A relative path was provided, this shouldn't have crashed the daemon",
 				_ => "Unknown error",
 			});
 
@@ -290,10 +512,9 @@ fn main() -> Result<(), Box<dyn Error>>
 
 	if let Some(command) = command
 	{
-		let collect_response = command.needs_response();
-		match send_command("fw16-led-matrixd.socket".to_string(), command, collect_response)
+		match Client::connect().and_then(|mut client| client.call(command))
 		{
-			Ok(Some(response)) => match response
+			Ok(response) => match response
 			{
 				IpcResponse::Brightness(left, right) =>
 				{
@@ -351,15 +572,79 @@ fn main() -> Result<(), Box<dyn Error>>
 						}
 					}
 				},
-				IpcResponse::InvalidCommand(e) =>
+				IpcResponse::Status(status) =>
 				{
-					println!("Daemon encountered an issue executing the command with error: {}", e)
+					let uptime = status.uptime.as_secs();
+					println!("Daemon uptime: {}h {}m {}s", uptime / 3600, uptime / 60 % 60, uptime % 60);
+
+					for (name, matrix) in [("Left", status.left), ("Right", status.right)]
+					{
+						if let Some(matrix) = matrix
+						{
+							print_matrix_status(name, matrix);
+						}
+					}
 				},
+				// Events only show up when watching
+				IpcResponse::Done | IpcResponse::Event(_) => (),
+				// The hello exchange is handled while sending the command
+				IpcResponse::Hello(_, _) => (),
+			},
+			// Failures exit with an error so that scripts can tell the command didn't happen
+			Err(ClientError::Daemon(e)) =>
+			{
+				eprintln!("Daemon encountered an issue executing the command with error: {}", e);
+				exit(1);
+			},
+			Err(e) =>
+			{
+				eprintln!("Failed to send command with error: {}", e);
+				exit(1);
 			},
-			Ok(None) => (),
-			Err(e) => println!("Failed to send command with error: {}", e),
 		}
 	}
 
 	Ok(())
 }
+
+fn print_matrix_status(name: &str, matrix: MatrixStatus)
+{
+	let unknown = || "unknown".to_string();
+
+	println!("{} LED matrix:", name);
+	println!("  Port: {}", matrix.port);
+	println!("  Connected: {}", matrix.connected);
+	let version = match matrix.version.map(Version::try_from)
+	{
+		Some(Ok(version)) => version.to_string(),
+		Some(Err(e)) => format!("invalid ({})", e),
+		None => unknown(),
+	};
+	println!("  Version: {}", version);
+	println!("  Brightness: {}", matrix.brightness.map_or_else(unknown, |b| b.to_string()));
+	println!("  Sleeping: {}", matrix.sleeping);
+	println!("  Scrolling: {}", matrix.scroll.map_or_else(unknown, |s| s.to_string()));
+
+	let content = match matrix.content
+	{
+		Some(ContentKind::Pattern(pattern)) => format!("{} pattern", pattern),
+		Some(ContentKind::Bitmap) => "bitmap".to_string(),
+		Some(ContentKind::Image) => "image".to_string(),
+		Some(ContentKind::Animation) => "animation".to_string(),
+		Some(ContentKind::Text(text)) => format!("text \"{}\"", text),
+		Some(ContentKind::File(path)) => format!("file \"{}\"", path),
+		Some(ContentKind::Widget(name)) => format!("{} widget", name),
+		None => unknown(),
+	};
+	println!("  Content: {}{}", content, if matrix.pair { " (pair)" } else { "" });
+
+	if let Some(frame) = matrix.frame
+	{
+		println!("  Frame: {}", frame);
+	}
+
+	if let Some(loops) = matrix.loops
+	{
+		println!("  Loops: {}", loops);
+	}
+}
@@ -0,0 +1,46 @@
+use fw16_led_matrix::{Client, IpcCommand, Urgency};
+use std::{collections::HashMap, error::Error};
+use zbus::{
+	blocking::{fdo::MonitoringProxy, Connection, MessageIterator},
+	message::Type,
+	zvariant::OwnedValue,
+	MatchRule,
+};
+
+// Arguments to org.freedesktop.Notifications.Notify: app name, replaced id, icon, summary, body, actions, hints, and timeout
+type NotifyArgs = (String, u32, String, String, String, Vec<String>, HashMap<String, OwnedValue>, i32);
+
+/// Forward every notification sent on the session bus to the daemon, until the bus goes away
+pub fn forward_notifications() -> Result<(), Box<dyn Error>>
+{
+	let connection = Connection::session()?;
+	let rule = MatchRule::builder().msg_type(Type::MethodCall)
+	                               .interface("org.freedesktop.Notifications")?
+	                               .member("Notify")?
+	                               .build();
+
+	// Monitoring sees the calls on their way to whichever notification daemon is running, without getting in the way
+	MonitoringProxy::new(&connection)?.become_monitor(&[rule], 0)?;
+	println!("Forwarding notifications to the daemon");
+
+	for message in MessageIterator::from(&connection)
+	{
+		let Ok((app, _, _, summary, _, _, hints, _)) = message?.body().deserialize::<NotifyArgs>()
+		else
+		{
+			continue;
+		};
+
+		let urgency =
+			hints.get("urgency").and_then(|u| u.downcast_ref::<u8>().ok()).map(Urgency::from).unwrap_or_default();
+		let command = IpcCommand::Notify { app: app.clone(), summary, urgency };
+
+		// The daemon might be restarted at any point, so every notification gets its own connection
+		if let Err(e) = Client::connect().and_then(|mut client| client.call(command))
+		{
+			eprintln!("Failed to forward a notification from {} with error: {}", app, e);
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,429 @@
+use crate::args::{Matrix, VisualizeArgs};
+use fw16_led_matrix::{Canvas, Client, IpcCommand};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use std::{
+	error::Error,
+	f32::consts::PI,
+	fs::File,
+	io::{copy, sink, stdin, BufReader, Cursor, ErrorKind, Read},
+	sync::Arc,
+	thread::sleep,
+	time::{Duration, Instant},
+};
+
+// Samples that go into each spectrum, which is about 46ms at 44.1kHz
+const FFT_SIZE: usize = 2048;
+
+// The bars are spread out logarithmically between these frequencies
+const MIN_FREQUENCY: f32 = 50.0;
+const MAX_FREQUENCY: f32 = 16000.0;
+
+// Anything this far below full scale is an empty bar
+const FLOOR_DB: f32 = -60.0;
+
+// Bars are dimmer than their peaks, so the peaks stand out
+const BAR_BRIGHTNESS: u8 = 0x60;
+
+/// Samples that are left after any header, along with how to interpret them
+struct Pcm
+{
+	samples: Box<dyn Read>,
+	rate: u32,
+	channels: u16,
+}
+
+struct Peak
+{
+	level: f32,
+	held: u32,
+}
+
+/// The most recent samples, and the bars and peaks drawn from them so far
+struct Spectrum
+{
+	fft: Arc<dyn Fft<f32>>,
+	window: Vec<f32>,
+	bands: Vec<(usize, usize)>,
+	decay: f32,
+	peak_hold: u32,
+	samples: Vec<f32>,
+	levels: Vec<f32>,
+	peaks: Vec<Peak>,
+}
+
+/// Draw a spectrum from the input until it runs out, sending every frame to the daemon as it's drawn
+pub fn visualize(args: VisualizeArgs) -> Result<(), Box<dyn Error>>
+{
+	let input: Box<dyn Read> = match &args.input
+	{
+		Some(path) => Box::new(File::open(path)?),
+		None => Box::new(stdin().lock()),
+	};
+
+	let Pcm { samples: mut input, rate, channels } = open_pcm(BufReader::new(input), args.rate, args.channels)?;
+	let mut client = Client::connect()?;
+
+	let pair = args.matrix == Matrix::Pair;
+	let mut spectrum = Spectrum::new(if pair { 18 } else { 9 }, rate, args.decay, args.peak_hold);
+	let mut buffer = vec![0u8; (rate / args.fps).max(1) as usize * channels as usize * 2];
+
+	let start = Instant::now();
+	let mut frame = 0;
+
+	loop
+	{
+		// Whatever is left over at the end isn't enough for another frame
+		match input.read_exact(&mut buffer)
+		{
+			Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+			result => result?,
+		}
+
+		// Mix every channel down to mono
+		let mono: Vec<f32> = buffer.chunks_exact(channels as usize * 2).map(mix).collect();
+		spectrum.push(&mono);
+
+		let mut canvas = Canvas::new(pair);
+		spectrum.draw(&mut canvas);
+
+		let frames = canvas.to_frames();
+		let command = match args.matrix
+		{
+			Matrix::Left => IpcCommand::RenderSingle(Some(frames), None),
+			Matrix::Right => IpcCommand::RenderSingle(None, Some(frames)),
+			Matrix::Both => IpcCommand::RenderSingle(Some(frames.clone()), Some(frames)),
+			Matrix::Pair => IpcCommand::RenderPair(frames),
+		};
+		client.send(command)?;
+
+		// Files can be read much faster than they play, so hold back to keep a steady frame rate
+		frame += 1;
+		if let Some(wait) = (start + Duration::from_secs(frame) / args.fps).checked_duration_since(Instant::now())
+		{
+			sleep(wait);
+		}
+	}
+
+	Ok(())
+}
+
+impl Spectrum
+{
+	fn new(bars: usize, rate: u32, decay: f32, peak_hold: u32) -> Self
+	{
+		Spectrum { fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+		           window: (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect(),
+		           bands: bands(bars, rate),
+		           decay,
+		           peak_hold,
+		           samples: vec![0.0; FFT_SIZE],
+		           levels: vec![0.0; bars],
+		           peaks: (0..bars).map(|_| Peak { level: 0.0, held: 0 }).collect() }
+	}
+
+	/// Take in new mono samples and work out where the bars and peaks are now
+	fn push(&mut self, mono: &[f32])
+	{
+		// Only the most recent samples are kept
+		let new = mono.len().min(FFT_SIZE);
+		self.samples.drain(..new);
+		self.samples.extend_from_slice(&mono[mono.len() - new..]);
+
+		let mut spectrum: Vec<Complex<f32>> =
+			self.samples.iter().zip(&self.window).map(|(s, w)| Complex::new(s * w, 0.0)).collect();
+		self.fft.process(&mut spectrum);
+
+		for (bar, (low, high)) in self.bands.iter().enumerate()
+		{
+			// A full scale sine wave peaks at a quarter of the FFT size once the window is applied
+			let magnitude =
+				spectrum[*low..*high].iter().map(|c| c.norm()).fold(0.0, f32::max) / (FFT_SIZE as f32 / 4.0);
+			let level = ((20.0 * magnitude.max(1e-9).log10() - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0);
+
+			// Bars jump up straight away but fall slowly, and peaks wait a while before falling too
+			self.levels[bar] = level.max(self.levels[bar] - self.decay);
+
+			let peak = &mut self.peaks[bar];
+			if self.levels[bar] >= peak.level
+			{
+				*peak = Peak { level: self.levels[bar], held: 0 };
+			}
+			else if peak.held < self.peak_hold
+			{
+				peak.held += 1;
+			}
+			else
+			{
+				peak.level = (peak.level - self.decay).max(self.levels[bar]);
+			}
+		}
+	}
+
+	fn draw(&self, canvas: &mut Canvas)
+	{
+		for (bar, (level, peak)) in self.levels.iter().zip(&self.peaks).enumerate()
+		{
+			draw_bar(canvas, bar, *level, peak.level);
+		}
+	}
+}
+
+/// Skip past the header of a WAV file to get to its samples, anything else is treated as raw PCM
+fn open_pcm(mut input: impl Read + 'static, rate: u32, channels: u16) -> Result<Pcm, Box<dyn Error>>
+{
+	let mut header = [0u8; 12];
+	let read = read_up_to(&mut input, &mut header)?;
+
+	if read < 12 || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE"
+	{
+		// Put back what was read, since it's the start of the samples
+		return Ok(Pcm { samples: Box::new(Cursor::new(header[..read].to_vec()).chain(input)), rate, channels });
+	}
+
+	let mut format = None;
+	loop
+	{
+		let mut chunk = [0u8; 8];
+		input.read_exact(&mut chunk)?;
+		let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+		match &chunk[0..4]
+		{
+			b"fmt " =>
+			{
+				let mut fmt = vec![0u8; size as usize];
+				input.read_exact(&mut fmt)?;
+				if fmt.len() < 16
+				{
+					return Err("the WAV file has a malformed format chunk".into());
+				}
+
+				// 1 is plain PCM and 0xFFFE is the extensible format, which is used for more than 2 channels
+				let kind = u16::from_le_bytes([fmt[0], fmt[1]]);
+				let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+				if !matches!(kind, 1 | 0xFFFE) || bits != 16
+				{
+					return Err("only 16-bit PCM WAV files are supported".into());
+				}
+
+				format =
+					Some((u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]), u16::from_le_bytes([fmt[2], fmt[3]])));
+			},
+			b"data" =>
+			{
+				let (rate, channels) = format.ok_or("the WAV file has no format chunk before its samples")?;
+				if rate == 0 || channels == 0
+				{
+					return Err("the WAV file has no samples to play".into());
+				}
+
+				return Ok(Pcm { samples: Box::new(input.take(size)), rate, channels });
+			},
+			_ =>
+			{
+				copy(&mut (&mut input).take(size), &mut sink())?;
+			},
+		}
+
+		// Chunks are padded to an even number of bytes
+		if size % 2 == 1
+		{
+			input.read_exact(&mut [0u8; 1])?;
+		}
+	}
+}
+
+/// Like read_exact, but stops early at the end of the input instead of failing
+fn read_up_to(input: &mut impl Read, buffer: &mut [u8]) -> Result<usize, Box<dyn Error>>
+{
+	let mut read = 0;
+	while read < buffer.len()
+	{
+		match input.read(&mut buffer[read..])?
+		{
+			0 => break,
+			n => read += n,
+		}
+	}
+
+	Ok(read)
+}
+
+/// Average every channel of an interleaved frame into one sample between -1 and 1
+fn mix(frame: &[u8]) -> f32
+{
+	let channels = frame.len() / 2;
+	frame.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32).sum::<f32>() / (channels as f32 * 32768.0)
+}
+
+/// Range of FFT bins that go into each bar, so that every bar covers the same number of octaves
+fn bands(bars: usize, rate: u32) -> Vec<(usize, usize)>
+{
+	let max = MAX_FREQUENCY.min(rate as f32 / 2.0).max(MIN_FREQUENCY * 2.0);
+	let bin = |bar: usize| {
+		let frequency = MIN_FREQUENCY * (max / MIN_FREQUENCY).powf(bar as f32 / bars as f32);
+		((frequency * FFT_SIZE as f32 / rate as f32) as usize).min(FFT_SIZE / 2 - 1)
+	};
+
+	// Low bars can end up narrower than a bin, but they still need one to look at
+	(0..bars).map(|bar| (bin(bar), bin(bar + 1).max(bin(bar) + 1))).collect()
+}
+
+fn draw_bar(canvas: &mut Canvas, x: usize, level: f32, peak: f32)
+{
+	let height = canvas.height();
+	let lit = level * height as f32;
+
+	for row in 0..height
+	{
+		let brightness = (lit - row as f32).clamp(0.0, 1.0) * BAR_BRIGHTNESS as f32;
+		canvas.set(x, height - 1 - row, brightness as u8);
+	}
+
+	if peak > 0.0
+	{
+		canvas.set(x, height - 1 - (peak * (height - 1) as f32).round() as usize, 0xFF);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	/// A WAV file with an extra chunk before its samples, like the LIST chunk many encoders write
+	fn wav(rate: u32, channels: u16, bits: u16, samples: &[i16]) -> Vec<u8>
+	{
+		let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+		let mut fmt = vec![];
+		fmt.extend_from_slice(&1u16.to_le_bytes());
+		fmt.extend_from_slice(&channels.to_le_bytes());
+		fmt.extend_from_slice(&rate.to_le_bytes());
+		fmt.extend_from_slice(&(rate * channels as u32 * 2).to_le_bytes());
+		fmt.extend_from_slice(&(channels * 2).to_le_bytes());
+		fmt.extend_from_slice(&bits.to_le_bytes());
+
+		let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+		for (id, chunk) in [(b"fmt ", fmt), (b"LIST", b"odd".to_vec()), (b"data", data)]
+		{
+			wav.extend_from_slice(id);
+			wav.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+			wav.extend_from_slice(&chunk);
+			if chunk.len() % 2 == 1
+			{
+				wav.push(0);
+			}
+		}
+
+		wav
+	}
+
+	fn sine(frequency: f32, rate: u32, length: usize) -> Vec<i16>
+	{
+		(0..length).map(|i| ((2.0 * PI * frequency * i as f32 / rate as f32).sin() * 32767.0) as i16).collect()
+	}
+
+	fn read_all(pcm: Pcm) -> Vec<u8>
+	{
+		let mut samples = vec![];
+		let mut input = pcm.samples;
+		input.read_to_end(&mut samples).unwrap();
+		samples
+	}
+
+	#[test]
+	fn wav_headers_are_skipped()
+	{
+		let pcm = open_pcm(Cursor::new(wav(22050, 2, 16, &[1, -1, 2, -2])), 44100, 1).unwrap();
+		assert_eq!((pcm.rate, pcm.channels), (22050, 2));
+		assert_eq!(read_all(pcm), [1, 0, 255, 255, 2, 0, 254, 255]);
+	}
+
+	#[test]
+	fn raw_pcm_is_kept_whole()
+	{
+		// Short enough that it can't be a header, so none of it should be lost
+		let pcm = open_pcm(Cursor::new(vec![1, 2, 3, 4]), 8000, 1).unwrap();
+		assert_eq!((pcm.rate, pcm.channels), (8000, 1));
+		assert_eq!(read_all(pcm), [1, 2, 3, 4]);
+
+		let raw: Vec<u8> = (0..64).collect();
+		assert_eq!(read_all(open_pcm(Cursor::new(raw.clone()), 8000, 1).unwrap()), raw);
+	}
+
+	#[test]
+	fn unsupported_wavs_are_errors()
+	{
+		assert!(open_pcm(Cursor::new(wav(44100, 1, 8, &[0])), 44100, 1).is_err());
+		assert!(open_pcm(Cursor::new(wav(0, 1, 16, &[0])), 44100, 1).is_err());
+
+		// Cut off before the samples start
+		let wav = wav(44100, 1, 16, &[0]);
+		assert!(open_pcm(Cursor::new(wav[..30].to_vec()), 44100, 1).is_err());
+	}
+
+	#[test]
+	fn channels_are_mixed()
+	{
+		assert_eq!(mix(&[0, 0x40, 0, 0xC0]), 0.0);
+		assert_eq!(mix(&[0, 0x40, 0, 0x40]), 0.5);
+	}
+
+	#[test]
+	fn bands_cover_the_spectrum_in_order()
+	{
+		let bands = bands(18, 44100);
+		assert!(bands.iter().all(|(low, high)| low < high && *high < FFT_SIZE / 2));
+		assert!(bands.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+	}
+
+	#[test]
+	fn a_tone_lights_up_its_own_bar()
+	{
+		let rate = 44100;
+		let pcm = open_pcm(Cursor::new(wav(rate, 1, 16, &sine(1000.0, rate, FFT_SIZE))), rate, 1).unwrap();
+		let mono: Vec<f32> = read_all(pcm).chunks_exact(2).map(mix).collect();
+
+		let mut spectrum = Spectrum::new(9, rate, 0.05, 15);
+		spectrum.push(&mono);
+
+		let bar = spectrum.bands
+		                  .iter()
+		                  .position(|(low, high)| {
+			                  let bin = (1000.0 * FFT_SIZE as f32 / rate as f32) as usize;
+			                  (*low..*high).contains(&bin)
+		                  })
+		                  .unwrap();
+		let loudest = (0..9).max_by(|a, b| spectrum.levels[*a].total_cmp(&spectrum.levels[*b])).unwrap();
+		assert_eq!(loudest, bar);
+		assert!(spectrum.levels[bar] > 0.9);
+
+		let mut canvas = Canvas::new(false);
+		spectrum.draw(&mut canvas);
+		assert_eq!(canvas.get(bar, canvas.height() - 1), BAR_BRIGHTNESS);
+		// The peak sits on top of a bar that's nearly full height
+		assert!((0..4).any(|y| canvas.get(bar, y) == 0xFF));
+	}
+
+	#[test]
+	fn bars_fall_slowly_and_peaks_wait()
+	{
+		let rate = 44100;
+		let mut spectrum = Spectrum::new(9, rate, 0.1, 2);
+		spectrum.push(&sine(1000.0, rate, FFT_SIZE).iter().map(|s| *s as f32 / 32768.0).collect::<Vec<_>>());
+
+		let bar = (0..9).max_by(|a, b| spectrum.levels[*a].total_cmp(&spectrum.levels[*b])).unwrap();
+		let (level, peak) = (spectrum.levels[bar], spectrum.peaks[bar].level);
+
+		// Silence drops the bar a little at a time, while the peak holds for a couple of frames
+		spectrum.push(&[0.0; FFT_SIZE]);
+		assert!((spectrum.levels[bar] - (level - 0.1)).abs() < 1e-6);
+		spectrum.push(&[0.0; FFT_SIZE]);
+		assert_eq!(spectrum.peaks[bar].level, peak);
+
+		spectrum.push(&[0.0; FFT_SIZE]);
+		spectrum.push(&[0.0; FFT_SIZE]);
+		assert!(spectrum.peaks[bar].level < peak);
+	}
+}
@@ -1,5 +1,10 @@
+use crate::state::Pattern;
 use serde::Deserialize;
-use shared::error::Error as SharedError;
+use serial::SystemPort;
+use shared::{
+	error::Error as SharedError,
+	ipc::{Target, Urgency},
+};
 use std::{
 	error::Error,
 	fmt::{self, Display, Formatter},
@@ -11,6 +16,20 @@ pub struct Config
 {
 	pub left_matrix: Option<Matrix>,
 	pub right_matrix: Option<Matrix>,
+	pub idle: Option<Idle>,
+	#[serde(default)]
+	pub sensors: Vec<Sensor>,
+	pub notifications: Option<Notifications>,
+	#[cfg(feature = "http")]
+	pub http: Option<Http>,
+	#[cfg(feature = "mqtt")]
+	pub mqtt: Option<Mqtt>,
+	#[cfg(feature = "dbus")]
+	pub dbus: Option<Dbus>,
+	#[cfg(unix)]
+	pub user: Option<String>,
+	#[cfg(unix)]
+	pub group: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -19,12 +38,169 @@ pub struct Matrix
 	pub port: String,
 	pub baudrate: usize,
 	pub sleeping: bool,
+	pub startup: Option<Scene>,
+	#[serde(skip)]
+	pub handle: Option<SystemPort>,
+	// Last version read from the firmware, for when the matrix can't be asked without waking it
+	#[serde(skip)]
+	pub version: Option<Vec<u8>>,
+	// Whether the port was there the last time the keep alive thread looked
+	#[serde(skip)]
+	pub connected: bool,
+}
+
+/// What a matrix should display, such as when the daemon starts
+#[derive(Deserialize)]
+pub struct Scene
+{
+	pub brightness: Option<u8>,
+	pub image: Option<String>,
+	pub pattern: Option<Pattern>,
+	pub text: Option<String>,
+}
+
+/// What to do when the user stops using the computer
+#[derive(Deserialize)]
+pub struct Idle
+{
+	// Minutes without any activity before going idle
+	pub timeout: u64,
+	pub source: Option<ActivitySourceConfig>,
+	// The matrixes are put to sleep if there isn't a screensaver
+	pub screensaver: Option<Scene>,
+}
+
+/// Hardware monitoring input that the hwmon widget can show
+#[derive(Deserialize, Clone)]
+pub struct Sensor
+{
+	pub name: String,
+	// Name of the chip, such as "k10temp"
+	pub chip: String,
+	// Either the file the reading comes from, such as "temp1_input", or the label the chip gives it
+	pub input: Option<String>,
+	pub label: Option<String>,
+	// Range of the bar, in degrees Celsius, RPM, and so on
+	pub min: Option<f64>,
+	pub max: f64,
+	// Blink at or above this reading
+	pub danger: Option<f64>,
+	pub style: Option<SensorStyle>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorStyle
+{
+	Bar,
+	Number,
+}
+
+/// Desktop notifications forwarded by "ledcli notifications" from the user's session
+#[derive(Deserialize)]
+pub struct Notifications
+{
+	// Seconds to show each notification for, text that scrolls is shown for at least one pass
+	pub duration: Option<u64>,
+	// Defaults to both matrixes
+	pub matrix: Option<Target>,
+	// Defaults to the summary
+	pub show: Option<NotificationDisplay>,
+	// Notifications less urgent than this are ignored, defaults to low
+	pub urgency: Option<Urgency>,
+	// The first rule with a matching app takes priority over the settings above
+	#[serde(default)]
+	pub rules: Vec<NotificationRule>,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationRule
+{
+	// Compared without regard to case
+	pub app: String,
+	pub show: Option<NotificationDisplay>,
+	pub urgency: Option<Urgency>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationDisplay
+{
+	Summary,
+	App,
+	Icon,
+	// Ignore the notification entirely
+	Nothing,
+}
+
+/// Local HTTP API that mirrors the IPC commands
+#[cfg(feature = "http")]
+#[derive(Deserialize)]
+pub struct Http
+{
+	// Defaults to a port on localhost
+	pub address: Option<String>,
+	// Every request has to carry this as a bearer token
+	pub token: String,
+	// Web pages that may use the API, such as "http://localhost:3000"
+	#[serde(default)]
+	pub origins: Vec<String>,
+	// Listen on an address other machines can reach
+	#[serde(default)]
+	pub allow_remote: bool,
+}
+
+/// MQTT broker to take commands from and publish the state of the matrixes to
+#[cfg(feature = "mqtt")]
+#[derive(Deserialize)]
+pub struct Mqtt
+{
+	pub host: String,
+	pub port: Option<u16>,
+	pub client_id: Option<String>,
+	// Prefix for every topic
+	pub topic: Option<String>,
+	pub username: Option<String>,
+	pub password: Option<String>,
+}
+
+/// D-Bus service exposing each matrix as an object
+#[cfg(feature = "dbus")]
+#[derive(Deserialize)]
+pub struct Dbus
+{
+	// Defaults to the system bus
+	pub bus: Option<Bus>,
+	// Connects to a specific bus instead, such as a session bus when the environment doesn't say where it is
+	pub address: Option<String>,
+}
+
+#[cfg(feature = "dbus")]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bus
+{
+	System,
+	Session,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivitySourceConfig
+{
+	Input,
+	File(String),
 }
 
 #[derive(Debug)]
 pub enum ConfigError
 {
 	MissingMatrix,
+	ConflictingScene(String),
+	SensorInput(String),
+	IdleTimeout(u64),
+	UnknownUser(String),
+	UnknownGroup(String),
 }
 
 impl Error for ConfigError {}
@@ -36,6 +212,11 @@ impl Display for ConfigError
 		match self
 		{
 			Self::MissingMatrix => write!(f, "missing at least one matrix from the config file"),
+			Self::ConflictingScene(scene) => write!(f, "{} can only have one of an image, pattern, or text", scene),
+			Self::SensorInput(sensor) => write!(f, "sensor \"{}\" needs exactly one of an input or label", sensor),
+			Self::IdleTimeout(timeout) => write!(f, "an idle timeout of {} minutes is too long", timeout),
+			Self::UnknownUser(user) => write!(f, "user \"{}\" does not exist", user),
+			Self::UnknownGroup(group) => write!(f, "group \"{}\" does not exist", group),
 		}
 	}
 }
@@ -60,10 +241,77 @@ pub fn read_config(config: String) -> Result<Config, Box<dyn Error>>
 
 	if missing_both
 	{
-		Err(Box::new(ConfigError::MissingMatrix))
+		return Err(Box::new(ConfigError::MissingMatrix));
 	}
-	else
+
+	let scenes = [
+	              ("left_matrix.startup", config.left_matrix.as_ref().and_then(|m| m.startup.as_ref())),
+	              ("right_matrix.startup", config.right_matrix.as_ref().and_then(|m| m.startup.as_ref())),
+	              ("idle.screensaver", config.idle.as_ref().and_then(|i| i.screensaver.as_ref())),
+	];
+
+	for (name, scene) in scenes
 	{
-		Ok(config)
+		if let Some(scene) = scene
+		{
+			let content_count =
+				[scene.image.is_some(), scene.pattern.is_some(), scene.text.is_some()].iter().filter(|c| **c).count();
+
+			if content_count > 1
+			{
+				return Err(Box::new(ConfigError::ConflictingScene(name.to_string())));
+			}
+		}
+	}
+
+	if let Some(idle) = &config.idle
+	{
+		if idle.timeout.checked_mul(60).is_none()
+		{
+			return Err(Box::new(ConfigError::IdleTimeout(idle.timeout)));
+		}
+	}
+
+	for sensor in &config.sensors
+	{
+		if sensor.input.is_some() == sensor.label.is_some()
+		{
+			return Err(Box::new(ConfigError::SensorInput(sensor.name.clone())));
+		}
+	}
+
+	Ok(config)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		env::temp_dir,
+		fs::{remove_file, write},
+		process,
+	};
+
+	fn read(name: &str, contents: &str) -> Result<Config, Box<dyn Error>>
+	{
+		let path = temp_dir().join(format!("fw16-led-matrixd-{}-{}.toml", name, process::id()));
+		write(&path, format!("[left_matrix]\nport = \"/dev/null\"\nbaudrate = 115200\nsleeping = false\n{}", contents)).unwrap();
+		let config = read_config(path.to_string_lossy().to_string());
+		let _ = remove_file(&path);
+		config
+	}
+
+	#[test]
+	fn idle_timeouts_that_overflow_are_rejected()
+	{
+		let config = read("idle", "[idle]\ntimeout = 15\n").unwrap();
+		assert_eq!(config.idle.unwrap().timeout, 15);
+
+		assert!(read("idle-largest", &format!("[idle]\ntimeout = {}\n", u64::MAX / 60)).is_ok());
+
+		// TOML integers are signed, so this is the largest timeout that can be written
+		let error = read("idle-overflow", &format!("[idle]\ntimeout = {}\n", i64::MAX)).err().unwrap();
+		assert!(error.downcast_ref::<ConfigError>().is_some_and(|e| matches!(e, ConfigError::IdleTimeout(_))));
 	}
 }
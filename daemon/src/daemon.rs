@@ -1,33 +1,75 @@
+use crate::config::ConfigError;
+use log::{info, warn};
 use nix::{
+	errno::Errno,
 	libc::clearenv,
 	sys::{
 		resource::{getrlimit, Resource},
-		signal::{signal, sigprocmask, SigHandler, SigSet, SigmaskHow, Signal},
+		signal::{kill, signal, sigprocmask, SigHandler, SigSet, SigmaskHow, Signal},
 		stat::{umask, Mode},
 	},
-	unistd::{close, dup2, fork, getpid, pipe, setsid, ForkResult},
+	unistd::{
+		chown, close, dup2, fork, geteuid, getpid, initgroups, pipe, setgid, setgroups, setsid, setuid, ForkResult,
+		Group, Pid, User,
+	},
 };
 use std::{
-	env::set_current_dir,
+	env::{remove_var, set_current_dir, var},
 	error::Error,
-	fs::{remove_file, File},
-	io::{Read, Write},
-	os::fd::{AsRawFd, FromRawFd},
+	ffi::CString,
+	fs::{read_dir, read_to_string, remove_file, File},
+	io::{ErrorKind, Read, Write},
+	os::fd::{AsRawFd, FromRawFd, RawFd},
+	path::Path,
 	process::exit,
 };
 
+// The first file descriptor systemd passes when socket activating a service
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Get the listening socket passed in by systemd socket activation, if there is one
+/// This must be called before daemonizing, since the environment gets sanitized
+pub fn listen_fd() -> Option<RawFd>
+{
+	// LISTEN_PID guards against inheriting the variables from a parent that was activated instead of us
+	let pid: i32 = var("LISTEN_PID").ok()?.parse().ok()?;
+	let fds: i32 = var("LISTEN_FDS").ok()?.parse().ok()?;
+
+	// The socket belongs to the daemon now, so nothing it starts should think it was passed the socket too
+	for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"]
+	{
+		remove_var(name);
+	}
+
+	// Only the IPC socket is expected, so any extra sockets are ignored
+	if pid == getpid().as_raw() && fds >= 1
+	{
+		Some(SD_LISTEN_FDS_START)
+	}
+	else
+	{
+		None
+	}
+}
+
 // SysV compatible daemonize function
-pub fn daemonize<F>(pid_file: &str, daemon_main: F) -> Result<(), Box<dyn Error>>
+pub fn daemonize<F>(pid_file: &str, listen_fd: Option<RawFd>, daemon_main: F) -> Result<(), Box<dyn Error>>
 	where F: FnOnce()
 {
 	// Perform the main sequence for creating a sane daemon environment
 	unsafe {
 		// Step 1. Close all file descriptors that are not STDIN, STDOUT, or STDERR
+		// The socket passed in by systemd is kept open so that it can be listened on
 		let (num_fds, _) = getrlimit(Resource::RLIMIT_NOFILE)?;
 		if num_fds >= 3
 		{
 			for fd in 3..=num_fds
 			{
+				if Some(fd as i32) == listen_fd
+				{
+					continue;
+				}
+
 				// fd might not exist
 				let _ = close(fd as i32);
 			}
@@ -101,9 +143,26 @@ pub fn daemonize<F>(pid_file: &str, daemon_main: F) -> Result<(), Box<dyn Error>
 
 pub fn cleanup(pid_file: &str) -> Result<(), Box<dyn Error>>
 {
-	remove_file(pid_file)?;
+	match remove_file(pid_file)
+	{
+		// After dropping privileges the PID file usually can't be removed, so the next start replaces it instead
+		Err(e) if e.kind() == ErrorKind::PermissionDenied =>
+		{
+			info!("Leaving the PID file for the next start to replace, since it can't be removed without privileges");
+			Ok(())
+		},
+		r => Ok(r?),
+	}
+}
 
-	Ok(())
+/// Whether the process in a PID file is gone, a file that can't be read or parsed is stale too
+fn is_stale(pid_file: &str) -> bool
+{
+	match read_to_string(pid_file).ok().and_then(|pid| pid.trim().parse().ok())
+	{
+		Some(pid) => kill(Pid::from_raw(pid), None) == Err(Errno::ESRCH),
+		None => true,
+	}
 }
 
 unsafe fn daemonize_part2(pid_file: &str) -> Result<(), Box<dyn Error>>
@@ -134,12 +193,85 @@ unsafe fn daemonize_part2(pid_file: &str) -> Result<(), Box<dyn Error>>
 	set_current_dir("/")?;
 
 	// Step 12. Race-free PID file
-	let mut pid_file = File::create_new(pid_file)?;
+	// A PID file left behind by a daemon that isn't running anymore is replaced, since it can't always be removed on exit
+	let mut pid_file = match File::create_new(pid_file)
+	{
+		Err(e) if e.kind() == ErrorKind::AlreadyExists && is_stale(pid_file) =>
+		{
+			remove_file(pid_file)?;
+			File::create_new(pid_file)?
+		},
+		f => f?,
+	};
 	pid_file.write_all(format!("{}\n", getpid().as_raw()).as_bytes())?;
 	drop(pid_file);
 
 	// Step 13. Adjust privileges
-	// This isn't done because root is needed to interact with the serial ports on some systems
+	// This is deferred to drop_privileges, since root is needed to open the serial ports on some systems
+
+	Ok(())
+}
+
+/// Switch to an unprivileged user and/or group, keeping any handles that are already open
+/// The directory and everything in it is handed over first, so that files in it can still be replaced afterwards
+pub fn drop_privileges(user: Option<&str>, group: Option<&str>, directory: Option<&Path>)
+                       -> Result<(), Box<dyn Error>>
+{
+	if user.is_none() && group.is_none()
+	{
+		return Ok(());
+	}
+
+	if !geteuid().is_root()
+	{
+		warn!("Not dropping privileges since the daemon isn't running as root");
+		return Ok(());
+	}
+
+	let user = match user
+	{
+		Some(name) => Some(User::from_name(name)?.ok_or(ConfigError::UnknownUser(name.to_string()))?),
+		None => None,
+	};
+
+	// Fall back to the user's primary group if a group wasn't given
+	let gid = match group
+	{
+		Some(name) => Some(Group::from_name(name)?.ok_or(ConfigError::UnknownGroup(name.to_string()))?.gid),
+		None => user.as_ref().map(|u| u.gid),
+	};
+
+	if let Some(directory) = directory
+	{
+		let (uid, gid) = (user.as_ref().map(|u| u.uid), gid);
+		chown(directory, uid, gid)?;
+		for entry in read_dir(directory)?
+		{
+			chown(&entry?.path(), uid, gid)?;
+		}
+
+		info!("Handed \"{}\" over to the unprivileged user", directory.display());
+	}
+
+	// The groups must be changed first, since they can't be changed after the user is
+	if let Some(gid) = gid
+	{
+		// Keep the user's supplementary groups so that udev permitted groups can still reopen the ports
+		match user
+		{
+			Some(ref u) => initgroups(&CString::new(u.name.as_str())?, gid)?,
+			None => setgroups(&[gid])?,
+		}
+
+		setgid(gid)?;
+		info!("Switched to group {}", gid);
+	}
+
+	if let Some(u) = user
+	{
+		setuid(u.uid)?;
+		info!("Switched to user \"{}\"", u.name);
+	}
 
 	Ok(())
 }
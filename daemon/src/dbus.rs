@@ -0,0 +1,315 @@
+use crate::{
+	config::{Bus, Dbus},
+	handler::{run, HandlerData},
+	state::{pattern_command, MatrixState, Pattern},
+	Matrix,
+};
+use log::{info, warn};
+use shared::{
+	error::Error as SharedError,
+	image::ImageOptions,
+	ipc::{Event, IpcCommand, Side, Target},
+	version::Version,
+};
+use std::{
+	error::Error,
+	sync::{mpsc::channel, Arc, Mutex},
+	thread::spawn,
+};
+use zbus::{
+	block_on,
+	blocking::{connection::Builder, Connection},
+	fdo, interface,
+};
+
+const BUS_NAME: &str = "io.github.NukingDragons.LedMatrix1";
+const LEFT_PATH: &str = "/io/github/NukingDragons/LedMatrix1/left";
+const RIGHT_PATH: &str = "/io/github/NukingDragons/LedMatrix1/right";
+
+/// One of the matrixes, exported as its own object
+struct MatrixObject
+{
+	side: Side,
+	data: Arc<Mutex<HandlerData>>,
+	announced: Arc<Mutex<Announced>>,
+}
+
+// Property values that D-Bus already knows about, since zbus announces the ones that get set through it
+#[derive(Default)]
+struct Announced
+{
+	brightness: Option<u8>,
+	sleeping: Option<bool>,
+	scrolling: Option<bool>,
+}
+
+impl Announced
+{
+	/// Remember the value from an event, and whether it's news to D-Bus
+	fn update(&mut self, event: &Event) -> bool
+	{
+		match *event
+		{
+			Event::BrightnessChanged { brightness, .. } => self.brightness.replace(brightness) != Some(brightness),
+			Event::SleepChanged { sleeping, .. } => self.sleeping.replace(sleeping) != Some(sleeping),
+			Event::ScrollChanged { scrolling, .. } => self.scrolling.replace(scrolling) != Some(scrolling),
+			_ => true,
+		}
+	}
+}
+
+impl MatrixObject
+{
+	/// Read what's already known about the matrix, without asking it anything
+	fn known<T>(&self, read: impl FnOnce(&Matrix, &MatrixState) -> T) -> fdo::Result<T>
+	{
+		let data = self.data.lock().unwrap();
+		let (port, state) = match self.side
+		{
+			Side::Left => (&data.left_port, &data.state.left),
+			Side::Right => (&data.right_port, &data.state.right),
+		};
+
+		port.as_ref()
+		    .map(|port| read(port, state))
+		    .ok_or_else(|| fdo::Error::Failed("the matrix isn't in the config".to_string()))
+	}
+
+	/// Set a property, which zbus announces once it's set
+	fn set<T>(&self, command: IpcCommand, value: T, field: impl Fn(&mut Announced) -> &mut Option<T>)
+	          -> fdo::Result<()>
+	{
+		// The daemon's event for the change can arrive before this returns, so it has to be known up front
+		*field(&mut self.announced.lock().unwrap()) = Some(value);
+
+		let result = self.run(command);
+		if result.is_err()
+		{
+			*field(&mut self.announced.lock().unwrap()) = None;
+		}
+
+		result
+	}
+
+	fn run(&self, command: IpcCommand) -> fdo::Result<()>
+	{
+		// Anything other than the handler failing means the arguments were wrong
+		run(command, &self.data).map(|_| ()).map_err(|e| match e
+		                                    {
+			                                    SharedError::Handler(e) => fdo::Error::Failed(e),
+		                                        e => fdo::Error::InvalidArgs(e.to_string()),
+		                                    })
+	}
+
+	fn sides(&self) -> (bool, bool)
+	{
+		(matches!(self.side, Side::Left), matches!(self.side, Side::Right))
+	}
+}
+
+#[interface(name = "io.github.NukingDragons.LedMatrix1.Matrix")]
+impl MatrixObject
+{
+	/// -1 when the brightness isn't known yet, such as when the matrix has slept since the daemon started
+	#[zbus(property)]
+	fn brightness(&self) -> fdo::Result<i16>
+	{
+		self.known(|_, state| state.brightness.map_or(-1, i16::from))
+	}
+
+	#[zbus(property)]
+	fn set_brightness(&mut self, brightness: i16) -> fdo::Result<()>
+	{
+		let brightness = u8::try_from(brightness).map_err(|_| {
+			                                         fdo::Error::InvalidArgs("the brightness goes from 0 to 255".to_string())
+		                                         })?;
+		let (left, right) = self.sides();
+
+		self.set(IpcCommand::SetBrightness(left.then_some(brightness), right.then_some(brightness)), brightness, |a| {
+			    &mut a.brightness
+		    })
+	}
+
+	#[zbus(property)]
+	fn sleeping(&self) -> fdo::Result<bool>
+	{
+		self.known(|port, _| port.sleeping)
+	}
+
+	#[zbus(property)]
+	fn set_sleeping(&mut self, sleeping: bool) -> fdo::Result<()>
+	{
+		let (left, right) = self.sides();
+		self.set(IpcCommand::SetSleep(left.then_some(sleeping), right.then_some(sleeping)), sleeping, |a| {
+			    &mut a.sleeping
+		    })
+	}
+
+	#[zbus(property)]
+	fn scrolling(&self) -> fdo::Result<bool>
+	{
+		self.known(|_, state| state.scroll.unwrap_or_default())
+	}
+
+	#[zbus(property)]
+	fn set_scrolling(&mut self, scrolling: bool) -> fdo::Result<()>
+	{
+		let (left, right) = self.sides();
+		self.set(IpcCommand::SetAnimate(left.then_some(scrolling), right.then_some(scrolling)), scrolling, |a| {
+			    &mut a.scrolling
+		    })
+	}
+
+	/// Empty when the firmware hasn't been asked yet
+	#[zbus(property)]
+	fn version(&self) -> fdo::Result<String>
+	{
+		self.known(|port, _| match port.version.clone().map(Version::try_from)
+		    {
+			    Some(Ok(version)) => version.to_string(),
+		        _ => String::new(),
+		    })
+	}
+
+	/// Have the daemon read an image file and display it
+	fn render(&self, path: String) -> fdo::Result<()>
+	{
+		let target = match self.side
+		{
+			Side::Left => Target::Left,
+			Side::Right => Target::Right,
+		};
+
+		self.run(IpcCommand::RenderFile { path, target, options: ImageOptions::default() })
+	}
+
+	/// Either a percentage or the name of the pattern, such as "double_gradient"
+	fn pattern(&self, pattern: String) -> fdo::Result<()>
+	{
+		let pattern = pattern.parse::<Pattern>().map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+		let (left, right) = self.sides();
+
+		self.run(pattern_command(&pattern, left, right))
+	}
+
+	fn text(&self, text: String) -> fdo::Result<()>
+	{
+		let (left, right) = self.sides();
+		self.run(IpcCommand::RenderText(left.then(|| text.clone()), right.then_some(text)))
+	}
+}
+
+/// Connect to the bus, this happens before dropping privileges since the system bus knows the daemon by who connected
+pub fn connect(dbus: &Dbus) -> Result<Connection, Box<dyn Error>>
+{
+	let builder = match (&dbus.address, dbus.bus.as_ref().unwrap_or(&Bus::System))
+	{
+		(Some(address), _) => Builder::address(address.as_str())?,
+		(None, Bus::System) => Builder::system()?,
+		(None, Bus::Session) => Builder::session()?,
+	};
+
+	info!("Connecting to D-Bus");
+	Ok(builder.build()?)
+}
+
+/// Export an object for each matrix, then claim the name once they're ready to be used
+pub fn start_dbus_thread(connection: Connection, data: Arc<Mutex<HandlerData>>) -> Result<(), Box<dyn Error>>
+{
+	let (left, right) = {
+		let data = data.lock().unwrap();
+		(data.left_port.is_some(), data.right_port.is_some())
+	};
+
+	let announced = [Arc::new(Mutex::new(Announced::default())), Arc::new(Mutex::new(Announced::default()))];
+	for (side, path, present) in [(Side::Left, LEFT_PATH, left), (Side::Right, RIGHT_PATH, right)]
+	{
+		if present
+		{
+			connection.object_server().at(
+			                               path,
+			                               MatrixObject { side,
+			                                              data: Arc::clone(&data),
+			                                              announced: Arc::clone(&announced[side as usize]), },
+			)?;
+		}
+	}
+
+	connection.request_name(BUS_NAME)?;
+	info!("Serving {} on D-Bus", BUS_NAME);
+
+	let (sender, receiver) = channel();
+	data.lock().unwrap().subscribe(sender);
+
+	// The properties that change without going through D-Bus are announced from the daemon's events
+	spawn(move || {
+		for event in receiver
+		{
+			let side = match event
+			{
+				Event::BrightnessChanged { matrix, .. }
+				| Event::SleepChanged { matrix, .. }
+				| Event::ScrollChanged { matrix, .. }
+				| Event::Connected { matrix }
+				| Event::Disconnected { matrix } => matrix,
+				_ => continue,
+			};
+
+			// Anything set through D-Bus was already announced by zbus
+			if !announced[side as usize].lock().unwrap().update(&event)
+			{
+				continue;
+			}
+
+			if let Err(e) = announce(&connection, side, &event)
+			{
+				warn!("Failed to announce a property change on D-Bus with error: {}", e);
+			}
+		}
+	});
+
+	Ok(())
+}
+
+fn announce(connection: &Connection, side: Side, event: &Event) -> zbus::Result<()>
+{
+	let path = match side
+	{
+		Side::Left => LEFT_PATH,
+		Side::Right => RIGHT_PATH,
+	};
+
+	let iface = connection.object_server().interface::<_, MatrixObject>(path)?;
+	let emitter = iface.signal_emitter();
+	let object = iface.get();
+
+	block_on(async {
+		match event
+		{
+			Event::BrightnessChanged { .. } => object.brightness_changed(emitter).await,
+			Event::SleepChanged { .. } => object.sleeping_changed(emitter).await,
+			Event::ScrollChanged { .. } => object.scrolling_changed(emitter).await,
+			// Reconnecting means the firmware version may have been read again
+			_ => object.version_changed(emitter).await,
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn changes_made_over_dbus_are_only_announced_once()
+	{
+		// Setting the property remembers it before the daemon's event comes through
+		let mut announced = Announced { brightness: Some(50), ..Default::default() };
+		assert!(!announced.update(&Event::BrightnessChanged { matrix: Side::Left, brightness: 50 }));
+
+		// Anything else that changes it still gets announced
+		assert!(announced.update(&Event::BrightnessChanged { matrix: Side::Left, brightness: 80 }));
+		assert!(announced.update(&Event::ScrollChanged { matrix: Side::Left, scrolling: true }));
+		assert!(!announced.update(&Event::ScrollChanged { matrix: Side::Left, scrolling: true }));
+	}
+}
@@ -1,12 +1,54 @@
-use crate::{matrix::*, Matrix};
+use crate::{
+	config::Sensor,
+	matrix::*,
+	notifications::Notification,
+	state::{Content, MatrixState, Pattern, State},
+	widget::{generator, TimerControl},
+	Matrix,
+};
 use log::{error, info, warn};
-use shared::ipc::*;
+use shared::{image::decode_image, ipc::*, text::render_text, widget::Widget};
 use std::{
+	collections::HashMap,
 	error::Error,
-	sync::{Arc, Mutex},
+	fs::{metadata, File, OpenOptions},
+	io::BufReader,
+	sync::{mpsc::Sender, Arc, Mutex},
 	thread::{sleep, spawn},
+	time::Instant,
 };
 
+/// How far along an animation is
+#[derive(Default, Clone, Copy)]
+pub struct Playback
+{
+	pub frame: Option<usize>,
+	pub loops: u64,
+}
+
+impl Playback
+{
+	fn advance(&mut self, frame: usize)
+	{
+		if frame == 0 && self.frame.is_some()
+		{
+			self.loops += 1;
+		}
+
+		self.frame = Some(frame);
+	}
+}
+
+// What subscribers were last told about a matrix, so that only changes get sent out
+#[derive(Default, Clone, Copy, PartialEq)]
+struct Reported
+{
+	connected: bool,
+	sleeping: bool,
+	brightness: Option<u8>,
+	scroll: Option<bool>,
+}
+
 pub struct HandlerData
 {
 	pub left_port: Option<Matrix>,
@@ -14,13 +56,78 @@ pub struct HandlerData
 	left_thread: Option<Arc<()>>,
 	right_thread: Option<Arc<()>>,
 	pair_thread: Option<Arc<()>>,
+	left_playback: Playback,
+	right_playback: Playback,
+	pair_playback: Playback,
+	pub state: State,
+	// The state has changed since it was last saved
+	unsaved: bool,
+	pub idle: bool,
+	// Something temporary is displayed, such as a notification
+	pub interrupted: bool,
+	// Something temporary is being put up, and the state gets put back once it's displayed
+	pub temporary: bool,
+	pub sensors: Vec<Sensor>,
+	pub notifications: Option<Sender<Notification>>,
+	// Keyed by the thread a timer is drawn on, so that the left, right, and pair timers are controlled separately
+	timers: HashMap<Target, Arc<Mutex<TimerControl>>>,
+	started: Instant,
+	subscribers: Vec<(u64, Sender<Event>)>,
+	next_subscriber: u64,
+	left_reported: Reported,
+	right_reported: Reported,
 }
 
 impl HandlerData
 {
 	pub fn new(left_port: Option<Matrix>, right_port: Option<Matrix>) -> Self
 	{
-		HandlerData { left_port, right_port, left_thread: None, right_thread: None, pair_thread: None }
+		HandlerData { left_port,
+		              right_port,
+		              left_thread: None,
+		              right_thread: None,
+		              pair_thread: None,
+		              left_playback: Playback::default(),
+		              right_playback: Playback::default(),
+		              pair_playback: Playback::default(),
+		              state: State::default(),
+		              unsaved: false,
+		              idle: false,
+		              interrupted: false,
+		              temporary: false,
+		              sensors: vec![],
+		              notifications: None,
+		              timers: HashMap::new(),
+		              started: Instant::now(),
+		              subscribers: vec![],
+		              next_subscriber: 0,
+		              left_reported: Reported::default(),
+		              right_reported: Reported::default() }
+	}
+
+	/// Let the state thread know that the state needs saving
+	pub fn save_state(&mut self)
+	{
+		// Matrixes put to sleep while idle should still wake up after a restart
+		if !self.idle && !self.interrupted
+		{
+			self.state.left.sleeping = self.left_port.as_ref().is_some_and(|p| p.sleeping);
+			self.state.right.sleeping = self.right_port.as_ref().is_some_and(|p| p.sleeping);
+		}
+
+		self.unsaved = true;
+	}
+
+	/// The state if it needs saving, which waits until anything temporary has been put up
+	pub fn take_unsaved(&mut self) -> Option<State>
+	{
+		if !self.unsaved || self.temporary
+		{
+			return None;
+		}
+
+		self.unsaved = false;
+		Some(self.state.clone())
 	}
 
 	pub fn get_ports(&mut self) -> (&mut Option<Matrix>, &mut Option<Matrix>)
@@ -28,25 +135,129 @@ impl HandlerData
 		(&mut self.left_port, &mut self.right_port)
 	}
 
+	/// Start sending events to a subscriber, the returned ID is used to stop again
+	pub fn subscribe(&mut self, subscriber: Sender<Event>) -> u64
+	{
+		let id = self.next_subscriber;
+		self.next_subscriber += 1;
+		self.subscribers.push((id, subscriber));
+
+		id
+	}
+
+	pub fn unsubscribe(&mut self, id: u64)
+	{
+		self.subscribers.retain(|(s, _)| *s != id);
+	}
+
+	/// Send an event to every subscriber, forgetting about the ones that have gone away
+	pub fn emit(&mut self, event: Event)
+	{
+		self.subscribers.retain(|(_, s)| s.send(event.clone()).is_ok());
+	}
+
+	/// Let the subscribers know about anything that has changed since the last time this was called
+	pub fn notify(&mut self)
+	{
+		let (left_changed, right_changed) = self.state.take_changed();
+
+		for (matrix, changed) in [(Side::Left, left_changed), (Side::Right, right_changed)]
+		{
+			let (port, state, reported) = match matrix
+			{
+				Side::Left => (&self.left_port, &self.state.left, &mut self.left_reported),
+				Side::Right => (&self.right_port, &self.state.right, &mut self.right_reported),
+			};
+
+			let Some(port) = port
+			else
+			{
+				continue;
+			};
+
+			let current = Reported { connected: port.connected,
+			                         sleeping: port.sleeping,
+			                         brightness: state.brightness,
+			                         scroll: state.scroll };
+			let previous = *reported;
+			*reported = current;
+
+			let mut events: Vec<Event> = vec![];
+
+			if current.connected != previous.connected
+			{
+				events.push(match current.connected
+				      {
+					      true => Event::Connected { matrix },
+				          false => Event::Disconnected { matrix },
+				      });
+			}
+
+			if current.sleeping != previous.sleeping
+			{
+				events.push(Event::SleepChanged { matrix, sleeping: current.sleeping });
+			}
+
+			if let Some(brightness) = current.brightness.filter(|_| current.brightness != previous.brightness)
+			{
+				events.push(Event::BrightnessChanged { matrix, brightness });
+			}
+
+			if let Some(scrolling) = current.scroll.filter(|_| current.scroll != previous.scroll)
+			{
+				events.push(Event::ScrollChanged { matrix, scrolling });
+			}
+
+			if changed
+			{
+				let content = self.state.pair.as_ref().or(state.content.as_ref()).map(|c| c.kind());
+				events.push(Event::ContentChanged { matrix, content });
+			}
+
+			for event in events
+			{
+				self.emit(event);
+			}
+		}
+	}
+
+	/// Stop the animations on the matrixes that something else is about to be displayed on
 	pub fn kill_threads(&mut self, keep_left_alive: bool, keep_right_alive: bool)
 	{
-		self.pair_thread = None;
+		self.stop_threads(keep_left_alive, keep_right_alive);
+	}
+
+	/// Stop the animations that came to an end by themselves, which subscribers are told about
+	pub fn finish_threads(&mut self, keep_left_alive: bool, keep_right_alive: bool)
+	{
+		let (left, right) = self.stop_threads(keep_left_alive, keep_right_alive);
 
-		if !keep_left_alive
+		if left
 		{
-			self.left_thread = None;
+			self.emit(Event::AnimationFinished { matrix: Side::Left });
 		}
 
-		if !keep_right_alive
+		if right
 		{
-			self.right_thread = None;
+			self.emit(Event::AnimationFinished { matrix: Side::Right });
 		}
 	}
 
+	// Which matrixes had an animation running
+	fn stop_threads(&mut self, keep_left_alive: bool, keep_right_alive: bool) -> (bool, bool)
+	{
+		let pair = self.pair_thread.take().is_some();
+		let left = !keep_left_alive && self.left_thread.take().is_some();
+		let right = !keep_right_alive && self.right_thread.take().is_some();
+
+		(pair || left, pair || right)
+	}
+
 	pub fn start_pair_thread<F: FnMut() + Send + 'static>(&mut self, mut worker: F)
 	{
 		let arc = Arc::new(());
 		self.pair_thread = Some(Arc::clone(&arc));
+		self.pair_playback = Playback::default();
 
 		let clone = Arc::clone(&arc);
 		spawn(move || {
@@ -61,6 +272,7 @@ impl HandlerData
 	{
 		let arc = Arc::new(());
 		self.left_thread = Some(Arc::clone(&arc));
+		self.left_playback = Playback::default();
 
 		let clone = Arc::clone(&arc);
 		spawn(move || {
@@ -75,6 +287,7 @@ impl HandlerData
 	{
 		let arc = Arc::new(());
 		self.right_thread = Some(Arc::clone(&arc));
+		self.right_playback = Playback::default();
 
 		let clone = Arc::clone(&arc);
 		spawn(move || {
@@ -99,6 +312,72 @@ impl HandlerData
 	{
 		self.pair_thread.is_some()
 	}
+
+	pub fn status(&self) -> Status
+	{
+		let left_playback = match (self.is_pair_animated(), self.is_left_animated())
+		{
+			(true, _) => Some(self.pair_playback),
+			(false, true) => Some(self.left_playback),
+			(false, false) => None,
+		};
+
+		let right_playback = match (self.is_pair_animated(), self.is_right_animated())
+		{
+			(true, _) => Some(self.pair_playback),
+			(false, true) => Some(self.right_playback),
+			(false, false) => None,
+		};
+
+		Status { uptime: self.started.elapsed(),
+		         left: matrix_status(&self.left_port, &self.state.left, &self.state.pair, left_playback),
+		         right: matrix_status(&self.right_port, &self.state.right, &self.state.pair, right_playback) }
+	}
+}
+
+fn matrix_status(matrix: &Option<Matrix>,
+                 state: &MatrixState,
+                 pair: &Option<Content>,
+                 playback: Option<Playback>)
+                 -> Option<MatrixStatus>
+{
+	let matrix = matrix.as_ref()?;
+
+	// Everything here is already known, so that asking for the status never waits on the matrixes
+	Some(MatrixStatus { port: matrix.port.clone(),
+	                    connected: matrix.connected,
+	                    version: matrix.version.clone(),
+	                    brightness: state.brightness,
+	                    sleeping: matrix.sleeping,
+	                    scroll: state.scroll,
+	                    content: pair.as_ref().or(state.content.as_ref()).map(|c| c.kind()),
+	                    pair: pair.is_some(),
+	                    frame: playback.and_then(|p| p.frame),
+	                    loops: playback.map(|p| p.loops) })
+}
+
+/// Keep an awake matrix from timing out, and fill in what isn't known about it yet while at it
+pub fn keep_alive(matrix: &mut Matrix, state: &mut MatrixState)
+{
+	if matrix.sleeping
+	{
+		return;
+	}
+
+	if let Ok(v) = version(matrix)
+	{
+		matrix.version = Some(v);
+	}
+
+	if state.brightness.is_none()
+	{
+		state.brightness = get_brightness(matrix).ok();
+	}
+
+	if state.scroll.is_none()
+	{
+		state.scroll = is_scrolling(matrix).ok();
+	}
 }
 
 macro_rules! send_left_cmd_r {
@@ -110,8 +389,7 @@ macro_rules! send_left_cmd_r {
 		}
 		else
 		{
-			warn!("Can't send a command on the left matrix since the left matrix hasn't been defined");
-			None
+			return Err("can't send a command on the left matrix since the left matrix hasn't been defined".into());
 		}
 	};
 }
@@ -125,8 +403,7 @@ macro_rules! send_right_cmd_r {
 		}
 		else
 		{
-			warn!("Can't send a command on the right matrix since the right matrix hasn't been defined");
-			None
+			return Err("can't send a command on the right matrix since the right matrix hasn't been defined".into());
 		}
 	};
 }
@@ -140,7 +417,7 @@ macro_rules! send_left_cmd {
 		}
 		else
 		{
-			warn!("Can't send a command on the left matrix since the left matrix hasn't been defined");
+			return Err("can't send a command on the left matrix since the left matrix hasn't been defined".into());
 		}
 	};
 }
@@ -154,7 +431,7 @@ macro_rules! send_right_cmd {
 		}
 		else
 		{
-			warn!("Can't send a command on the right matrix since the right matrix hasn't been defined");
+			return Err("can't send a command on the right matrix since the right matrix hasn't been defined".into());
 		}
 	};
 }
@@ -169,20 +446,87 @@ macro_rules! send_pair_cmd {
 		}
 		else
 		{
-			warn!("Can't send a pair command due to missing at least one matrix from the config");
+			return Err("can't send a pair command due to missing at least one matrix from the config".into());
 		}
 	};
 }
 
+/// Open an image that a client asked for, only regular files are read so that something like a FIFO can't hang the daemon
+fn open_image(path: &str) -> Result<File, Box<dyn Error>>
+{
+	if !metadata(path)?.is_file()
+	{
+		return Err(format!("\"{}\" isn't a regular file", path).into());
+	}
+
+	// The path could have been swapped out since it was checked, so make sure opening it can't block either
+	let mut options = OpenOptions::new();
+	options.read(true);
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, nix::fcntl::OFlag::O_NONBLOCK.bits());
+
+	let file = options.open(path)?;
+	if !file.metadata()?.is_file()
+	{
+		return Err(format!("\"{}\" isn't a regular file", path).into());
+	}
+
+	Ok(file)
+}
+
+/// Validate and carry out a command that came from somewhere other than the IPC socket, such as the HTTP API
+#[cfg(any(feature = "http", feature = "mqtt", feature = "dbus"))]
+pub fn run(command: IpcCommand, data: &Arc<Mutex<HandlerData>>) -> Result<IpcResponse, shared::error::Error>
+{
+	command.validate()?;
+
+	let mut response: Option<IpcResponse> = None;
+	match handler(command, &mut response, Arc::clone(data))
+	{
+		Ok(_) => Ok(response.unwrap_or(IpcResponse::Done)),
+		Err(e) => Err(shared::error::Error::Handler(e.to_string())),
+	}
+}
+
 pub fn handler(command: IpcCommand,
                response: &mut Option<IpcResponse>,
                handler_data: Arc<Mutex<HandlerData>>)
                -> Result<bool, Box<dyn Error>>
+{
+	let renders = !command.needs_response();
+	let result = handle(command, response, Arc::clone(&handler_data));
+
+	let mut data = handler_data.lock().unwrap();
+	if let Err(e) = &result
+	{
+		if renders
+		{
+			data.emit(Event::RenderError { message: e.to_string() });
+		}
+	}
+
+	data.notify();
+
+	result
+}
+
+fn handle(command: IpcCommand,
+          response: &mut Option<IpcResponse>,
+          handler_data: Arc<Mutex<HandlerData>>)
+          -> Result<bool, Box<dyn Error>>
 {
 	let mut data = handler_data.lock().unwrap();
 
+	// Only the commands that don't respond can change what's displayed
+	let changes_state = !command.needs_response();
+
 	match command
 	{
+		// The validate function has already rejected clients that are too old
+		IpcCommand::Hello(_, _) =>
+		{
+			*response = Some(IpcResponse::Hello(PROTOCOL_VERSION, capabilities()));
+		},
 		// Commands the require responses
 		// GetBrightness doesn't need to kill render threads
 		IpcCommand::GetBrightness(left, right) =>
@@ -275,6 +619,12 @@ pub fn handler(command: IpcCommand,
 
 			*response = Some(IpcResponse::Version(left_res, right_res));
 		},
+		IpcCommand::Status =>
+		{
+			*response = Some(IpcResponse::Status(Box::new(data.status())));
+		},
+		// Subscribers need to hang on to the connection, so the IPC listener takes care of them
+		IpcCommand::Subscribe => return Err("subscribing is only possible over the IPC socket".into()),
 		// Commands that don't need responses
 		// Brightness doesn't need to kill render threads
 		IpcCommand::SetBrightness(left, right) =>
@@ -282,11 +632,13 @@ pub fn handler(command: IpcCommand,
 			if let Some(b) = left
 			{
 				send_left_cmd!(data, |left_port| -> Result<(), Box<dyn Error>> { set_brightness(left_port, b) });
+				data.state.left.brightness = Some(b);
 			}
 
 			if let Some(b) = right
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { set_brightness(right_port, b) });
+				data.state.right.brightness = Some(b);
 			}
 		},
 		// Also update the Matrix sleeping bool so that sleeping works as intended
@@ -299,6 +651,7 @@ pub fn handler(command: IpcCommand,
 					left_port.sleeping = s;
 					set_sleep(left_port, s)
 				});
+				data.state.left.sleeping = s;
 			}
 
 			if let Some(s) = right
@@ -308,6 +661,7 @@ pub fn handler(command: IpcCommand,
 					right_port.sleeping = s;
 					set_sleep(right_port, s)
 				});
+				data.state.right.sleeping = s;
 			}
 		},
 		IpcCommand::SetAnimate(left, right) =>
@@ -316,12 +670,14 @@ pub fn handler(command: IpcCommand,
 			{
 				data.kill_threads(false, true);
 				send_left_cmd!(data, |left_port| -> Result<(), Box<dyn Error>> { set_scroll(left_port, s) });
+				data.state.left.scroll = Some(s);
 			}
 
 			if let Some(s) = right
 			{
 				data.kill_threads(true, false);
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { set_scroll(right_port, s) });
+				data.state.right.scroll = Some(s);
 			}
 		},
 		IpcCommand::PatternPercentage(left, right) =>
@@ -330,12 +686,14 @@ pub fn handler(command: IpcCommand,
 			{
 				data.kill_threads(false, true);
 				send_left_cmd!(data, |left_port| -> Result<(), Box<dyn Error>> { render_percentage(left_port, p) });
+				data.state.set_pattern(true, false, Pattern::Percentage(p));
 			}
 
 			if let Some(p) = right
 			{
 				data.kill_threads(true, false);
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_percentage(right_port, p) });
+				data.state.set_pattern(false, true, Pattern::Percentage(p));
 			}
 		},
 		IpcCommand::PatternGradient(left, right) =>
@@ -351,6 +709,8 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_gradient(right_port) });
 			}
+
+			data.state.set_pattern(left, right, Pattern::Gradient);
 		},
 		IpcCommand::PatternDoubleGradient(left, right) =>
 		{
@@ -367,6 +727,8 @@ pub fn handler(command: IpcCommand,
 					render_double_gradient(right_port)
 				});
 			}
+
+			data.state.set_pattern(left, right, Pattern::DoubleGradient);
 		},
 		IpcCommand::PatternLotusHorizontal(left, right) =>
 		{
@@ -383,6 +745,8 @@ pub fn handler(command: IpcCommand,
 					render_lotus_horizontal(right_port)
 				});
 			}
+
+			data.state.set_pattern(left, right, Pattern::LotusHorizontal);
 		},
 		IpcCommand::PatternLotusVertical(left, right) =>
 		{
@@ -397,6 +761,8 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_lotus_vertical(right_port) });
 			}
+
+			data.state.set_pattern(left, right, Pattern::LotusVertical);
 		},
 		IpcCommand::PatternZigzag(left, right) =>
 		{
@@ -411,6 +777,8 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_zigzag(right_port) });
 			}
+
+			data.state.set_pattern(left, right, Pattern::Zigzag);
 		},
 		IpcCommand::PatternFullbright(left, right) =>
 		{
@@ -425,6 +793,8 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_fullbright(right_port) });
 			}
+
+			data.state.set_pattern(left, right, Pattern::Fullbright);
 		},
 		IpcCommand::PatternPanic(left, right) =>
 		{
@@ -439,6 +809,8 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { render_panic(right_port) });
 			}
+
+			data.state.set_pattern(left, right, Pattern::Panic);
 		},
 		IpcCommand::Bootloader(left, right) =>
 		{
@@ -477,6 +849,7 @@ pub fn handler(command: IpcCommand,
 				// The validate function ensures this won't fail here
 				let bw: [u8; 39] = bw.try_into().unwrap();
 				send_left_cmd!(data, |left_port| -> Result<(), Box<dyn Error>> { draw_bw(left_port, &bw) });
+				data.state.set_content(Some(Content::BW(bw.to_vec())), None);
 			}
 
 			if let Some(bw) = right
@@ -486,6 +859,7 @@ pub fn handler(command: IpcCommand,
 				// The validate function ensures this won't fail here
 				let bw: [u8; 39] = bw.try_into().unwrap();
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { draw_bw(right_port, &bw) });
+				data.state.set_content(None, Some(Content::BW(bw.to_vec())));
 			}
 		},
 		IpcCommand::StageCol(left, right) =>
@@ -495,6 +869,7 @@ pub fn handler(command: IpcCommand,
 				// The validate function ensures this won't fail here
 				let vals: [u8; 34] = vals.try_into().unwrap();
 				send_left_cmd!(data, |left_port| -> Result<(), Box<dyn Error>> { stage_column(left_port, col, &vals) });
+				data.state.left.stage_column(col, &vals);
 			}
 
 			if let Some((col, vals)) = right
@@ -504,6 +879,7 @@ pub fn handler(command: IpcCommand,
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> {
 					stage_column(right_port, col, &vals)
 				});
+				data.state.right.stage_column(col, &vals);
 			}
 		},
 		IpcCommand::FlushCols(left, right) =>
@@ -519,12 +895,15 @@ pub fn handler(command: IpcCommand,
 			{
 				send_right_cmd!(data, |right_port| -> Result<(), Box<dyn Error>> { flush_columns(right_port) });
 			}
+
+			data.state.flush_columns(left, right);
 		},
 		IpcCommand::RenderSingle(left, right) =>
 		{
 			if let Some(s) = left
 			{
 				data.kill_threads(false, true);
+				let content = Content::Frames(s.clone());
 				if s.len() == 1
 				{
 					// The validate function ensures this won't fail here
@@ -533,11 +912,16 @@ pub fn handler(command: IpcCommand,
 				}
 				else
 				{
+					if data.left_port.is_none()
+					{
+						return Err("can't send a command on the left matrix since the left matrix hasn't been defined".into());
+					}
+
 					info!("Starting up left thread");
 					let data_two = Arc::clone(&handler_data);
 					data.start_left_thread(move || {
 						    // Render left
-						    for (frame, duration) in &s
+						    for (index, (frame, duration)) in s.iter().enumerate()
 						    {
 							    let mut data = data_two.lock().unwrap();
 
@@ -551,21 +935,24 @@ pub fn handler(command: IpcCommand,
 									    if let Err(e) = render_single(left_port, &s)
 									    {
 										    error!("Left render thread failed with error: {}", e);
-										    data.kill_threads(true, false);
+										    data.emit(Event::RenderError { message: format!("left render thread failed with error: {}", e) });
+										    data.finish_threads(false, true);
 										    break;
 									    }
+
+									    data.left_playback.advance(index);
+									    data.notify();
 								    }
 								    else
 								    {
 									    warn!("Can't send a command on the left matrix since the left matrix hasn't been defined");
-									    data.kill_threads(true, false);
+									    data.finish_threads(false, true);
 									    break;
 								    }
 							    }
 							    else
 							    {
 								    warn!("Terminating left render thread early");
-								    data.kill_threads(true, false);
 								    break;
 							    }
 
@@ -574,11 +961,15 @@ pub fn handler(command: IpcCommand,
 						    }
 					    });
 				}
+
+				// Only what made it to the matrix is remembered
+				data.state.set_content(Some(content), None);
 			}
 
 			if let Some(s) = right
 			{
 				data.kill_threads(true, false);
+				let content = Content::Frames(s.clone());
 				if s.len() == 1
 				{
 					// The validate function ensures this won't fail here
@@ -587,11 +978,16 @@ pub fn handler(command: IpcCommand,
 				}
 				else
 				{
+					if data.right_port.is_none()
+					{
+						return Err("can't send a command on the right matrix since the right matrix hasn't been defined".into());
+					}
+
 					info!("Starting up right thread");
 					let data_two = Arc::clone(&handler_data);
 					data.start_right_thread(move || {
 						    // Render right
-						    for (frame, duration) in &s
+						    for (index, (frame, duration)) in s.iter().enumerate()
 						    {
 							    let mut data = data_two.lock().unwrap();
 
@@ -605,21 +1001,24 @@ pub fn handler(command: IpcCommand,
 									    if let Err(e) = render_single(right_port, &s)
 									    {
 										    error!("Right render thread failed with error: {}", e);
-										    data.kill_threads(true, false);
+										    data.emit(Event::RenderError { message: format!("right render thread failed with error: {}", e) });
+										    data.finish_threads(true, false);
 										    break;
 									    }
+
+									    data.right_playback.advance(index);
+									    data.notify();
 								    }
 								    else
 								    {
 									    warn!("Can't send a command on the right matrix since the right matrix hasn't been defined");
-									    data.kill_threads(true, false);
+									    data.finish_threads(true, false);
 									    break;
 								    }
 							    }
 							    else
 							    {
 								    warn!("Terminating right render thread early");
-								    data.kill_threads(true, false);
 								    break;
 							    }
 
@@ -628,11 +1027,14 @@ pub fn handler(command: IpcCommand,
 						    }
 					    });
 				}
+
+				data.state.set_content(None, Some(content));
 			}
 		},
 		IpcCommand::RenderPair(pair) =>
 		{
 			data.kill_threads(false, false);
+			let content = Content::Frames(pair.clone());
 
 			if pair.len() == 1
 			{
@@ -644,11 +1046,16 @@ pub fn handler(command: IpcCommand,
 			}
 			else
 			{
+				if data.left_port.is_none() || data.right_port.is_none()
+				{
+					return Err("can't send a pair command due to missing at least one matrix from the config".into());
+				}
+
 				info!("Starting up pair thread");
 				let data_two = Arc::clone(&handler_data);
 				data.start_pair_thread(move || {
 					    // Render pair
-					    for (frame, duration) in &pair
+					    for (index, (frame, duration)) in pair.iter().enumerate()
 					    {
 						    let mut data = data_two.lock().unwrap();
 
@@ -662,21 +1069,24 @@ pub fn handler(command: IpcCommand,
 								    if let Err(e) = render_pair(left_port, right_port, &pair)
 								    {
 									    error!("Pair render thread failed with error: {}", e);
-									    data.kill_threads(false, false);
+									    data.emit(Event::RenderError { message: format!("pair render thread failed with error: {}", e) });
+									    data.finish_threads(false, false);
 									    break;
 								    }
+
+								    data.pair_playback.advance(index);
+								    data.notify();
 							    }
 							    else
 							    {
 								    warn!("Can't send a pair command due to missing at least one matrix from the config");
-								    data.kill_threads(false, false);
+								    data.finish_threads(false, false);
 								    break;
 							    }
 						    }
 						    else
 						    {
 							    warn!("Terminating pair render thread early");
-							    data.kill_threads(false, false);
 							    break;
 						    }
 
@@ -685,8 +1095,349 @@ pub fn handler(command: IpcCommand,
 					    }
 				    });
 			}
+
+			data.state.set_pair(content);
+		},
+		// Text is displayed like any other image, but it's remembered as text
+		IpcCommand::RenderText(left, right) =>
+		{
+			let left_frames = left.as_deref().map(|t| render_text(t, false));
+			let right_frames = right.as_deref().map(|t| render_text(t, false));
+
+			drop(data);
+			handle(IpcCommand::RenderSingle(left_frames, right_frames), response, Arc::clone(&handler_data))?;
+
+			data = handler_data.lock().unwrap();
+			data.state.set_content(left.map(Content::Text), right.map(Content::Text));
+		},
+		IpcCommand::RenderFile { path, target, options } =>
+		{
+			// Reading the file can take a while, so let the render threads carry on in the meantime
+			drop(data);
+
+			let frames = decode_image(BufReader::new(open_image(&path)?), target == Target::Pair, &options)?;
+			let command = match target
+			{
+				Target::Left => IpcCommand::RenderSingle(Some(frames), None),
+				Target::Right => IpcCommand::RenderSingle(None, Some(frames)),
+				Target::Both => IpcCommand::RenderSingle(Some(frames.clone()), Some(frames)),
+				Target::Pair => IpcCommand::RenderPair(frames),
+			};
+			handle(command, response, Arc::clone(&handler_data))?;
+
+			data = handler_data.lock().unwrap();
+			let content = Content::File(path, options);
+			match target
+			{
+				Target::Left => data.state.set_content(Some(content), None),
+				Target::Right => data.state.set_content(None, Some(content)),
+				Target::Both => data.state.set_content(Some(content.clone()), Some(content)),
+				Target::Pair => data.state.set_pair_source(content),
+			}
+		},
+		IpcCommand::RenderTextPair(text) =>
+		{
+			drop(data);
+			handle(IpcCommand::RenderPair(render_text(&text, true)), response, Arc::clone(&handler_data))?;
+
+			data = handler_data.lock().unwrap();
+			data.state.set_pair(Content::Text(text));
+		},
+		IpcCommand::StartWidget { target, widget } =>
+		{
+			if matches!(target, Target::Left | Target::Both)
+			{
+				data.kill_threads(false, true);
+				start_widget_thread(&mut data, &handler_data, Target::Left, &widget)?;
+				data.state.set_content(Some(Content::Widget(widget.clone())), None);
+			}
+
+			if matches!(target, Target::Right | Target::Both)
+			{
+				data.kill_threads(true, false);
+				start_widget_thread(&mut data, &handler_data, Target::Right, &widget)?;
+				data.state.set_content(None, Some(Content::Widget(widget.clone())));
+			}
+
+			if target == Target::Pair
+			{
+				data.kill_threads(false, false);
+				start_widget_thread(&mut data, &handler_data, Target::Pair, &widget)?;
+				data.state.set_pair(Content::Widget(widget));
+			}
+		},
+		// The notification thread decides what to show, so the client isn't kept waiting while it's displayed
+		IpcCommand::Notify { app, summary, urgency } => match &data.notifications
+		{
+			Some(notifications) => notifications.send(Notification { app, summary, urgency })?,
+			None => return Err("notifications aren't enabled in the config".into()),
+		},
+		// Only a timer that's displayed can be controlled, so a stray command can't affect the next one
+		IpcCommand::Timer { target, action } =>
+		{
+			let targets = match target
+			{
+				Target::Both => vec![Target::Left, Target::Right],
+				target => vec![target],
+			};
+
+			for target in &targets
+			{
+				let (content, name) = match target
+				{
+					Target::Left => (&data.state.left.content, "the left matrix"),
+					Target::Right => (&data.state.right.content, "the right matrix"),
+					_ => (&data.state.pair, "the pair"),
+				};
+
+				if !matches!(content, Some(Content::Widget(Widget::Timer(_))))
+				{
+					return Err(format!("there's no timer displayed on {}", name).into());
+				}
+			}
+
+			for target in targets
+			{
+				let mut timer = data.timers.entry(target).or_default().lock().unwrap();
+				match action
+				{
+					TimerAction::Pause => timer.pause(),
+					TimerAction::Resume => timer.resume(),
+					TimerAction::Cancel => timer.cancel(),
+				}
+			}
 		},
 	}
 
+	if changes_state
+	{
+		data.save_state();
+	}
+
 	Ok(false)
 }
+
+/// Keep drawing a widget on the left, right or pair render thread until something else takes over that thread
+fn start_widget_thread(data: &mut HandlerData,
+                       handler_data: &Arc<Mutex<HandlerData>>,
+                       target: Target,
+                       widget: &Widget)
+                       -> Result<(), Box<dyn Error>>
+{
+	let missing = match target
+	{
+		Target::Left => data.left_port.is_none(),
+		Target::Right => data.right_port.is_none(),
+		_ => data.left_port.is_none() || data.right_port.is_none(),
+	};
+
+	if missing
+	{
+		return Err(format!("can't start the {} widget since a matrix it needs hasn't been defined", widget.name()).into());
+	}
+
+	info!("Starting the {} widget", widget.name());
+	let name = widget.name();
+	let timer = Arc::clone(data.timers.entry(target).or_default());
+	let mut generator = generator(widget, target, &data.sensors, &timer)?;
+	let fallback = data.state.content_commands(target);
+	let data_two = Arc::clone(handler_data);
+	let worker = move || {
+		// Drawing happens without the lock, so that slow widgets don't hold up everything else
+		let frame = generator.next_frame();
+
+		let mut data = data_two.lock().unwrap();
+
+		// We got the lock, but don't render if we shouldn't be animated anymore
+		let animated = match target
+		{
+			Target::Left => data.is_left_animated(),
+			Target::Right => data.is_right_animated(),
+			_ => data.is_pair_animated(),
+		};
+		if !animated
+		{
+			return;
+		}
+
+		let (keep_left_alive, keep_right_alive) = (target == Target::Right, target == Target::Left);
+
+		// Whatever was displayed before comes back once the widget is done, from another thread since this one has the lock
+		if generator.finished()
+		{
+			info!("The {} widget is finished, restoring what was displayed before it", name);
+			data.finish_threads(keep_left_alive, keep_right_alive);
+
+			let (commands, data) = (fallback.clone(), Arc::clone(&data_two));
+			spawn(move || {
+				for command in commands
+				{
+					if let Err(e) = handler(command, &mut None, Arc::clone(&data))
+					{
+						warn!("Failed to restore what was displayed before the {} widget with error: {}", name, e);
+					}
+				}
+			});
+
+			return;
+		}
+
+		let result =
+			frame.and_then(|(frame, delay)| {
+				     match target
+				     {
+					     Target::Left => render_single(
+				                                       data.left_port.as_mut().ok_or("the left matrix is gone")?,
+				                                       &frame.try_into()
+				                                             .map_err(|_| "the frame is the wrong size")?,
+				)?,
+				         Target::Right => render_single(
+				                                        data.right_port.as_mut().ok_or("the right matrix is gone")?,
+				                                        &frame.try_into()
+				                                              .map_err(|_| "the frame is the wrong size")?,
+				)?,
+				         _ =>
+				         {
+					         let frame: [u8; 612] = frame.try_into().map_err(|_| "the frame is the wrong size")?;
+					         match data.get_ports()
+					         {
+						         (Some(left_port), Some(right_port)) => render_pair(left_port, right_port, &frame)?,
+					             _ => return Err("a matrix is gone".into()),
+					         }
+				         },
+				     }
+
+				     Ok(delay)
+			     });
+
+		match result
+		{
+			Ok(delay) =>
+			{
+				data.notify();
+				drop(data);
+				sleep(delay);
+			},
+			Err(e) =>
+			{
+				error!("Widget render thread failed with error: {}", e);
+				data.emit(Event::RenderError { message: format!("widget render thread failed with error: {}", e) });
+				data.finish_threads(keep_left_alive, keep_right_alive);
+			},
+		}
+	};
+
+	match target
+	{
+		Target::Left => data.start_left_thread(worker),
+		Target::Right => data.start_right_thread(worker),
+		_ => data.start_pair_thread(worker),
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{env::temp_dir, fs::remove_file, process, sync::mpsc::channel, time::Duration};
+
+	#[test]
+	fn replaced_animations_are_not_reported_as_finished()
+	{
+		let mut data = HandlerData::new(None, None);
+		let (sender, receiver) = channel();
+		data.subscribe(sender);
+
+		data.left_thread = Some(Arc::new(()));
+		data.kill_threads(false, true);
+
+		assert!(!data.is_left_animated());
+		assert!(receiver.try_recv().is_err());
+	}
+
+	#[test]
+	fn finished_pair_is_reported_on_both_sides()
+	{
+		let mut data = HandlerData::new(None, None);
+		let (sender, receiver) = channel();
+		data.subscribe(sender);
+
+		data.pair_thread = Some(Arc::new(()));
+		data.finish_threads(false, false);
+
+		let events: Vec<Event> = receiver.try_iter().collect();
+		assert!(matches!(
+			events[..],
+			[Event::AnimationFinished { matrix: Side::Left }, Event::AnimationFinished { matrix: Side::Right }]
+		));
+	}
+
+	#[test]
+	fn unsubscribed_get_nothing()
+	{
+		let mut data = HandlerData::new(None, None);
+		let (sender, receiver) = channel();
+		let id = data.subscribe(sender);
+
+		data.unsubscribe(id);
+		data.emit(Event::RenderError { message: String::new() });
+
+		assert!(receiver.recv().is_err());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn fifos_are_not_opened()
+	{
+		let path = temp_dir().join(format!("fw16-led-matrixd-fifo-{}", process::id()));
+		nix::unistd::mkfifo(&path, nix::sys::stat::Mode::S_IRWXU).unwrap();
+
+		let result = open_image(path.to_str().unwrap());
+		remove_file(&path).unwrap();
+
+		assert!(result.is_err());
+	}
+
+	#[test]
+	fn failed_renders_are_not_remembered()
+	{
+		let data = Arc::new(Mutex::new(HandlerData::new(None, None)));
+		let frames = |size| vec![(vec![0xFF; size], Duration::from_millis(10)); 2];
+
+		assert!(handler(IpcCommand::RenderSingle(Some(frames(306)[..1].to_vec()), None), &mut None, Arc::clone(&data)).is_err());
+		assert!(handler(IpcCommand::RenderSingle(None, Some(frames(306))), &mut None, Arc::clone(&data)).is_err());
+		assert!(handler(IpcCommand::RenderPair(frames(612)), &mut None, Arc::clone(&data)).is_err());
+
+		let data = data.lock().unwrap();
+		assert!(data.state.left.content.is_none() && data.state.right.content.is_none() && data.state.pair.is_none());
+	}
+
+	#[test]
+	fn timers_are_controlled_separately()
+	{
+		let data = Arc::new(Mutex::new(HandlerData::new(None, None)));
+		let timer = || Some(Content::Widget(Widget::Timer(Default::default())));
+		data.lock().unwrap().state.set_content(timer(), timer());
+
+		let pause =
+			|target| handler(IpcCommand::Timer { target, action: TimerAction::Pause }, &mut None, Arc::clone(&data));
+		pause(Target::Left).unwrap();
+
+		let paused = |target| data.lock().unwrap().timers.get(&target).map(|t| t.lock().unwrap().paused());
+		assert_eq!(paused(Target::Left), Some(true));
+		assert_ne!(paused(Target::Right), Some(true));
+
+		// There's no pair timer, so nothing is touched
+		assert!(pause(Target::Pair).is_err());
+		assert!(pause(Target::Both).is_ok());
+		assert_eq!(paused(Target::Right), Some(true));
+	}
+
+	#[test]
+	fn directories_are_not_opened()
+	{
+		assert!(open_image(temp_dir().to_str().unwrap()).is_err());
+	}
+}
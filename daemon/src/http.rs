@@ -0,0 +1,369 @@
+use crate::{
+	config::Http,
+	handler::{run, HandlerData},
+};
+use log::{info, warn};
+use serde_json::{from_str, json, to_string};
+use shared::{
+	image::{decode_image, ImageOptions, Rotation},
+	ipc::*,
+	widget::Widget,
+};
+use std::{
+	error::Error,
+	io::{Cursor, Read},
+	net::{SocketAddr, ToSocketAddrs},
+	sync::{Arc, Mutex},
+	thread::spawn,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8016";
+
+/// Why a request couldn't be turned into a command, along with the status code to send back
+struct RouteError(u16, String);
+
+impl<E: Error> From<E> for RouteError
+{
+	fn from(e: E) -> Self
+	{
+		RouteError(400, e.to_string())
+	}
+}
+
+/// The server along with who may use it
+pub struct HttpServer
+{
+	server: Server,
+	access: Access,
+}
+
+// Browsers let any page send simple requests to localhost, so every request has to prove it's meant for the API
+struct Access
+{
+	token: String,
+	hosts: Vec<String>,
+	origins: Vec<String>,
+}
+
+impl Access
+{
+	fn check(&self, request: &Request) -> Result<(), RouteError>
+	{
+		let header =
+			|name: &'static str| request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str());
+
+		// A page on another site that resolves its own name to localhost still sends its own name as the host
+		if !header("Host").is_some_and(|host| self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+		{
+			return Err(RouteError(403, "the host isn't the API".to_string()));
+		}
+
+		if header("Origin").is_some_and(|origin| !self.origins.iter().any(|o| o == origin))
+		{
+			return Err(RouteError(403, "the origin isn't allowed to use the API".to_string()));
+		}
+
+		let token = header("Authorization").and_then(|a| a.strip_prefix("Bearer ")).unwrap_or_default();
+		if !same(token.as_bytes(), self.token.as_bytes())
+		{
+			return Err(RouteError(401, "a valid bearer token is needed".to_string()));
+		}
+
+		Ok(())
+	}
+}
+
+// Compares the whole token no matter where it differs, so the time taken doesn't give any of it away
+fn same(a: &[u8], b: &[u8]) -> bool
+{
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Bind the server, this happens before dropping privileges in case the port needs them
+pub fn bind(http: &Http) -> Result<HttpServer, Box<dyn Error>>
+{
+	if http.token.is_empty()
+	{
+		return Err("the HTTP API needs a token".into());
+	}
+
+	let address = http.address.as_deref().unwrap_or(DEFAULT_ADDRESS);
+
+	// Anyone on the network could guess at the token otherwise
+	let resolved: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+	if !http.allow_remote && !resolved.iter().all(|a| a.ip().is_loopback())
+	{
+		return Err(format!("the HTTP API only listens on localhost unless allow_remote is set, not {}", address).into());
+	}
+
+	info!("Serving the HTTP API on {}", address);
+	let server = Server::http(address).map_err(|e| e.to_string())?;
+
+	// Clients on this machine can name it any of these ways
+	let mut hosts = vec![address.to_string()];
+	if let Some(port) = server.server_addr().to_ip().map(|a| a.port())
+	{
+		hosts.extend(["localhost", "127.0.0.1", "[::1]"].map(|h| format!("{}:{}", h, port)));
+	}
+
+	Ok(HttpServer { server, access: Access { token: http.token.clone(), hosts, origins: http.origins.clone() } })
+}
+
+pub fn start_http_thread(server: HttpServer, data: Arc<Mutex<HandlerData>>)
+{
+	let HttpServer { server, access } = server;
+
+	spawn(move || {
+		for mut request in server.incoming_requests()
+		{
+			let (status, body) = match access.check(&request).and_then(|_| route(&mut request))
+			{
+				Ok(command) => match run(command, &data)
+				{
+					Ok(response) => (200, to_string(&response).unwrap_or_default()),
+					Err(e) => (500, json!({ "error": e.to_string() }).to_string()),
+				},
+				Err(RouteError(status, message)) => (status, json!({ "error": message }).to_string()),
+			};
+
+			// The header is valid, so this won't fail
+			let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+			if let Err(e) = request.respond(Response::from_string(body).with_status_code(status).with_header(header))
+			{
+				warn!("Failed to respond to an HTTP request with error: {}", e);
+			}
+		}
+	});
+}
+
+/// Turn a request into the IPC command it mirrors
+fn route(request: &mut Request) -> Result<IpcCommand, RouteError>
+{
+	let url = request.url().to_string();
+	let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+	let params = query.split('&')
+	                  .filter(|p| !p.is_empty())
+	                  .map(|p| {
+		                  let (key, value) = p.split_once('=').unwrap_or((p, ""));
+		                  Ok((decode(key)?, decode(value)?))
+	                  })
+	                  .collect::<Result<Vec<(String, String)>, RouteError>>()?;
+	let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+	let flag = |name: &str| param(name).is_some_and(|value| value != "false");
+
+	// Everything that draws takes the matrix from the query, and defaults to both of them
+	let target = match param("matrix").unwrap_or("both")
+	{
+		"left" => Target::Left,
+		"right" => Target::Right,
+		"both" => Target::Both,
+		"pair" => Target::Pair,
+		m => return Err(RouteError(400, format!("unknown matrix \"{}\", expected left, right, both or pair", m))),
+	};
+	let (left, right) = (target != Target::Right, target != Target::Left);
+
+	let mut body: Vec<u8> = vec![];
+	request.as_reader().take(MAX_BINARY_SIZE as u64 + 1).read_to_end(&mut body)?;
+	if body.len() > MAX_BINARY_SIZE
+	{
+		return Err(RouteError(413, "request body is too large".to_string()));
+	}
+
+	match (request.method(), path)
+	{
+		(Method::Get, "/status") => Ok(IpcCommand::Status),
+		(Method::Put, "/brightness") =>
+		{
+			if target == Target::Pair
+			{
+				return Err(RouteError(400, "brightness can't be set on a pair".to_string()));
+			}
+
+			let brightness: u8 = String::from_utf8(body)?.trim().parse()?;
+			Ok(IpcCommand::SetBrightness(left.then_some(brightness), right.then_some(brightness)))
+		},
+		(Method::Post, "/render") =>
+		{
+			let rotate = match param("rotate")
+			{
+				Some(degrees) => Rotation::try_from(degrees.parse::<u16>()?).map_err(|e| RouteError(400, e))?,
+				None => Rotation::None,
+			};
+
+			let options = ImageOptions { rotate,
+			                             flip_horizontal: flag("flip_horizontal"),
+			                             flip_vertical: flag("flip_vertical"),
+			                             invert: flag("invert") };
+
+			let frames =
+				decode_image(Cursor::new(body), target == Target::Pair, &options).map_err(|e| {
+					                                                                 RouteError(400, e.to_string())
+				                                                                 })?;
+
+			Ok(match target
+			{
+				Target::Pair => IpcCommand::RenderPair(frames),
+				_ => IpcCommand::RenderSingle(left.then(|| frames.clone()), right.then_some(frames)),
+			})
+		},
+		(Method::Post, "/text") =>
+		{
+			let text = String::from_utf8(body)?.trim_end().to_string();
+
+			Ok(match target
+			{
+				Target::Pair => IpcCommand::RenderTextPair(text),
+				_ => IpcCommand::RenderText(left.then(|| text.clone()), right.then_some(text)),
+			})
+		},
+		// Most of what the IPC socket understands can be sent as JSON
+		(Method::Post, "/command") =>
+		{
+			let command: IpcCommand = from_str(&String::from_utf8(body)?)?;
+			match allowed(&command)
+			{
+				true => Ok(command),
+				false => Err(RouteError(403, format!("{} can't be sent over HTTP", command.name()))),
+			}
+		},
+		_ => Err(RouteError(404, format!("nothing at {} {}", request.method(), path))),
+	}
+}
+
+// Commands that could harm the matrixes, or that read files off of the machine, are left to the IPC socket
+fn allowed(command: &IpcCommand) -> bool
+{
+	// Widgets can be pointed at another directory or bus, which would let the caller choose what the daemon reads
+	if let IpcCommand::StartWidget { widget, .. } = command
+	{
+		return match widget
+		{
+			Widget::Battery(options) => options.sysfs.is_none(),
+			Widget::Hwmon(options) => options.sysfs.is_none(),
+			Widget::NowPlaying(options) => options.address.is_none(),
+			_ => true,
+		};
+	}
+
+	matches!(
+	         command,
+	         IpcCommand::SetBrightness(_, _)
+	         | IpcCommand::GetBrightness(_, _)
+	         | IpcCommand::PatternPercentage(_, _)
+	         | IpcCommand::PatternGradient(_, _)
+	         | IpcCommand::PatternDoubleGradient(_, _)
+	         | IpcCommand::PatternLotusHorizontal(_, _)
+	         | IpcCommand::PatternLotusVertical(_, _)
+	         | IpcCommand::PatternZigzag(_, _)
+	         | IpcCommand::PatternFullbright(_, _)
+	         | IpcCommand::PatternPanic(_, _)
+	         | IpcCommand::SetSleep(_, _)
+	         | IpcCommand::GetSleep(_, _)
+	         | IpcCommand::SetAnimate(_, _)
+	         | IpcCommand::GetAnimate(_, _)
+	         | IpcCommand::DrawBW(_, _)
+	         | IpcCommand::StageCol(_, _)
+	         | IpcCommand::FlushCols(_, _)
+	         | IpcCommand::Version(_, _)
+	         | IpcCommand::RenderSingle(_, _)
+	         | IpcCommand::RenderPair(_)
+	         | IpcCommand::RenderText(_, _)
+	         | IpcCommand::RenderTextPair(_)
+	         | IpcCommand::Status
+	         | IpcCommand::Notify { .. }
+	         | IpcCommand::Timer { .. }
+	)
+}
+
+/// Undo the percent encoding of a query key or value, where a plus is a space
+fn decode(encoded: &str) -> Result<String, RouteError>
+{
+	let mut bytes = encoded.bytes();
+	let mut decoded: Vec<u8> = Vec::with_capacity(encoded.len());
+
+	while let Some(byte) = bytes.next()
+	{
+		decoded.push(match byte
+		       {
+			       b'+' => b' ',
+		           b'%' =>
+		           {
+			           let hex = [bytes.next(), bytes.next()];
+			           let hex = match hex
+			           {
+				           [Some(high), Some(low)] => String::from_utf8(vec![high, low])?,
+			               _ => return Err(RouteError(400, format!("\"{}\" is cut off", encoded))),
+			           };
+			           u8::from_str_radix(&hex, 16)?
+		           },
+		           b => b,
+		       });
+	}
+
+	Ok(String::from_utf8(decoded)?)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use shared::widget::{BatteryOptions, ClockOptions, HwmonOptions, NowPlayingOptions};
+
+	#[test]
+	fn query_values_are_decoded()
+	{
+		assert_eq!(decode("hello+there%21").ok(), Some("hello there!".to_string()));
+		assert_eq!(decode("%E2%9C%93").ok(), Some("\u{2713}".to_string()));
+		assert!(decode("cut%2").is_err());
+		assert!(decode("%zz").is_err());
+	}
+
+	#[test]
+	fn tokens_have_to_match_exactly()
+	{
+		assert!(same(b"secret", b"secret"));
+		assert!(!same(b"secret", b"secreT"));
+		assert!(!same(b"secret", b"secret2"));
+		assert!(!same(b"", b"secret"));
+	}
+
+	#[test]
+	fn dangerous_commands_are_not_allowed()
+	{
+		assert!(!allowed(&IpcCommand::Bootloader(true, true)));
+		assert!(!allowed(&IpcCommand::Crash(true, false)));
+		assert!(!allowed(&IpcCommand::RenderFile { path: "/etc/shadow".to_string(),
+		                                           target: Target::Left,
+		                                           options: ImageOptions::default() }));
+		assert!(allowed(&IpcCommand::SetBrightness(Some(10), None)));
+	}
+
+	#[test]
+	fn widgets_reading_somewhere_else_are_not_allowed()
+	{
+		let widget = |widget| IpcCommand::StartWidget { target: Target::Left, widget };
+		let sysfs = Some("/etc".to_string());
+
+		assert!(allowed(&widget(Widget::Battery(BatteryOptions::default()))));
+		assert!(!allowed(&widget(Widget::Battery(BatteryOptions { sysfs: sysfs.clone(), ..Default::default() }))));
+		assert!(!allowed(&widget(Widget::Hwmon(HwmonOptions { sysfs, ..Default::default() }))));
+
+		let address = Some("tcp:host=example.com,port=1234".to_string());
+		assert!(!allowed(&widget(Widget::NowPlaying(NowPlayingOptions { address, ..Default::default() }))));
+		assert!(allowed(&widget(Widget::Clock(ClockOptions::default()))));
+	}
+
+	#[test]
+	fn only_localhost_is_bound_unless_allowed()
+	{
+		let http = |address: &str, allow_remote| Http { address: Some(address.to_string()),
+		                                                token: "secret".to_string(),
+		                                                origins: vec![],
+		                                                allow_remote };
+
+		assert!(bind(&http("0.0.0.0:0", false)).is_err());
+		assert!(bind(&http("127.0.0.1:0", false)).is_ok());
+		assert!(bind(&http("0.0.0.0:0", true)).is_ok());
+	}
+}
@@ -0,0 +1,338 @@
+use crate::{
+	config::{ActivitySourceConfig, ConfigError, Idle},
+	handler::{handler, HandlerData},
+	matrix::get_brightness,
+	scene::scene_commands,
+};
+use log::{info, warn};
+use shared::ipc::IpcCommand;
+use std::{
+	error::Error,
+	fs::metadata,
+	sync::{Arc, Mutex},
+	thread::{sleep, spawn},
+	time::{Duration, SystemTime},
+};
+
+#[cfg(target_os = "linux")]
+use std::{
+	fs::{read_dir, File},
+	io::Read,
+};
+
+// How often the activity source is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Somewhere to find out when the user was last active
+pub trait ActivitySource: Send
+{
+	fn last_activity(&self) -> Option<SystemTime>;
+}
+
+/// Treats the modification time of a file as the last activity, so anything that can touch it keeps the matrixes awake
+pub struct FileActivity
+{
+	path: String,
+}
+
+impl ActivitySource for FileActivity
+{
+	fn last_activity(&self) -> Option<SystemTime>
+	{
+		metadata(&self.path).and_then(|m| m.modified()).ok()
+	}
+}
+
+/// Watches every input device for events
+/// The devices are opened up front, so input devices plugged in later aren't watched
+#[cfg(target_os = "linux")]
+pub struct InputActivity
+{
+	last: Arc<Mutex<SystemTime>>,
+}
+
+#[cfg(target_os = "linux")]
+impl InputActivity
+{
+	pub fn open() -> Result<Self, Box<dyn Error>>
+	{
+		let last = Arc::new(Mutex::new(SystemTime::now()));
+
+		for entry in read_dir("/dev/input")?
+		{
+			let path = entry?.path();
+			if !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("event"))
+			{
+				continue;
+			}
+
+			let mut device = match File::open(&path)
+			{
+				Ok(d) => d,
+				Err(e) =>
+				{
+					warn!("Failed to open input device \"{}\" with error: {}", path.display(), e);
+					continue;
+				},
+			};
+
+			// Every read returns at least one input event, and the contents of the events don't matter
+			let device_last = Arc::clone(&last);
+			spawn(move || {
+				let mut buffer = [0u8; 256];
+				while let Ok(size) = device.read(&mut buffer)
+				{
+					if size == 0
+					{
+						break;
+					}
+
+					*device_last.lock().unwrap() = SystemTime::now();
+				}
+			});
+		}
+
+		Ok(InputActivity { last })
+	}
+}
+
+#[cfg(target_os = "linux")]
+impl ActivitySource for InputActivity
+{
+	fn last_activity(&self) -> Option<SystemTime>
+	{
+		Some(*self.last.lock().unwrap())
+	}
+}
+
+/// Open the configured activity source, this must happen before dropping privileges
+pub fn open_source(source: &Option<ActivitySourceConfig>) -> Result<Box<dyn ActivitySource>, Box<dyn Error>>
+{
+	match source
+	{
+		Some(ActivitySourceConfig::File(path)) => Ok(Box::new(FileActivity { path: path.clone() })),
+		#[cfg(target_os = "linux")]
+		Some(ActivitySourceConfig::Input) | None => Ok(Box::new(InputActivity::open()?)),
+		#[cfg(not(target_os = "linux"))]
+		Some(ActivitySourceConfig::Input) | None => Err("input devices can't be watched on this platform".into()),
+	}
+}
+
+/// Watch for the user going idle, and either sleep the matrixes or show the screensaver until they come back
+pub fn start_idle_thread(idle: Idle,
+                         source: Box<dyn ActivitySource>,
+                         data: Arc<Mutex<HandlerData>>)
+                         -> Result<(), Box<dyn Error>>
+{
+	let timeout = Duration::from_secs(idle.timeout.checked_mul(60).ok_or(ConfigError::IdleTimeout(idle.timeout))?);
+
+	let mut screensaver: Vec<IpcCommand> = vec![];
+	if let Some(scene) = &idle.screensaver
+	{
+		screensaver.extend(scene_commands(scene, true)?);
+		screensaver.extend(scene_commands(scene, false)?);
+	}
+
+	info!("Starting idle thread");
+	spawn(move || {
+		// Nothing has happened yet as far as the daemon knows
+		let mut watch = IdleWatch::new(source, timeout, SystemTime::now());
+
+		loop
+		{
+			sleep(POLL_INTERVAL);
+
+			match watch.poll(SystemTime::now())
+			{
+				Some(Transition::Idle(idle_for)) =>
+				{
+					info!("Idle for {} seconds, putting the matrixes to rest", idle_for.as_secs());
+					go_idle(&screensaver, &data);
+				},
+				Some(Transition::Active) =>
+				{
+					info!("Activity detected, restoring the matrixes");
+					wake(&data);
+				},
+				None => (),
+			}
+		}
+	});
+
+	Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Transition
+{
+	/// The user went idle, and has been for this long
+	Idle(Duration),
+	/// The user came back
+	Active,
+}
+
+/// Keeps track of whether the user is idle, going by the activity source
+struct IdleWatch
+{
+	source: Box<dyn ActivitySource>,
+	timeout: Duration,
+	started: SystemTime,
+	idle: bool,
+}
+
+impl IdleWatch
+{
+	fn new(source: Box<dyn ActivitySource>, timeout: Duration, started: SystemTime) -> Self
+	{
+		IdleWatch { source, timeout, started, idle: false }
+	}
+
+	/// Check the activity source, and say whether the user has gone idle or come back since the last time
+	fn poll(&mut self, now: SystemTime) -> Option<Transition>
+	{
+		// Activity from before the daemon started doesn't count
+		let last_activity = self.source.last_activity().unwrap_or(self.started).max(self.started);
+		let idle_for = now.duration_since(last_activity).unwrap_or_default();
+
+		match (self.idle, idle_for >= self.timeout)
+		{
+			(false, true) =>
+			{
+				self.idle = true;
+				Some(Transition::Idle(idle_for))
+			},
+			(true, false) =>
+			{
+				self.idle = false;
+				Some(Transition::Active)
+			},
+			_ => None,
+		}
+	}
+}
+
+fn go_idle(screensaver: &[IpcCommand], data: &Arc<Mutex<HandlerData>>)
+{
+	let commands = match screensaver.is_empty()
+	{
+		true =>
+		{
+			let data = data.lock().unwrap();
+			vec![IpcCommand::SetSleep(data.left_port.as_ref().map(|_| true), data.right_port.as_ref().map(|_| true))]
+		},
+		false => screensaver.to_vec(),
+	};
+
+	// The screensaver shouldn't be remembered, so the state is put back once it's displayed
+	let saved = {
+		let mut data = data.lock().unwrap();
+		data.idle = true;
+		data.temporary = true;
+
+		// Hang on to the brightness as well in case the screensaver changes it, asking a sleeping matrix would wake it up
+		let mut saved = data.state.clone();
+		if saved.left.brightness.is_none()
+		{
+			saved.left.brightness =
+				data.left_port.as_mut().filter(|p| !p.sleeping).and_then(|p| get_brightness(p).ok());
+		}
+
+		if saved.right.brightness.is_none()
+		{
+			saved.right.brightness =
+				data.right_port.as_mut().filter(|p| !p.sleeping).and_then(|p| get_brightness(p).ok());
+		}
+
+		saved
+	};
+
+	run(commands, data);
+
+	let mut data = data.lock().unwrap();
+	data.state = saved;
+	data.temporary = false;
+}
+
+fn wake(data: &Arc<Mutex<HandlerData>>)
+{
+	let mut commands: Vec<IpcCommand> = vec![];
+
+	{
+		let mut data = data.lock().unwrap();
+		data.idle = false;
+
+		// Matrixes without any content to restore need waking up explicitly
+		let left = data.left_port.as_ref().is_some_and(|p| p.sleeping) && !data.state.left.sleeping;
+		let right = data.right_port.as_ref().is_some_and(|p| p.sleeping) && !data.state.right.sleeping;
+		if left || right
+		{
+			commands.push(IpcCommand::SetSleep(left.then_some(false), right.then_some(false)));
+		}
+
+		commands.extend(data.state.commands());
+	}
+
+	run(commands, data);
+}
+
+fn run(commands: Vec<IpcCommand>, data: &Arc<Mutex<HandlerData>>)
+{
+	for command in commands
+	{
+		if let Err(e) = handler(command, &mut None, Arc::clone(data))
+		{
+			warn!("Idle thread failed to send a command with error: {}", e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		env::temp_dir,
+		fs::{remove_file, File},
+		process,
+	};
+
+	#[test]
+	fn touching_the_file_wakes()
+	{
+		let path = temp_dir().join(format!("fw16-led-matrixd-activity-{}", process::id()));
+		let file = File::create(&path).unwrap();
+
+		let start = SystemTime::now();
+		let timeout = Duration::from_secs(60);
+		let source = FileActivity { path: path.to_string_lossy().to_string() };
+		let mut watch = IdleWatch::new(Box::new(source), timeout, start - Duration::from_secs(600));
+
+		// Touched just now, so the user is still around
+		file.set_modified(start).unwrap();
+		assert_eq!(watch.poll(start), None);
+		assert_eq!(watch.poll(start + Duration::from_secs(59)), None);
+
+		// Going idle is only reported once
+		assert_eq!(watch.poll(start + timeout), Some(Transition::Idle(timeout)));
+		assert_eq!(watch.poll(start + Duration::from_secs(120)), None);
+
+		// Touching the file again brings them back
+		let touched = start + Duration::from_secs(180);
+		file.set_modified(touched).unwrap();
+		assert_eq!(watch.poll(touched + Duration::from_secs(1)), Some(Transition::Active));
+		assert_eq!(watch.poll(touched + Duration::from_secs(2)), None);
+
+		remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn missing_file_counts_from_the_start()
+	{
+		let start = SystemTime::now();
+		let source = FileActivity { path: "/nonexistent/fw16-led-matrixd-activity".to_string() };
+		let mut watch = IdleWatch::new(Box::new(source), Duration::from_secs(60), start);
+
+		assert_eq!(watch.poll(start + Duration::from_secs(30)), None);
+		assert_eq!(watch.poll(start + Duration::from_secs(60)), Some(Transition::Idle(Duration::from_secs(60))));
+	}
+}
@@ -1,19 +1,94 @@
 use crate::handler::HandlerData;
-use interprocess::local_socket::{prelude::*, GenericNamespaced, ListenerOptions};
-use log::warn;
+use interprocess::local_socket::{prelude::*, Listener, ListenerOptions, Stream};
+use log::{info, warn};
 use shared::{error::Error as SharedError, ipc::*};
 use std::{
 	error::Error,
-	io::{BufRead, BufReader, Write},
-	sync::{Arc, Mutex},
+	io::{copy, sink, BufRead, BufReader, Read, Write},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::channel,
+		Arc, Mutex,
+	},
+	thread::spawn,
 };
 
-pub fn listen<F>(name: String, mut handler: F, data: Arc<Mutex<HandlerData>>) -> Result<(), Box<dyn Error>>
-	where F: FnMut(IpcCommand, &mut Option<IpcResponse>, Arc<Mutex<HandlerData>>) -> Result<bool, Box<dyn Error>>
+#[cfg(unix)]
+use interprocess::os::unix::uds_local_socket::Listener as UdsListener;
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+/// Wrap an already bound and listening socket, such as one passed in through systemd socket activation
+#[cfg(unix)]
+pub fn listener_from_fd(fd: RawFd) -> Listener
+{
+	// The daemon is the only owner of the descriptor after daemonizing
+	Listener::from(UdsListener::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+type ParsedRequest = Result<IpcRequest, (u64, SharedError)>;
+
+enum Incoming
+{
+	Request(ParsedRequest),
+	/// A line from a client that's too old to send requests, which gets its reply the way it understands them
+	Legacy(LegacyReply),
+}
+
+/// Read the next request off of a connection, which is either a line of JSON or a binary request
+fn read_request(conn: &mut BufReader<Stream>) -> Result<Option<Incoming>, Box<dyn Error>>
 {
-	let socket_path = name.to_ns_name::<GenericNamespaced>()?;
-	let opts = ListenerOptions::new().name(socket_path);
-	let listener = opts.create_sync()?;
+	let binary = match conn.fill_buf()?.first()
+	{
+		Some(byte) => *byte == BINARY_MARKER,
+		None => return Ok(None),
+	};
+
+	if binary
+	{
+		let mut header = [0u8; 5];
+		conn.read_exact(&mut header)?;
+
+		// A bad length can't be skipped over, so the connection has to be dropped
+		let size = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+		if size > MAX_BINARY_SIZE
+		{
+			return Err(format!("binary request of {} bytes is too large", size).into());
+		}
+
+		let mut payload = vec![0u8; size];
+		conn.read_exact(&mut payload)?;
+
+		Ok(Some(Incoming::Request(IpcRequest::from_binary(&payload))))
+	}
+	else
+	{
+		let mut buffer = String::new();
+		conn.read_line(&mut buffer)?;
+
+		match LegacyReply::for_line(&buffer)
+		{
+			Some(reply) => Ok(Some(Incoming::Legacy(reply))),
+			None => Ok(Some(Incoming::Request(IpcRequest::from_json(buffer)))),
+		}
+	}
+}
+
+/// Create the IPC socket, this must happen before dropping privileges since the socket's directory usually belongs to root
+pub fn bind(name: &str) -> Result<Listener, Box<dyn Error>>
+{
+	// A socket file left behind by a daemon that crashed would otherwise stop this one from starting
+	let opts = ListenerOptions::new().name(socket_name(name)?).try_overwrite(true);
+	Ok(opts.create_sync()?)
+}
+
+pub fn listen<F>(listener: Listener, handler: F, data: Arc<Mutex<HandlerData>>) -> Result<(), Box<dyn Error>>
+	where F: Fn(IpcCommand, &mut Option<IpcResponse>, Arc<Mutex<HandlerData>>) -> Result<bool, Box<dyn Error>>
+	          + Clone
+	          + Send
+	          + 'static
+{
+	let closing = Arc::new(AtomicBool::new(false));
 
 	for conn in listener.incoming().filter_map(|conn| match conn
 	                               {
@@ -25,40 +100,133 @@ pub fn listen<F>(name: String, mut handler: F, data: Arc<Mutex<HandlerData>>) ->
 	                                   },
 	                               })
 	{
-		let mut buffer = String::new();
-		let mut conn = BufReader::new(conn);
-		conn.read_line(&mut buffer)?;
+		if closing.load(Ordering::Relaxed)
+		{
+			break;
+		}
+
+		// Every connection gets its own thread, so that clients holding on to theirs don't keep everyone else waiting
+		let (handler, data, closing) = (handler.clone(), Arc::clone(&data), Arc::clone(&closing));
+		spawn(move || serve(BufReader::new(conn), handler, data, closing));
+	}
 
-		let command = IpcCommand::from_json(buffer)?;
+	Ok(())
+}
 
-		let mut response: Option<IpcResponse> = None;
-		match command.validate()
+/// Answer the requests on a connection until the client goes away
+fn serve<F>(mut conn: BufReader<Stream>, handler: F, data: Arc<Mutex<HandlerData>>, closing: Arc<AtomicBool>)
+	where F: Fn(IpcCommand, &mut Option<IpcResponse>, Arc<Mutex<HandlerData>>) -> Result<bool, Box<dyn Error>>
+{
+	// A connection can carry several commands, such as the hello exchange followed by the actual command
+	loop
+	{
+		let parsed = match read_request(&mut conn)
 		{
-			Ok(_) => match handler(command, &mut response, Arc::clone(&data))
+			Ok(Some(Incoming::Request(p))) => p,
+			Ok(Some(Incoming::Legacy(reply))) =>
 			{
-				Ok(close) =>
+				info!("Answered a client that's older than the request IDs with: {:?}", reply);
+				match reply.to_json().and_then(|r| Ok(conn.get_mut().write_all(r.as_bytes())?))
 				{
-					if close
+					Ok(_) => continue,
+					Err(e) =>
 					{
+						warn!("Failed to respond on the IPC socket with error: {}", e);
 						break;
+					},
+				}
+			},
+			Ok(None) => break,
+			Err(e) =>
+			{
+				warn!("Failed to read from the IPC socket with error: {}", e);
+				break;
+			},
+		};
+
+		// Every request gets a reply, whether the command worked or not
+		let mut close = false;
+		let reply = match parsed
+		{
+			// Subscribers keep their connection, and every event is forwarded until they go away
+			Ok(IpcRequest { id, command: IpcCommand::Subscribe }) =>
+			{
+				let (sender, receiver) = channel();
+				let subscriber = data.lock().unwrap().subscribe(sender);
+
+				let (mut recv_half, mut send_half) = conn.into_inner().split();
+				let forwarder = spawn(move || {
+					// The subscription is acknowledged like any other request, then every event reuses its ID
+					let mut reply = IpcReply { id, result: Ok(IpcResponse::Done) };
+					loop
+					{
+						if let Err(e) = reply.to_json().and_then(|r| Ok(send_half.write_all(r.as_bytes())?))
+						{
+							info!("Subscriber went away with error: {}", e);
+							break;
+						}
+
+						match receiver.recv()
+						{
+							Ok(event) => reply = IpcReply { id, result: Ok(IpcResponse::Event(event)) },
+							Err(_) => break,
+						}
 					}
-				},
-				Err(e) =>
+				});
+
+				// Subscribers don't send anything else, so reading only ends once they go away
+				let _ = copy(&mut recv_half, &mut sink());
+				data.lock().unwrap().unsubscribe(subscriber);
+				let _ = forwarder.join();
+
+				return;
+			},
+			Ok(request) =>
+			{
+				let result = match request.command.validate()
 				{
-					response = Some(IpcResponse::InvalidCommand(SharedError::Handler(e.to_string())));
-				},
+					Ok(_) =>
+					{
+						let mut response: Option<IpcResponse> = None;
+						match handler(request.command, &mut response, Arc::clone(&data))
+						{
+							Ok(c) =>
+							{
+								close = c;
+								Ok(response.unwrap_or(IpcResponse::Done))
+							},
+							Err(e) =>
+							{
+								warn!("Failed to execute request {} with error: {}", request.id, e);
+								Err(SharedError::Handler(e.to_string()))
+							},
+						}
+					},
+					Err(e) => Err(e),
+				};
+
+				IpcReply { id: request.id, result }
 			},
-			Err(e) =>
+			// Unknown commands get reported back to the client rather than taking down the daemon
+			Err((id, e)) =>
 			{
-				response = Some(IpcResponse::InvalidCommand(e));
+				warn!("Received an invalid command with error: {}", e);
+				IpcReply { id, result: Err(e) }
 			},
+		};
+
+		let written = reply.to_json().and_then(|r| Ok(conn.get_mut().write_all(r.as_bytes())?));
+		if let Err(e) = written
+		{
+			warn!("Failed to respond on the IPC socket with error: {}", e);
+			break;
 		}
 
-		if let Some(r) = response
+		// The listener stops taking new connections, the ones that are already open carry on
+		if close
 		{
-			conn.get_mut().write_all(r.to_json()?.as_bytes())?;
+			closing.store(true, Ordering::Relaxed);
+			break;
 		}
 	}
-
-	Ok(())
 }
@@ -1,15 +1,40 @@
 mod config;
+#[cfg(feature = "dbus")]
+mod dbus;
 mod handler;
+#[cfg(feature = "http")]
+mod http;
+mod idle;
 mod ipc;
 mod logger;
 mod matrix;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notifications;
+mod scene;
+mod state;
+mod widget;
 
 pub use config::Matrix;
 
-use crate::{config::read_config, handler::handler, handler::HandlerData, ipc::listen, matrix::version};
+use crate::{
+	config::read_config,
+	handler::handler,
+	handler::keep_alive,
+	handler::HandlerData,
+	idle::open_source,
+	idle::start_idle_thread,
+	ipc::listen,
+	matrix::{is_present, open},
+	notifications::start_notification_thread,
+	scene::scene_commands,
+	state::{start_state_thread, StateFile},
+};
 use clap::{crate_authors, Parser};
-use log::{error, info};
+use interprocess::local_socket::Listener;
+use log::{error, info, warn};
 use logger::setup_logging;
+use shared::ipc::{IpcCommand, SOCKET_NAME};
 use std::{
 	error::Error,
 	sync::{Arc, Mutex},
@@ -38,6 +63,11 @@ struct Args
 	#[arg(short, long, default_value = "/run/fw16-led-matrixd.pid")]
 	pid_file: String,
 
+	/// State file used to restore what the matrixes were displaying when the daemon restarts
+	#[cfg(unix)]
+	#[arg(short, long, default_value = "/var/lib/fw16-led-matrixd/state.json")]
+	state_file: String,
+
 	/// Log file for the daemon
 	#[cfg(windows)]
 	#[arg(short, long, default_value = "C:\\Program Files\\fw16-led-matrixd\\fw16-led-matrixd.log")]
@@ -47,6 +77,11 @@ struct Args
 	#[cfg(windows)]
 	#[arg(short, long, default_value = "C:\\Program Files\\fw16-led-matrixd\\config.toml")]
 	config: String,
+
+	/// State file used to restore what the matrixes were displaying when the daemon restarts
+	#[cfg(windows)]
+	#[arg(short, long, default_value = "C:\\Program Files\\fw16-led-matrixd\\state.json")]
+	state_file: String,
 }
 
 #[cfg(windows)]
@@ -70,8 +105,11 @@ fn main() -> Result<(), Box<dyn Error>>
 	let args = Args::parse();
 	let pid_file = args.pid_file.clone();
 
-	daemon::daemonize(&pid_file.clone(), move || {
-		if let Err(e) = daemon_main(args)
+	// Grab the socket from systemd before the environment is sanitized
+	let listen_fd = daemon::listen_fd();
+
+	daemon::daemonize(&pid_file.clone(), listen_fd, move || {
+		if let Err(e) = daemon_main(args, listen_fd.map(ipc::listener_from_fd))
 		{
 			error!("Error: {}", e);
 		}
@@ -85,7 +123,7 @@ fn main() -> Result<(), Box<dyn Error>>
 	Ok(())
 }
 
-fn daemon_main(args: Args) -> Result<(), Box<dyn Error>>
+fn daemon_main(args: Args, listener: Option<Listener>) -> Result<(), Box<dyn Error>>
 {
 	if let Err(e) = setup_logging(Some(&args.log_file), None)
 	{
@@ -99,8 +137,180 @@ fn daemon_main(args: Args) -> Result<(), Box<dyn Error>>
 	{
 		Ok(config) =>
 		{
+			// The state file is opened before dropping privileges, and a missing one just means nothing gets restored
+			let state_file = match StateFile::open(&args.state_file)
+			{
+				Ok(f) => Some(f),
+				Err(e) =>
+				{
+					warn!("Failed to open state file \"{}\" with error: {}", args.state_file, e);
+					None
+				},
+			};
+
+			let state = match state_file.as_ref().map(|f| f.read())
+			{
+				Some(Ok(state)) => Some(state),
+				Some(Err(e)) =>
+				{
+					warn!("Failed to read state file \"{}\" with error: {}", args.state_file, e);
+					None
+				},
+				None => None,
+			};
+
+			// Build the startup scene before the ports are handed off, since images are read before dropping privileges
+			let mut startup: Vec<IpcCommand> = vec![];
+			for (matrix, left) in [(&config.left_matrix, true), (&config.right_matrix, false)]
+			{
+				if let Some(s) = matrix.as_ref().and_then(|m| m.startup.as_ref())
+				{
+					match scene_commands(s, left)
+					{
+						Ok(commands) => startup.extend(commands),
+						Err(e) => warn!("Failed to load the startup scene with error: {}", e),
+					}
+				}
+			}
+
+			let mut handler_data = HandlerData::new(config.left_matrix, config.right_matrix);
+			handler_data.sensors = config.sensors;
+
+			// Open the ports up front so that the handles survive dropping privileges
+			let (left_port, right_port) = handler_data.get_ports();
+			for port in [left_port, right_port].into_iter().flatten()
+			{
+				if let Err(e) = open(port)
+				{
+					warn!("Failed to open port \"{}\" with error: {}", port.port, e);
+				}
+			}
+
+			// The input devices are watched from before privileges are dropped as well
+			let activity_source = match config.idle.as_ref().map(|i| open_source(&i.source))
+			{
+				Some(Ok(source)) => Some(source),
+				Some(Err(e)) =>
+				{
+					warn!("Failed to open the idle activity source with error: {}", e);
+					None
+				},
+				None => None,
+			};
+
+			#[cfg(feature = "http")]
+			let http_server = match config.http.as_ref().map(http::bind)
+			{
+				Some(Ok(server)) => Some(server),
+				Some(Err(e)) =>
+				{
+					warn!("Failed to start the HTTP API with error: {}", e);
+					None
+				},
+				None => None,
+			};
+
+			#[cfg(feature = "dbus")]
+			let dbus_connection = match config.dbus.as_ref().map(dbus::connect)
+			{
+				Some(Ok(connection)) => Some(connection),
+				Some(Err(e)) =>
+				{
+					warn!("Failed to connect to D-Bus with error: {}", e);
+					None
+				},
+				None => None,
+			};
+
+			// The socket is created up front as well, unless the service manager already passed one in
+			let listener = match listener
+			{
+				Some(l) =>
+				{
+					info!("Listening on the socket passed in by the service manager");
+					l
+				},
+				None => ipc::bind(SOCKET_NAME)?,
+			};
+
+			// The state is written to a new file that replaces the old one, so the user needs to own the directory it's in
+			#[cfg(unix)]
+			{
+				let state_directory = state_file.as_ref().and_then(|f| f.own_directory());
+				if state_file.is_some()
+				   && state_directory.is_none()
+				   && (config.user.is_some() || config.group.is_some())
+				{
+					warn!("The state file shares its directory with other files, so it can only be saved if the directory is writable after dropping privileges");
+				}
+
+				daemon::drop_privileges(config.user.as_deref(), config.group.as_deref(), state_directory.as_deref())?;
+			}
+
+			let data = Arc::new(Mutex::new(handler_data));
+
+			if let Some(state) = state
+			{
+				info!("Restoring the previous state");
+				for command in state.commands()
+				{
+					if let Err(e) = handler(command, &mut None, Arc::clone(&data))
+					{
+						warn!("Failed to restore part of the previous state with error: {}", e);
+					}
+				}
+			}
+
+			// The startup scene is applied on top of the restored state
+			if !startup.is_empty()
+			{
+				info!("Displaying the startup scene");
+				for command in startup
+				{
+					if let Err(e) = handler(command, &mut None, Arc::clone(&data))
+					{
+						warn!("Failed to display part of the startup scene with error: {}", e);
+					}
+				}
+			}
+
+			if let Some(file) = state_file
+			{
+				start_state_thread(file, Arc::clone(&data));
+			}
+
+			if let (Some(idle), Some(source)) = (config.idle, activity_source)
+			{
+				start_idle_thread(idle, source, Arc::clone(&data))?;
+			}
+
+			if let Some(notifications) = config.notifications
+			{
+				start_notification_thread(notifications, Arc::clone(&data));
+			}
+
+			#[cfg(feature = "http")]
+			if let Some(server) = http_server
+			{
+				http::start_http_thread(server, Arc::clone(&data));
+			}
+
+			#[cfg(feature = "mqtt")]
+			if let Some(mqtt) = config.mqtt
+			{
+				mqtt::start_mqtt_threads(mqtt, Arc::clone(&data));
+			}
+
+			#[cfg(feature = "dbus")]
+			if let Some(connection) = dbus_connection
+			{
+				if let Err(e) = dbus::start_dbus_thread(connection, Arc::clone(&data))
+				{
+					warn!("Failed to start the D-Bus service with error: {}", e);
+				}
+			}
+
 			info!("Starting keep alive thread");
-			let data = Arc::new(Mutex::new(HandlerData::new(config.left_matrix, config.right_matrix)));
 
 			// Ask for the version every 45 seconds so that the matrixes don't timeout
 			// This can be any command, but it shouldn't refresh/reset the LEDs as that defeats the purpose of this thread
@@ -109,32 +319,36 @@ fn daemon_main(args: Args) -> Result<(), Box<dyn Error>>
 				loop
 				{
 					let mut data = keep_alive_data.lock().unwrap();
+					let data_ref = &mut *data;
+
+					// Looked at even while animating, since that's when a missing matrix matters most
+					for port in [&mut data_ref.left_port, &mut data_ref.right_port].into_iter().flatten()
+					{
+						port.connected = is_present(port);
+					}
 
-					if !data.is_pair_animated()
+					if !data_ref.is_pair_animated()
 					{
-						if !data.is_left_animated()
+						if !data_ref.is_left_animated()
 						{
-							if let Some(ref mut left_port) = &mut data.left_port
+							if let Some(ref mut left_port) = &mut data_ref.left_port
 							{
-								if !left_port.sleeping
-								{
-									let _ = version(left_port);
-								}
+								keep_alive(left_port, &mut data_ref.state.left);
 							}
 						}
 
-						if !data.is_right_animated()
+						if !data_ref.is_right_animated()
 						{
-							if let Some(ref mut right_port) = &mut data.right_port
+							if let Some(ref mut right_port) = &mut data_ref.right_port
 							{
-								if !right_port.sleeping
-								{
-									let _ = version(right_port);
-								}
+								keep_alive(right_port, &mut data_ref.state.right);
 							}
 						}
 					}
 
+					// A matrix could have been unplugged or plugged back in since the last time
+					data.notify();
+
 					// Let the handler thread gain access to the mutex while we sleep
 					drop(data);
 					sleep(Duration::from_secs(45));
@@ -142,7 +356,7 @@ fn daemon_main(args: Args) -> Result<(), Box<dyn Error>>
 			});
 
 			// Listen on the IPC socket and handle requests
-			listen("fw16-led-matrixd.socket".to_string(), handler, Arc::clone(&data))?;
+			listen(listener, handler, Arc::clone(&data))?;
 		},
 		Err(e) => return Err(e),
 	};
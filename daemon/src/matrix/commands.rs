@@ -1,17 +1,15 @@
-use crate::{matrix::open_port, Matrix};
+use crate::{matrix::open, Matrix};
 use std::{
 	error::Error,
 	io::{Read, Write},
 };
 
-fn send_command(matrix: &Matrix,
+fn send_command(matrix: &mut Matrix,
                 command: u8,
                 parameters: Option<Vec<u8>>,
                 response_size: usize)
                 -> Result<Vec<u8>, Box<dyn Error>>
 {
-	let mut port = open_port(&matrix.port, matrix.baudrate)?;
-
 	let request: Vec<u8> = match parameters
 	{
 		Some(mut p) =>
@@ -23,31 +21,42 @@ fn send_command(matrix: &Matrix,
 		None => vec![0x32, 0xAC, command],
 	};
 
-	port.write_all(&request)?;
+	// The matrix may have been replugged since the port was opened, so a failed write is retried once with a fresh handle
+	// Once privileges are dropped, this only works if the port is accessible to the daemon's user or group
+	let written = open(matrix).and_then(|port| Ok(port.write_all(&request)?));
+	if written.is_err()
+	{
+		matrix.handle = None;
+		open(matrix)?.write_all(&request)?;
+	}
 
-	let mut response: Vec<u8> = vec![];
+	// A failed read isn't retried, since the matrix already has the command and would carry it out twice
+	let mut response: Vec<u8> = vec![0; response_size];
 	if response_size > 0
 	{
-		response.resize(response_size, 0);
-		port.read_exact(&mut response)?;
+		if let Err(e) = open(matrix)?.read_exact(&mut response)
+		{
+			matrix.handle = None;
+			return Err(e.into());
+		}
 	}
 
 	Ok(response)
 }
 
-pub fn set_brightness(matrix: &Matrix, brightness: u8) -> Result<(), Box<dyn Error>>
+pub fn set_brightness(matrix: &mut Matrix, brightness: u8) -> Result<(), Box<dyn Error>>
 {
 	send_command(matrix, 0x00, Some(vec![brightness]), 0)?;
 
 	Ok(())
 }
 
-pub fn get_brightness(matrix: &Matrix) -> Result<u8, Box<dyn Error>>
+pub fn get_brightness(matrix: &mut Matrix) -> Result<u8, Box<dyn Error>>
 {
 	Ok(send_command(matrix, 0x00, None, 1)?[0])
 }
 
-pub fn pattern(matrix: &Matrix, pattern: u8, parameters: Option<u8>) -> Result<(), Box<dyn Error>>
+pub fn pattern(matrix: &mut Matrix, pattern: u8, parameters: Option<u8>) -> Result<(), Box<dyn Error>>
 {
 	let parameters = match parameters
 	{
@@ -60,14 +69,14 @@ pub fn pattern(matrix: &Matrix, pattern: u8, parameters: Option<u8>) -> Result<(
 	Ok(())
 }
 
-pub fn bootloader(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn bootloader(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	send_command(matrix, 0x02, None, 0)?;
 
 	Ok(())
 }
 
-pub fn set_sleep(matrix: &Matrix, sleep: bool) -> Result<(), Box<dyn Error>>
+pub fn set_sleep(matrix: &mut Matrix, sleep: bool) -> Result<(), Box<dyn Error>>
 {
 	let parameters = match sleep
 	{
@@ -81,7 +90,7 @@ pub fn set_sleep(matrix: &Matrix, sleep: bool) -> Result<(), Box<dyn Error>>
 }
 
 // Useless command since it wakes up the matrix upon request
-//pub fn is_sleeping(matrix: &Matrix) -> Result<bool, Box<dyn Error>>
+//pub fn is_sleeping(matrix: &mut Matrix) -> Result<bool, Box<dyn Error>>
 //{
 //	match send_command(matrix, 0x03, None, 1)?[0]
 //	{
@@ -90,7 +99,7 @@ pub fn set_sleep(matrix: &Matrix, sleep: bool) -> Result<(), Box<dyn Error>>
 //	}
 //}
 
-pub fn set_scroll(matrix: &Matrix, scroll: bool) -> Result<(), Box<dyn Error>>
+pub fn set_scroll(matrix: &mut Matrix, scroll: bool) -> Result<(), Box<dyn Error>>
 {
 	let parameters = match scroll
 	{
@@ -103,7 +112,7 @@ pub fn set_scroll(matrix: &Matrix, scroll: bool) -> Result<(), Box<dyn Error>>
 	Ok(())
 }
 
-pub fn is_scrolling(matrix: &Matrix) -> Result<bool, Box<dyn Error>>
+pub fn is_scrolling(matrix: &mut Matrix) -> Result<bool, Box<dyn Error>>
 {
 	match send_command(matrix, 0x04, None, 1)?[0]
 	{
@@ -112,21 +121,21 @@ pub fn is_scrolling(matrix: &Matrix) -> Result<bool, Box<dyn Error>>
 	}
 }
 
-pub fn crash(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn crash(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	send_command(matrix, 0x05, None, 0)?;
 
 	Ok(())
 }
 
-pub fn draw_bw(matrix: &Matrix, bitmap: &[u8; 39]) -> Result<(), Box<dyn Error>>
+pub fn draw_bw(matrix: &mut Matrix, bitmap: &[u8; 39]) -> Result<(), Box<dyn Error>>
 {
 	send_command(matrix, 0x06, Some(bitmap.to_vec()), 0)?;
 
 	Ok(())
 }
 
-pub fn stage_column(matrix: &Matrix, column_number: u8, column_vals: &[u8; 34]) -> Result<(), Box<dyn Error>>
+pub fn stage_column(matrix: &mut Matrix, column_number: u8, column_vals: &[u8; 34]) -> Result<(), Box<dyn Error>>
 {
 	let mut parameters: Vec<u8> = vec![column_number];
 	parameters.append(&mut column_vals.to_vec());
@@ -136,14 +145,14 @@ pub fn stage_column(matrix: &Matrix, column_number: u8, column_vals: &[u8; 34])
 	Ok(())
 }
 
-pub fn flush_columns(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn flush_columns(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	send_command(matrix, 0x08, None, 0)?;
 
 	Ok(())
 }
 
-pub fn version(matrix: &Matrix) -> Result<Vec<u8>, Box<dyn Error>>
+pub fn version(matrix: &mut Matrix) -> Result<Vec<u8>, Box<dyn Error>>
 {
 	send_command(matrix, 0x20, None, 3)
 }
@@ -3,13 +3,13 @@ mod error;
 
 use crate::Matrix;
 use error::MatrixError;
-use serial::{open, BaudRate, SerialPort};
+use serial::{BaudRate, SerialPort, SystemPort};
 use shared::check_port;
 use std::{error::Error, time::Duration};
 
 pub use commands::*;
 
-fn open_port(port_name: &str, baudrate: usize) -> Result<impl SerialPort, Box<dyn Error>>
+fn open_port(port_name: &str, baudrate: usize) -> Result<SystemPort, Box<dyn Error>>
 {
 	let (vid, pid) = check_port(port_name)?;
 
@@ -19,7 +19,7 @@ fn open_port(port_name: &str, baudrate: usize) -> Result<impl SerialPort, Box<dy
 	}
 	else
 	{
-		let mut port = open(&port_name)?;
+		let mut port = serial::open(&port_name)?;
 
 		// Serial config for the FW16 LED Serial
 		port.reconfigure(&|settings| {
@@ -37,47 +37,65 @@ fn open_port(port_name: &str, baudrate: usize) -> Result<impl SerialPort, Box<dy
 	}
 }
 
-pub fn render_percentage(matrix: &Matrix, percentage: u8) -> Result<(), Box<dyn Error>>
+/// Open the port for the matrix if it isn't already, and hand back the handle
+pub fn open(matrix: &mut Matrix) -> Result<&mut SystemPort, Box<dyn Error>>
+{
+	if matrix.handle.is_none()
+	{
+		matrix.handle = Some(open_port(&matrix.port, matrix.baudrate)?);
+	}
+
+	// The handle was populated above
+	Ok(matrix.handle.as_mut().unwrap())
+}
+
+/// Whether the matrix is plugged in, which the system knows without the matrix having to be asked
+pub fn is_present(matrix: &Matrix) -> bool
+{
+	check_port(&matrix.port).is_ok()
+}
+
+pub fn render_percentage(matrix: &mut Matrix, percentage: u8) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x00, Some(percentage))
 }
 
-pub fn render_gradient(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_gradient(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x01, None)
 }
 
-pub fn render_double_gradient(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_double_gradient(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x02, None)
 }
 
-pub fn render_lotus_horizontal(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_lotus_horizontal(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x03, None)
 }
 
-pub fn render_zigzag(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_zigzag(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x04, None)
 }
 
-pub fn render_fullbright(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_fullbright(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x05, None)
 }
 
-pub fn render_panic(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_panic(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x06, None)
 }
 
-pub fn render_lotus_vertical(matrix: &Matrix) -> Result<(), Box<dyn Error>>
+pub fn render_lotus_vertical(matrix: &mut Matrix) -> Result<(), Box<dyn Error>>
 {
 	pattern(matrix, 0x07, None)
 }
 
-pub fn render_single(matrix: &Matrix, frame: &[u8; 306]) -> Result<(), Box<dyn Error>>
+pub fn render_single(matrix: &mut Matrix, frame: &[u8; 306]) -> Result<(), Box<dyn Error>>
 {
 	stage_column(matrix, 0, <&[u8; 34]>::try_from(&frame[0..=33])?)?;
 	stage_column(matrix, 1, <&[u8; 34]>::try_from(&frame[34..=67])?)?;
@@ -93,7 +111,8 @@ pub fn render_single(matrix: &Matrix, frame: &[u8; 306]) -> Result<(), Box<dyn E
 	Ok(())
 }
 
-pub fn render_pair(left_matrix: &Matrix, right_matrix: &Matrix, frame: &[u8; 612]) -> Result<(), Box<dyn Error>>
+pub fn render_pair(left_matrix: &mut Matrix, right_matrix: &mut Matrix, frame: &[u8; 612])
+                   -> Result<(), Box<dyn Error>>
 {
 	stage_column(left_matrix, 0, <&[u8; 34]>::try_from(&frame[0..=33])?)?;
 	stage_column(left_matrix, 1, <&[u8; 34]>::try_from(&frame[34..=67])?)?;
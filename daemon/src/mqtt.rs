@@ -0,0 +1,313 @@
+use crate::{
+	config::Mqtt,
+	handler::{run, HandlerData},
+	state::{pattern_command, Pattern},
+};
+use log::{info, warn};
+use rumqttc::{Client, Event as MqttEvent, LastWill, MqttOptions, Packet, QoS};
+use serde_json::to_string;
+use shared::{image::ImageOptions, ipc::*};
+use std::{
+	error::Error,
+	sync::{mpsc::channel, Arc, Mutex},
+	thread::{sleep, spawn},
+	time::Duration,
+};
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_CLIENT_ID: &str = "fw16-led-matrixd";
+const DEFAULT_TOPIC: &str = "fw16-led-matrix";
+
+fn side_name(side: Side) -> &'static str
+{
+	match side
+	{
+		Side::Left => "left",
+		Side::Right => "right",
+	}
+}
+
+/// Connect to the broker, then run the commands published to it and publish whatever changes on the matrixes
+pub fn start_mqtt_threads(mqtt: Mqtt, data: Arc<Mutex<HandlerData>>)
+{
+	let prefix = mqtt.topic.unwrap_or(DEFAULT_TOPIC.to_string());
+	let availability = format!("{}/available", prefix);
+
+	let mut options = MqttOptions::new(
+	                                   mqtt.client_id.unwrap_or(DEFAULT_CLIENT_ID.to_string()),
+	                                   mqtt.host.clone(),
+	                                   mqtt.port.unwrap_or(DEFAULT_PORT),
+	);
+	options.set_keep_alive(Duration::from_secs(30));
+	options.set_last_will(LastWill::new(&availability, "offline", QoS::AtLeastOnce, true));
+	if let Some(username) = mqtt.username
+	{
+		options.set_credentials(username, mqtt.password.unwrap_or_default());
+	}
+
+	info!("Connecting to the MQTT broker at {}", mqtt.host);
+	let (client, mut connection) = Client::new(options, 64);
+
+	let (sender, receiver) = channel();
+	data.lock().unwrap().subscribe(sender);
+
+	let event_client = client.clone();
+	let event_prefix = prefix.clone();
+	spawn(move || {
+		for event in receiver
+		{
+			let (topic, payload, retain) = event_message(&event_prefix, event);
+			if let Err(e) = event_client.try_publish(topic, QoS::AtLeastOnce, retain, payload)
+			{
+				warn!("Failed to publish to the MQTT broker with error: {}", e);
+			}
+		}
+	});
+
+	spawn(move || {
+		let command_topic = format!("{}/+/+/set", prefix);
+
+		for notification in connection.iter()
+		{
+			match notification
+			{
+				// The broker forgets about subscriptions when the connection drops, so they're redone on every connect
+				Ok(MqttEvent::Incoming(Packet::ConnAck(_))) =>
+				{
+					info!("Connected to the MQTT broker");
+
+					let mut messages = vec![(availability.clone(), "online".to_string(), true)];
+					messages.extend(status_messages(&prefix, data.lock().unwrap().status()));
+
+					if let Err(e) = client.try_subscribe(&command_topic, QoS::AtLeastOnce)
+					{
+						warn!("Failed to subscribe to \"{}\" with error: {}", command_topic, e);
+					}
+
+					for (topic, payload, retain) in messages
+					{
+						if let Err(e) = client.try_publish(topic, QoS::AtLeastOnce, retain, payload)
+						{
+							warn!("Failed to publish to the MQTT broker with error: {}", e);
+						}
+					}
+				},
+				Ok(MqttEvent::Incoming(Packet::Publish(publish))) =>
+				{
+					let result: Result<IpcResponse, Box<dyn Error>> =
+						match parse_command(&prefix, &publish.topic, &publish.payload)
+						{
+							Ok(command) => run(command, &data).map_err(|e| e.into()),
+							Err(e) => Err(e),
+						};
+
+					// Failures to draw are already published as render errors by the handler
+					if let Err(e) = result
+					{
+						warn!("Failed to execute the command on \"{}\" with error: {}", publish.topic, e);
+					}
+				},
+				Ok(_) => (),
+				Err(e) =>
+				{
+					// The connection retries on the next iteration, so this just keeps it from spinning
+					warn!("Lost the connection to the MQTT broker with error: {}", e);
+					sleep(Duration::from_secs(5));
+				},
+			}
+		}
+	});
+}
+
+/// Turn a message on one of the command topics, such as "fw16-led-matrix/left/brightness/set", into a command
+fn parse_command(prefix: &str, topic: &str, payload: &[u8]) -> Result<IpcCommand, Box<dyn Error>>
+{
+	let parts: Vec<&str> = topic.strip_prefix(prefix).unwrap_or(topic).trim_start_matches('/').split('/').collect();
+	let [matrix, command, "set"] = parts[..]
+	else
+	{
+		return Err(format!("unknown topic \"{}\"", topic).into());
+	};
+
+	let target = match matrix
+	{
+		"left" => Target::Left,
+		"right" => Target::Right,
+		"both" => Target::Both,
+		"pair" => Target::Pair,
+		m => return Err(format!("unknown matrix \"{}\", expected left, right, both or pair", m).into()),
+	};
+	let (left, right) = (target != Target::Right, target != Target::Left);
+
+	let payload = String::from_utf8(payload.to_vec())?.trim().to_string();
+
+	if target == Target::Pair && !matches!(command, "text" | "file")
+	{
+		return Err(format!("{} can't be set on a pair", command).into());
+	}
+
+	match command
+	{
+		"brightness" =>
+		{
+			let brightness: u8 = payload.parse()?;
+			Ok(IpcCommand::SetBrightness(left.then_some(brightness), right.then_some(brightness)))
+		},
+		"sleep" =>
+		{
+			let sleeping: bool = payload.parse()?;
+			Ok(IpcCommand::SetSleep(left.then_some(sleeping), right.then_some(sleeping)))
+		},
+		"pattern" => Ok(pattern_command(&payload.parse::<Pattern>()?, left, right)),
+		"text" => Ok(match target
+		{
+			Target::Pair => IpcCommand::RenderTextPair(payload),
+			_ => IpcCommand::RenderText(left.then(|| payload.clone()), right.then_some(payload)),
+		}),
+		"file" => Ok(IpcCommand::RenderFile { path: payload, target, options: ImageOptions::default() }),
+		c => Err(format!("unknown command \"{}\"", c).into()),
+	}
+}
+
+/// Retained messages describing everything that's known about the matrixes, sent whenever the daemon connects
+fn status_messages(prefix: &str, status: Status) -> Vec<(String, String, bool)>
+{
+	let mut messages = vec![];
+
+	for (side, matrix) in [(Side::Left, status.left), (Side::Right, status.right)]
+	{
+		let Some(matrix) = matrix
+		else
+		{
+			continue;
+		};
+
+		let topic = format!("{}/{}", prefix, side_name(side));
+		messages.push((format!("{}/connected", topic), matrix.connected.to_string(), true));
+		messages.push((format!("{}/sleeping", topic), matrix.sleeping.to_string(), true));
+		messages.push((format!("{}/content", topic), to_string(&matrix.content).unwrap_or_default(), true));
+
+		if let Some(brightness) = matrix.brightness
+		{
+			messages.push((format!("{}/brightness", topic), brightness.to_string(), true));
+		}
+
+		if let Some(scrolling) = matrix.scroll
+		{
+			messages.push((format!("{}/scrolling", topic), scrolling.to_string(), true));
+		}
+	}
+
+	messages
+}
+
+/// The message for an event, which is retained when it describes the state of a matrix
+fn event_message(prefix: &str, event: Event) -> (String, String, bool)
+{
+	let topic = |side: Side, name: &str| format!("{}/{}/{}", prefix, side_name(side), name);
+
+	match event
+	{
+		Event::ContentChanged { matrix, content } =>
+		{
+			(topic(matrix, "content"), to_string(&content).unwrap_or_default(), true)
+		},
+		Event::AnimationFinished { matrix } => (topic(matrix, "animation_finished"), String::new(), false),
+		Event::BrightnessChanged { matrix, brightness } => (topic(matrix, "brightness"), brightness.to_string(), true),
+		Event::SleepChanged { matrix, sleeping } => (topic(matrix, "sleeping"), sleeping.to_string(), true),
+		Event::ScrollChanged { matrix, scrolling } => (topic(matrix, "scrolling"), scrolling.to_string(), true),
+		Event::Connected { matrix } => (topic(matrix, "connected"), "true".to_string(), true),
+		Event::Disconnected { matrix } => (topic(matrix, "connected"), "false".to_string(), true),
+		Event::RenderError { message } => (format!("{}/error", prefix), message, false),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn parse(topic: &str, payload: &str) -> Result<IpcCommand, Box<dyn Error>>
+	{
+		parse_command("leds", topic, payload.as_bytes())
+	}
+
+	#[test]
+	fn topics_are_turned_into_commands()
+	{
+		assert!(matches!(parse("leds/left/brightness/set", "100"), Ok(IpcCommand::SetBrightness(Some(100), None))));
+		assert!(matches!(parse("leds/both/sleep/set", " true\n"), Ok(IpcCommand::SetSleep(Some(true), Some(true)))));
+		assert!(matches!(parse("leds/right/pattern/set", "zigzag"), Ok(IpcCommand::PatternZigzag(false, true))));
+		assert!(matches!(parse("leds/right/pattern/set", "40"), Ok(IpcCommand::PatternPercentage(None, Some(40)))));
+		assert!(matches!(parse("leds/pair/text/set", "hi"), Ok(IpcCommand::RenderTextPair(t)) if t == "hi"));
+		assert!(matches!(parse("leds/left/file/set", "/a.png"),
+		                 Ok(IpcCommand::RenderFile { target: Target::Left, path, .. }) if path == "/a.png"));
+	}
+
+	#[test]
+	fn unknown_topics_are_errors()
+	{
+		assert!(parse("leds/left/brightness", "100").is_err());
+		assert!(parse("leds/middle/brightness/set", "100").is_err());
+		assert!(parse("leds/left/colour/set", "red").is_err());
+		assert!(parse("leds/left/brightness/set/more", "100").is_err());
+	}
+
+	#[test]
+	fn pairs_only_take_text_and_files()
+	{
+		assert!(parse("leds/pair/brightness/set", "100").is_err());
+		assert!(parse("leds/pair/sleep/set", "true").is_err());
+		assert!(parse("leds/pair/pattern/set", "zigzag").is_err());
+		assert!(parse("leds/pair/file/set", "/a.png").is_ok());
+	}
+
+	#[test]
+	fn bad_payloads_are_errors()
+	{
+		assert!(parse("leds/left/brightness/set", "256").is_err());
+		assert!(parse("leds/left/brightness/set", "bright").is_err());
+		assert!(parse("leds/left/sleep/set", "yes").is_err());
+		assert!(parse("leds/left/pattern/set", "checkers").is_err());
+		assert!(parse_command("leds", "leds/left/text/set", &[0xFF, 0xFE]).is_err());
+	}
+
+	#[test]
+	fn state_events_are_retained()
+	{
+		let (topic, payload, retain) =
+			event_message("leds", Event::ScrollChanged { matrix: Side::Right, scrolling: true });
+		assert_eq!((topic.as_str(), payload.as_str(), retain), ("leds/right/scrolling", "true", true));
+
+		let (topic, payload, retain) = event_message("leds", Event::Disconnected { matrix: Side::Left });
+		assert_eq!((topic.as_str(), payload.as_str(), retain), ("leds/left/connected", "false", true));
+
+		// Things that happen once aren't state, so they aren't kept for the next subscriber
+		let (topic, _, retain) = event_message("leds", Event::AnimationFinished { matrix: Side::Left });
+		assert_eq!((topic.as_str(), retain), ("leds/left/animation_finished", false));
+
+		let (topic, payload, retain) = event_message("leds", Event::RenderError { message: "oops".to_string() });
+		assert_eq!((topic.as_str(), payload.as_str(), retain), ("leds/error", "oops", false));
+	}
+
+	#[test]
+	fn status_includes_scrolling()
+	{
+		let matrix = MatrixStatus { port: "/dev/ttyACM0".to_string(),
+		                            connected: true,
+		                            version: None,
+		                            brightness: Some(10),
+		                            sleeping: false,
+		                            scroll: Some(false),
+		                            content: None,
+		                            pair: false,
+		                            frame: None,
+		                            loops: None };
+		let status = Status { uptime: Duration::ZERO, left: Some(matrix), right: None };
+
+		let messages = status_messages("leds", status);
+		assert!(messages.contains(&("leds/left/scrolling".to_string(), "false".to_string(), true)));
+		assert!(messages.contains(&("leds/left/brightness".to_string(), "10".to_string(), true)));
+		assert!(messages.iter().all(|(topic, ..)| topic.starts_with("leds/left/")));
+	}
+}
@@ -0,0 +1,280 @@
+use crate::{
+	config::{NotificationDisplay, Notifications},
+	handler::{handler, HandlerData},
+};
+use log::{info, warn};
+use shared::{
+	canvas::Canvas,
+	ipc::{IpcCommand, Target, Urgency},
+	text::render_text,
+};
+use std::{
+	sync::{mpsc::channel, Arc, Mutex},
+	thread::{sleep, spawn},
+	time::Duration,
+};
+
+// Seconds a notification is shown for when the config doesn't say
+const DEFAULT_DURATION: u64 = 5;
+
+// A bell, one row per entry with the left column in bit 8
+const BELL: [u16; 10] = [0b000010000,
+                         0b000111000,
+                         0b001111100,
+                         0b001111100,
+                         0b001111100,
+                         0b011111110,
+                         0b111111111,
+                         0b000000000,
+                         0b000111000,
+                         0b000010000];
+
+/// A desktop notification as it was forwarded to the daemon
+pub struct Notification
+{
+	pub app: String,
+	pub summary: String,
+	pub urgency: Urgency,
+}
+
+/// Show notifications one at a time as they come in, putting back whatever was displayed before each of them
+pub fn start_notification_thread(config: Notifications, data: Arc<Mutex<HandlerData>>)
+{
+	let (sender, receiver) = channel();
+	data.lock().unwrap().notifications = Some(sender);
+
+	info!("Starting notification thread");
+	spawn(move || {
+		for notification in receiver
+		{
+			show(&config, notification, &data);
+		}
+	});
+}
+
+/// What the config says to show for a notification, if anything
+fn display(config: &Notifications, notification: &Notification) -> Option<NotificationDisplay>
+{
+	let rule = config.rules.iter().find(|r| r.app.eq_ignore_ascii_case(&notification.app));
+
+	let urgency = rule.and_then(|r| r.urgency).or(config.urgency).unwrap_or(Urgency::Low);
+	if notification.urgency < urgency
+	{
+		return None;
+	}
+
+	match rule.and_then(|r| r.show).or(config.show).unwrap_or(NotificationDisplay::Summary)
+	{
+		NotificationDisplay::Nothing => None,
+		display => Some(display),
+	}
+}
+
+fn show(config: &Notifications, notification: Notification, data: &Arc<Mutex<HandlerData>>)
+{
+	let Some(display) = display(config, &notification)
+	else
+	{
+		return;
+	};
+
+	let target = config.matrix.unwrap_or(Target::Both);
+	let pair = target == Target::Pair;
+
+	// Notifications without a summary fall back to the name of the app
+	let frames = match display
+	{
+		NotificationDisplay::Icon => bell(pair),
+		NotificationDisplay::App => render_text(&notification.app, pair),
+		_ if notification.summary.is_empty() => render_text(&notification.app, pair),
+		_ => render_text(&notification.summary, pair),
+	};
+
+	// Scrolling text stays up until it's gone by once
+	let scroll: Duration = frames.iter().map(|(_, duration)| *duration).sum();
+	let shown = scroll.max(Duration::from_secs(config.duration.unwrap_or(DEFAULT_DURATION)));
+
+	// The notification shouldn't be remembered, so the state is put back once it's displayed
+	let (saved, target) = {
+		let mut data = data.lock().unwrap();
+
+		// Nobody is around to see it
+		if data.idle
+		{
+			return;
+		}
+
+		data.interrupted = true;
+		data.temporary = true;
+
+		// Both matrixes just means whichever ones there are
+		let target = match (target, data.left_port.is_some(), data.right_port.is_some())
+		{
+			(Target::Both, true, false) => Target::Left,
+			(Target::Both, false, true) => Target::Right,
+			(target, ..) => target,
+		};
+
+		(data.state.clone(), target)
+	};
+
+	info!("Showing a notification from {}", notification.app);
+	run(vec![frames_command(frames, target)], data);
+	{
+		let mut data = data.lock().unwrap();
+		data.state = saved;
+		data.temporary = false;
+	}
+
+	sleep(shown);
+
+	let commands = {
+		let mut data = data.lock().unwrap();
+		data.interrupted = false;
+
+		// Going idle in the meantime took over the matrixes, and they'll be restored when the user comes back
+		if data.idle
+		{
+			return;
+		}
+
+		// Anything that had nothing on it before goes blank again
+		let mut commands = vec![];
+		let left = target != Target::Right && data.left_port.is_some() && data.state.left.content.is_none();
+		let right = target != Target::Left && data.right_port.is_some() && data.state.right.content.is_none();
+		if data.state.pair.is_none() && (left || right)
+		{
+			let blank = Canvas::single().to_frames();
+			commands.push(IpcCommand::RenderSingle(left.then(|| blank.clone()), right.then_some(blank)));
+		}
+
+		commands.extend(data.state.commands());
+		commands
+	};
+
+	run(commands, data);
+}
+
+/// The bell flashing on and off, in the middle of the matrix or pair
+fn bell(pair: bool) -> Vec<(Vec<u8>, Duration)>
+{
+	let mut canvas = Canvas::new(pair);
+	let left = (canvas.width() - 9) / 2;
+	let top = (canvas.height() - BELL.len()) / 2;
+
+	for (y, row) in BELL.iter().enumerate()
+	{
+		for x in 0..9
+		{
+			if row & (1 << (8 - x)) != 0
+			{
+				canvas.set(left + x, top + y, 0xFF);
+			}
+		}
+	}
+
+	let on = canvas.pixels().to_vec();
+	canvas.clear();
+
+	vec![(on, Duration::from_millis(400)), (canvas.pixels().to_vec(), Duration::from_millis(200))]
+}
+
+fn frames_command(frames: Vec<(Vec<u8>, Duration)>, target: Target) -> IpcCommand
+{
+	match target
+	{
+		Target::Left => IpcCommand::RenderSingle(Some(frames), None),
+		Target::Right => IpcCommand::RenderSingle(None, Some(frames)),
+		Target::Both => IpcCommand::RenderSingle(Some(frames.clone()), Some(frames)),
+		Target::Pair => IpcCommand::RenderPair(frames),
+	}
+}
+
+fn run(commands: Vec<IpcCommand>, data: &Arc<Mutex<HandlerData>>)
+{
+	for command in commands
+	{
+		if let Err(e) = handler(command, &mut None, Arc::clone(data))
+		{
+			warn!("Notification thread failed to send a command with error: {}", e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::config::NotificationRule;
+
+	fn settings(show: Option<NotificationDisplay>,
+	            urgency: Option<Urgency>,
+	            rules: Vec<NotificationRule>)
+	            -> Notifications
+	{
+		Notifications { duration: None, matrix: None, show, urgency, rules }
+	}
+
+	fn rule(app: &str, show: Option<NotificationDisplay>, urgency: Option<Urgency>) -> NotificationRule
+	{
+		NotificationRule { app: app.to_string(), show, urgency }
+	}
+
+	fn notification(app: &str, urgency: Urgency) -> Notification
+	{
+		Notification { app: app.to_string(), summary: "New message".to_string(), urgency }
+	}
+
+	#[test]
+	fn everything_is_summarised_by_default()
+	{
+		let config = settings(None, None, vec![]);
+		assert!(display(&config, &notification("mail", Urgency::Low)) == Some(NotificationDisplay::Summary));
+	}
+
+	#[test]
+	fn less_urgent_notifications_are_ignored()
+	{
+		let config = settings(Some(NotificationDisplay::Icon), Some(Urgency::Normal), vec![]);
+		assert!(display(&config, &notification("mail", Urgency::Low)).is_none());
+		assert!(display(&config, &notification("mail", Urgency::Normal)) == Some(NotificationDisplay::Icon));
+		assert!(display(&config, &notification("mail", Urgency::Critical)) == Some(NotificationDisplay::Icon));
+	}
+
+	#[test]
+	fn rules_override_the_defaults_for_their_app()
+	{
+		let rules = vec![
+		                 rule("Chat", Some(NotificationDisplay::App), Some(Urgency::Low)),
+		                 rule("chat", Some(NotificationDisplay::Icon), None),
+		                 rule("updates", None, Some(Urgency::Critical))
+		];
+		let config = settings(Some(NotificationDisplay::Summary), Some(Urgency::Normal), rules);
+
+		// The first matching rule wins, whatever the case of the app's name
+		assert!(display(&config, &notification("chat", Urgency::Low)) == Some(NotificationDisplay::App));
+
+		// Whatever a rule leaves out comes from the defaults
+		assert!(display(&config, &notification("updates", Urgency::Normal)).is_none());
+		assert!(display(&config, &notification("updates", Urgency::Critical)) == Some(NotificationDisplay::Summary));
+
+		assert!(display(&config, &notification("mail", Urgency::Low)).is_none());
+	}
+
+	#[test]
+	fn nothing_shows_nothing()
+	{
+		let rules = vec![rule("noisy", Some(NotificationDisplay::Nothing), None)];
+		let config = settings(None, None, rules);
+		assert!(display(&config, &notification("noisy", Urgency::Critical)).is_none());
+		assert!(display(&config, &notification("quiet", Urgency::Critical)).is_some());
+
+		let config = settings(
+		                      Some(NotificationDisplay::Nothing),
+		                      None,
+		                      vec![rule("chat", Some(NotificationDisplay::Icon), None)],
+		);
+		assert!(display(&config, &notification("mail", Urgency::Critical)).is_none());
+		assert!(display(&config, &notification("chat", Urgency::Low)) == Some(NotificationDisplay::Icon));
+	}
+}
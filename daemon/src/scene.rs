@@ -0,0 +1,39 @@
+use crate::{
+	config::Scene,
+	state::{content_command, Content},
+};
+use shared::{
+	image::{read_image, ImageOptions},
+	ipc::IpcCommand,
+};
+use std::error::Error;
+
+/// Build the commands that display a scene on the left or right matrix
+pub fn scene_commands(scene: &Scene, left: bool) -> Result<Vec<IpcCommand>, Box<dyn Error>>
+{
+	let right = !left;
+	let mut commands: Vec<IpcCommand> = vec![];
+
+	if let Some(b) = scene.brightness
+	{
+		commands.push(IpcCommand::SetBrightness(left.then_some(b), right.then_some(b)));
+	}
+
+	// The config validation ensures only one of these is set
+	let content = if let Some(image) = &scene.image
+	{
+		Some(Content::Frames(read_image(image.clone(), false, &ImageOptions::default())?))
+	}
+	else if let Some(text) = &scene.text
+	{
+		Some(Content::Text(text.clone()))
+	}
+	else
+	{
+		scene.pattern.clone().map(Content::Pattern)
+	};
+
+	commands.extend(content_command(&content, left));
+
+	Ok(commands)
+}
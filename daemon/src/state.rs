@@ -0,0 +1,543 @@
+use crate::handler::HandlerData;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, Value};
+use shared::{
+	canvas::Canvas,
+	image::ImageOptions,
+	ipc::{ContentKind, IpcCommand, Target},
+	text::render_text,
+	widget::Widget,
+};
+use std::{
+	error::Error,
+	fs::{read_dir, read_to_string, rename, File, OpenOptions},
+	io::Write,
+	mem,
+	path::PathBuf,
+	str::FromStr,
+	sync::{Arc, Mutex},
+	thread::{sleep, spawn},
+	time::Duration,
+};
+
+// How long a change to the state can go unsaved
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern
+{
+	Percentage(u8),
+	Gradient,
+	DoubleGradient,
+	LotusHorizontal,
+	LotusVertical,
+	Zigzag,
+	Fullbright,
+	Panic,
+}
+
+/// Patterns are either a percentage or the name of the pattern, such as "double_gradient"
+impl FromStr for Pattern
+{
+	type Err = Box<dyn Error>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err>
+	{
+		match s.parse::<u8>()
+		{
+			Ok(p) => Ok(Pattern::Percentage(p)),
+			Err(_) => Ok(from_value(Value::String(s.to_string()))?),
+		}
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Content
+{
+	Pattern(Pattern),
+	BW(Vec<u8>),
+	Frames(Vec<(Vec<u8>, Duration)>),
+	Text(String),
+	File(String, ImageOptions),
+	Widget(Widget),
+}
+
+impl Content
+{
+	pub fn kind(&self) -> ContentKind
+	{
+		match self
+		{
+			Content::Pattern(pattern) => ContentKind::Pattern(match pattern
+			{
+				Pattern::Percentage(p) => format!("percentage {}", p),
+				Pattern::Gradient => "gradient".to_string(),
+				Pattern::DoubleGradient => "double gradient".to_string(),
+				Pattern::LotusHorizontal => "lotus horizontal".to_string(),
+				Pattern::LotusVertical => "lotus vertical".to_string(),
+				Pattern::Zigzag => "zigzag".to_string(),
+				Pattern::Fullbright => "fullbright".to_string(),
+				Pattern::Panic => "panic".to_string(),
+			}),
+			Content::BW(_) => ContentKind::Bitmap,
+			Content::Frames(frames) if frames.len() > 1 => ContentKind::Animation,
+			Content::Frames(_) => ContentKind::Image,
+			Content::Text(text) => ContentKind::Text(text.clone()),
+			Content::File(path, _) => ContentKind::File(path.clone()),
+			Content::Widget(widget) => ContentKind::Widget(widget.name().to_string()),
+		}
+	}
+
+	// Only frames and text can be split up without reading anything, a file's frames are known once it's displayed
+	fn pair_frame(&self) -> Option<Vec<u8>>
+	{
+		match self
+		{
+			Content::Frames(frames) => frames.first().map(|(frame, _)| frame.clone()),
+			Content::Text(text) => render_text(text, true).first().map(|(frame, _)| frame.clone()),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct MatrixState
+{
+	pub content: Option<Content>,
+	pub brightness: Option<u8>,
+	pub sleeping: bool,
+	pub scroll: Option<bool>,
+	// Columns that have been staged but not flushed yet
+	#[serde(skip)]
+	pub staged: Option<Vec<u8>>,
+}
+
+impl MatrixState
+{
+	pub fn stage_column(&mut self, col: u8, vals: &[u8])
+	{
+		let staged = self.staged.get_or_insert_with(|| vec![0; 306]);
+		let start = col as usize * 34;
+		staged[start..start + 34].copy_from_slice(vals);
+	}
+}
+
+/// What the matrixes were last told to display, so that it can be restored when the daemon restarts
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct State
+{
+	pub left: MatrixState,
+	pub right: MatrixState,
+	pub pair: Option<Content>,
+	// First frame of the pair, so that either matrix can keep showing its half
+	#[serde(skip)]
+	pair_frame: Option<Vec<u8>>,
+	// Which matrixes got new content since the last time it was checked
+	#[serde(skip)]
+	changed: (bool, bool),
+}
+
+impl State
+{
+	/// Record new content for the given matrixes, leaving the other one alone
+	pub fn set_content(&mut self, left: Option<Content>, right: Option<Content>)
+	{
+		if left.is_none() && right.is_none()
+		{
+			return;
+		}
+
+		// Whichever matrix isn't overwritten keeps showing its half of the pair
+		self.split_pair();
+
+		self.changed = (self.changed.0 || left.is_some(), self.changed.1 || right.is_some());
+
+		if left.is_some()
+		{
+			self.left.content = left;
+		}
+
+		if right.is_some()
+		{
+			self.right.content = right;
+		}
+	}
+
+	/// Give each matrix its half of the pair, so that they can be changed separately
+	fn split_pair(&mut self)
+	{
+		if self.pair.take().is_some()
+		{
+			if let Some(frame) = self.pair_frame.take()
+			{
+				let (l, r) = frame.split_at(306);
+				self.left.content = Some(Content::Frames(vec![(l.to_vec(), Duration::default())]));
+				self.right.content = Some(Content::Frames(vec![(r.to_vec(), Duration::default())]));
+			}
+		}
+	}
+
+	pub fn set_pattern(&mut self, left: bool, right: bool, pattern: Pattern)
+	{
+		self.set_content(left.then(|| Content::Pattern(pattern.clone())), right.then_some(Content::Pattern(pattern)));
+	}
+
+	pub fn flush_columns(&mut self, left: bool, right: bool)
+	{
+		// Flushing without anything staged clears the matrix
+		let left = left.then(|| self.left.staged.take().unwrap_or(vec![0; 306]));
+		let right = right.then(|| self.right.staged.take().unwrap_or(vec![0; 306]));
+
+		self.set_content(
+		                 left.map(|frame| Content::Frames(vec![(frame, Duration::default())])),
+		                 right.map(|frame| Content::Frames(vec![(frame, Duration::default())])),
+		);
+	}
+
+	pub fn set_pair(&mut self, pair: Content)
+	{
+		self.left.content = None;
+		self.right.content = None;
+		self.pair_frame = pair.pair_frame();
+		self.pair = Some(pair);
+		self.changed = (true, true);
+	}
+
+	/// Record where the pair that was just displayed came from, such as a file, keeping the frame it was rendered to
+	pub fn set_pair_source(&mut self, pair: Content)
+	{
+		let frame = self.pair_frame.take();
+		self.set_pair(pair);
+		self.pair_frame = self.pair_frame.take().or(frame);
+	}
+
+	/// Which matrixes got new content since the last call
+	pub fn take_changed(&mut self) -> (bool, bool)
+	{
+		mem::take(&mut self.changed)
+	}
+
+	/// Build the commands that will bring the matrixes back to this state
+	pub fn commands(&self) -> Vec<IpcCommand>
+	{
+		let mut commands: Vec<IpcCommand> = vec![];
+
+		if self.left.brightness.is_some() || self.right.brightness.is_some()
+		{
+			commands.push(IpcCommand::SetBrightness(self.left.brightness, self.right.brightness));
+		}
+
+		match &self.pair
+		{
+			Some(Content::Text(text)) => commands.push(IpcCommand::RenderTextPair(text.clone())),
+			Some(Content::File(path, options)) => commands.push(IpcCommand::RenderFile { path: path.clone(),
+			                                                                             target: Target::Pair,
+			                                                                             options: options.clone() }),
+			Some(Content::Widget(widget)) =>
+			{
+				commands.push(IpcCommand::StartWidget { target: Target::Pair, widget: widget.clone() })
+			},
+			Some(Content::Frames(frames)) => commands.push(IpcCommand::RenderPair(frames.clone())),
+			// Nothing else can span both matrixes
+			_ => (),
+		}
+
+		commands.extend(content_command(&self.left.content, true));
+		commands.extend(content_command(&self.right.content, false));
+
+		if self.left.scroll.is_some() || self.right.scroll.is_some()
+		{
+			commands.push(IpcCommand::SetAnimate(self.left.scroll, self.right.scroll));
+		}
+
+		// Sleeping goes last, since every other command wakes the matrixes back up
+		if self.left.sleeping || self.right.sleeping
+		{
+			commands.push(IpcCommand::SetSleep(
+				self.left.sleeping.then_some(true),
+				self.right.sleeping.then_some(true),
+			));
+		}
+
+		commands
+	}
+
+	/// Build the commands that bring back only what the target is showing, matrixes without content go blank
+	pub fn content_commands(&self, target: Target) -> Vec<IpcCommand>
+	{
+		let mut state = State { pair: self.pair.clone(), pair_frame: self.pair_frame.clone(), ..Default::default() };
+		state.left.content = self.left.content.clone();
+		state.right.content = self.right.content.clone();
+
+		let (left, right) = match target
+		{
+			Target::Left => (true, false),
+			Target::Right => (false, true),
+			_ => (true, true),
+		};
+
+		// A matrix on its own gets back its half of the pair
+		if !(left && right)
+		{
+			state.split_pair();
+			state.left.content = state.left.content.filter(|_| left);
+			state.right.content = state.right.content.filter(|_| right);
+		}
+
+		let blank = |content: &Option<Content>, wanted: bool| {
+			(wanted && state.pair.is_none() && content.is_none()).then(|| Canvas::single().to_frames())
+		};
+
+		let mut commands = vec![];
+		match (blank(&state.left.content, left), blank(&state.right.content, right))
+		{
+			(None, None) => (),
+			(l, r) => commands.push(IpcCommand::RenderSingle(l, r)),
+		}
+
+		commands.extend(state.commands());
+		commands
+	}
+}
+
+/// Draw a pattern on either or both matrixes
+pub fn pattern_command(pattern: &Pattern, left: bool, right: bool) -> IpcCommand
+{
+	match pattern
+	{
+		Pattern::Percentage(p) => IpcCommand::PatternPercentage(left.then_some(*p), right.then_some(*p)),
+		Pattern::Gradient => IpcCommand::PatternGradient(left, right),
+		Pattern::DoubleGradient => IpcCommand::PatternDoubleGradient(left, right),
+		Pattern::LotusHorizontal => IpcCommand::PatternLotusHorizontal(left, right),
+		Pattern::LotusVertical => IpcCommand::PatternLotusVertical(left, right),
+		Pattern::Zigzag => IpcCommand::PatternZigzag(left, right),
+		Pattern::Fullbright => IpcCommand::PatternFullbright(left, right),
+		Pattern::Panic => IpcCommand::PatternPanic(left, right),
+	}
+}
+
+pub fn content_command(content: &Option<Content>, left: bool) -> Option<IpcCommand>
+{
+	let right = !left;
+
+	match content.as_ref()?
+	{
+		Content::Pattern(pattern) => Some(pattern_command(pattern, left, right)),
+		Content::BW(bw) => Some(IpcCommand::DrawBW(left.then(|| bw.clone()), right.then(|| bw.clone()))),
+		Content::Frames(frames) =>
+		{
+			Some(IpcCommand::RenderSingle(left.then(|| frames.clone()), right.then(|| frames.clone())))
+		},
+		Content::Text(text) => Some(IpcCommand::RenderText(left.then(|| text.clone()), right.then(|| text.clone()))),
+		Content::File(path, options) =>
+		{
+			Some(IpcCommand::RenderFile { path: path.clone(),
+			                              target: if left { Target::Left } else { Target::Right },
+			                              options: options.clone() })
+		},
+		Content::Widget(widget) =>
+		{
+			Some(IpcCommand::StartWidget { target: if left { Target::Left } else { Target::Right },
+			                               widget: widget.clone() })
+		},
+	}
+}
+
+/// Where the state is saved, which is written to a temporary file first so that a crash never leaves it half written
+pub struct StateFile
+{
+	path: PathBuf,
+}
+
+impl StateFile
+{
+	pub fn open(path: &str) -> Result<Self, Box<dyn Error>>
+	{
+		// Make sure there's a state file before privileges are dropped, so that its directory can be handed over
+		OpenOptions::new().append(true).create(true).open(path)?;
+
+		Ok(StateFile { path: PathBuf::from(path) })
+	}
+
+	pub fn read(&self) -> Result<State, Box<dyn Error>>
+	{
+		let contents = read_to_string(&self.path)?;
+
+		// A brand new state file is empty
+		if contents.trim().is_empty()
+		{
+			Ok(State::default())
+		}
+		else
+		{
+			Ok(serde_json::from_str(&contents)?)
+		}
+	}
+
+	pub fn write(&self, state: &State) -> Result<(), Box<dyn Error>>
+	{
+		let contents = serde_json::to_string(state)?;
+		let temporary = self.temporary();
+
+		let mut file = File::create(&temporary)?;
+		file.write_all(contents.as_bytes())?;
+		file.sync_all()?;
+		rename(&temporary, &self.path)?;
+
+		// The rename only survives a power loss once the directory itself has been synced
+		#[cfg(unix)]
+		if let Some(directory) = self.path.parent().filter(|d| !d.as_os_str().is_empty())
+		{
+			File::open(directory)?.sync_all()?;
+		}
+
+		Ok(())
+	}
+
+	/// The directory the state file is in, as long as nothing else is kept there
+	/// The state can only be written by the user the daemon runs as once that user owns this directory
+	pub fn own_directory(&self) -> Option<PathBuf>
+	{
+		let directory = self.path.parent().filter(|d| !d.as_os_str().is_empty())?;
+		let temporary = self.temporary();
+		let names = [self.path.file_name()?, temporary.file_name()?];
+
+		let mut entries = read_dir(directory).ok()?;
+		entries.all(|e| e.is_ok_and(|e| names.contains(&e.file_name().as_os_str()))).then(|| directory.to_path_buf())
+	}
+
+	fn temporary(&self) -> PathBuf
+	{
+		let mut temporary = self.path.clone().into_os_string();
+		temporary.push(".tmp");
+		PathBuf::from(temporary)
+	}
+}
+
+/// Save the state every so often when it has changed, so that streamed frames don't each rewrite the state file
+pub fn start_state_thread(file: StateFile, data: Arc<Mutex<HandlerData>>)
+{
+	info!("Starting state thread");
+	spawn(move || {
+		loop
+		{
+			sleep(SAVE_INTERVAL);
+
+			// Writing happens without the lock, since syncing to the disk can take a while
+			let unsaved = data.lock().unwrap().take_unsaved();
+			if let Some(state) = unsaved
+			{
+				if let Err(e) = file.write(&state)
+				{
+					warn!("Failed to save the state file with error: {}", e);
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn pair() -> Vec<(Vec<u8>, Duration)>
+	{
+		let mut frame = vec![1; 306];
+		frame.extend([2; 306]);
+		vec![(frame, Duration::default())]
+	}
+
+	#[test]
+	fn replacing_one_side_keeps_the_other_half_of_the_pair()
+	{
+		let mut state = State::default();
+		state.set_pair(Content::Frames(pair()));
+		state.set_pattern(true, false, Pattern::Gradient);
+
+		assert!(state.pair.is_none());
+		assert!(matches!(state.left.content, Some(Content::Pattern(Pattern::Gradient))));
+		assert!(matches!(&state.right.content, Some(Content::Frames(f)) if f[0].0 == vec![2; 306]));
+	}
+
+	#[test]
+	fn a_file_pair_keeps_the_frame_it_was_rendered_to()
+	{
+		let mut state = State::default();
+		state.set_pair(Content::Frames(pair()));
+		state.set_pair_source(Content::File("/nonexistent.png".to_string(), ImageOptions::default()));
+		state.set_pattern(false, true, Pattern::Zigzag);
+
+		assert!(matches!(&state.left.content, Some(Content::Frames(f)) if f[0].0 == vec![1; 306]));
+	}
+
+	#[test]
+	fn commands_restore_everything_with_sleep_last()
+	{
+		let mut state = State::default();
+		state.set_pattern(true, false, Pattern::Gradient);
+		state.set_content(None, Some(Content::Text("hi".to_string())));
+		state.left.brightness = Some(100);
+		state.right.scroll = Some(true);
+		state.right.sleeping = true;
+
+		let commands = state.commands();
+		assert_eq!(commands.len(), 5);
+		assert!(matches!(commands[0], IpcCommand::SetBrightness(Some(100), None)));
+		assert!(matches!(commands[1], IpcCommand::PatternGradient(true, false)));
+		assert!(matches!(&commands[2], IpcCommand::RenderText(None, Some(t)) if t == "hi"));
+		assert!(matches!(commands[3], IpcCommand::SetAnimate(None, Some(true))));
+		assert!(matches!(commands[4], IpcCommand::SetSleep(None, Some(true))));
+	}
+
+	#[test]
+	fn a_pair_is_restored_across_both_matrixes()
+	{
+		let mut state = State::default();
+		state.set_pair(Content::Frames(pair()));
+		assert!(matches!(&state.commands()[..], [IpcCommand::RenderPair(_)]));
+
+		// A file is read again rather than sending the frames it was rendered to
+		state.set_pair_source(Content::File("/nonexistent.png".to_string(), ImageOptions::default()));
+		assert!(matches!(&state.commands()[..], [IpcCommand::RenderFile { target: Target::Pair, .. }]));
+	}
+
+	#[test]
+	fn content_commands_only_bring_back_the_target()
+	{
+		let mut state = State::default();
+		state.set_pattern(true, false, Pattern::Zigzag);
+		state.left.brightness = Some(50);
+		state.left.sleeping = true;
+
+		// Settings aren't touched, only what's displayed
+		let commands = state.content_commands(Target::Left);
+		assert!(matches!(&commands[..], [IpcCommand::PatternZigzag(true, false)]));
+
+		// A matrix without content goes blank
+		let commands = state.content_commands(Target::Right);
+		assert!(matches!(&commands[..], [IpcCommand::RenderSingle(None, Some(f))] if f[0].0 == vec![0; 306]));
+
+		let commands = state.content_commands(Target::Both);
+		assert!(matches!(
+			&commands[..],
+			[IpcCommand::RenderSingle(None, Some(_)), IpcCommand::PatternZigzag(true, false)]
+		));
+	}
+
+	#[test]
+	fn content_commands_split_a_pair_for_one_side()
+	{
+		let mut state = State::default();
+		state.set_pair(Content::Frames(pair()));
+
+		let commands = state.content_commands(Target::Right);
+		assert!(matches!(&commands[..], [IpcCommand::RenderSingle(None, Some(f))] if f[0].0 == vec![2; 306]));
+
+		// The whole pair comes back when both sides are wanted, and the state itself isn't split
+		assert!(matches!(&state.content_commands(Target::Pair)[..], [IpcCommand::RenderPair(_)]));
+		assert!(state.pair.is_some());
+	}
+}
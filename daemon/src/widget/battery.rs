@@ -0,0 +1,293 @@
+use crate::widget::{font::*, vertical_bar, Generator};
+use shared::{canvas::Canvas, widget::BatteryOptions};
+use std::{
+	error::Error,
+	fs::{read_dir, read_to_string},
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+const WIDTH: usize = 9;
+const HEIGHT: usize = 34;
+const SYSFS: &str = "/sys/class/power_supply";
+
+// Inside of the outline, which is where the gauge fills up
+const FILL_LEFT: usize = 2;
+const FILL_WIDTH: usize = 5;
+const FILL_TOP: usize = 4;
+const FILL_HEIGHT: usize = 28;
+
+// Steps it takes the charging animation to climb from the current level to the top
+const CHARGING_STEPS: u64 = 8;
+
+struct Reading
+{
+	// Between 0 and 1
+	level: f64,
+	charging: bool,
+}
+
+pub struct Battery
+{
+	options: BatteryOptions,
+	root: PathBuf,
+	pair: bool,
+	tick: u64,
+}
+
+impl Battery
+{
+	pub fn new(options: BatteryOptions, pair: bool) -> Self
+	{
+		let root = PathBuf::from(options.sysfs.as_deref().unwrap_or(SYSFS));
+		Battery { options, root, pair, tick: 0 }
+	}
+
+	/// Every battery combined, or just the chosen one, along with whether anything is charging them
+	fn read(&self) -> Result<Reading, Box<dyn Error>>
+	{
+		let (mut now, mut full, mut charging, mut found) = (0.0, 0.0, false, false);
+
+		for entry in read_dir(&self.root)?
+		{
+			let path = entry?.path();
+			let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+			match read_attribute(&path, "type").as_deref()
+			{
+				Some("Mains") | Some("USB") => charging |= read_attribute(&path, "online").as_deref() == Some("1"),
+				Some("Battery") if self.options.battery.as_ref().is_none_or(|b| *b == name) =>
+				{
+					let (battery_now, battery_full) =
+						read_level(&path).ok_or(format!("{} doesn't report its level", name))?;
+					now += battery_now;
+					full += battery_full;
+					found = true;
+
+					charging |= read_attribute(&path, "status").as_deref() == Some("Charging");
+				},
+				_ => (),
+			}
+		}
+
+		if !found
+		{
+			return Err(match &self.options.battery
+			{
+				Some(battery) => format!("there is no battery named {} in {}", battery, self.root.display()).into(),
+				None => format!("there are no batteries in {}", self.root.display()).into(),
+			});
+		}
+
+		Ok(Reading { level: if full > 0.0 { (now / full).clamp(0.0, 1.0) } else { 0.0 }, charging })
+	}
+
+	fn draw_gauge(&self, canvas: &mut Canvas, reading: &Reading, percent: u8)
+	{
+		let critical = !reading.charging && percent <= self.options.critical;
+		let low = !reading.charging && percent <= self.options.low;
+
+		// Flashing is done by leaving things out every other frame
+		let blank = (low || critical) && self.tick % 2 == 1;
+
+		if !(critical && blank)
+		{
+			// The terminal at the top, then the rest of the outline
+			for x in 3..6
+			{
+				canvas.set(x, 0, 0xFF);
+				canvas.set(x, 1, 0xFF);
+			}
+
+			for x in 0..WIDTH
+			{
+				canvas.set(x, 2, 0xFF);
+				canvas.set(x, HEIGHT - 1, 0xFF);
+			}
+
+			for y in 2..HEIGHT
+			{
+				canvas.set(0, y, 0xFF);
+				canvas.set(WIDTH - 1, y, 0xFF);
+			}
+		}
+
+		// While charging the gauge keeps climbing from the current level up to the top
+		let climb = (self.tick % (CHARGING_STEPS + 1)) as f64 / CHARGING_STEPS as f64;
+		let fill = match reading.charging
+		{
+			true => reading.level + (1.0 - reading.level) * climb,
+			false => reading.level,
+		};
+
+		if !blank
+		{
+			vertical_bar(canvas, FILL_LEFT, FILL_WIDTH, FILL_TOP, FILL_HEIGHT, fill);
+		}
+	}
+}
+
+impl Generator for Battery
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let reading = self.read()?;
+		let percent = (reading.level * 100.0).round() as u8;
+
+		let mut canvas = Canvas::new(self.pair);
+		self.draw_gauge(&mut canvas, &reading, percent);
+
+		// The other half of a pair shows the percentage, one digit above the other
+		if self.pair
+		{
+			draw_number(&mut canvas, WIDTH + 3, percent as u64);
+		}
+
+		let delay = match reading.charging
+		{
+			true => Duration::from_millis(150),
+			false if percent <= self.options.critical => Duration::from_millis(250),
+			false if percent <= self.options.low => Duration::from_millis(500),
+			false => Duration::from_secs(5),
+		};
+
+		self.tick += 1;
+		Ok((canvas.pixels().to_vec(), delay))
+	}
+}
+
+fn read_attribute(path: &Path, name: &str) -> Option<String>
+{
+	read_to_string(path.join(name)).ok().map(|v| v.trim().to_string())
+}
+
+/// How much charge the battery has and how much it can hold, in whichever units it reports them
+fn read_level(path: &Path) -> Option<(f64, f64)>
+{
+	let number = |name| read_attribute(path, name).and_then(|v| v.parse::<f64>().ok());
+
+	match (number("energy_now"), number("energy_full"), number("charge_now"), number("charge_full"))
+	{
+		(Some(now), Some(full), ..) | (_, _, Some(now), Some(full)) => Some((now, full)),
+		// Some batteries only report a percentage, which isn't as precise
+		_ => number("capacity").map(|capacity| (capacity, 100.0)),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		env::temp_dir,
+		fs::{create_dir_all, remove_dir_all, write},
+		process,
+	};
+
+	/// A power_supply tree in a temporary directory, removed once the test is done with it
+	struct Sysfs(PathBuf);
+
+	impl Sysfs
+	{
+		fn new(name: &str) -> Self
+		{
+			let root = temp_dir().join(format!("fw16-led-matrixd-{}-{}", name, process::id()));
+			let _ = remove_dir_all(&root);
+			create_dir_all(&root).unwrap();
+			Sysfs(root)
+		}
+
+		fn supply(&self, name: &str, attributes: &[(&str, &str)]) -> &Self
+		{
+			let path = self.0.join(name);
+			create_dir_all(&path).unwrap();
+
+			for (attribute, value) in attributes
+			{
+				write(path.join(attribute), format!("{}\n", value)).unwrap();
+			}
+
+			self
+		}
+
+		fn battery(&self, battery: Option<&str>) -> Battery
+		{
+			let options = BatteryOptions { low: 20,
+			                               critical: 5,
+			                               battery: battery.map(str::to_string),
+			                               sysfs: Some(self.0.to_string_lossy().to_string()) };
+			Battery::new(options, false)
+		}
+	}
+
+	impl Drop for Sysfs
+	{
+		fn drop(&mut self)
+		{
+			let _ = remove_dir_all(&self.0);
+		}
+	}
+
+	#[test]
+	fn batteries_are_combined()
+	{
+		let sysfs = Sysfs::new("battery-combined");
+		sysfs.supply("BAT0", &[("type", "Battery"), ("energy_now", "30"), ("energy_full", "100")])
+		     .supply("BAT1", &[("type", "Battery"), ("charge_now", "50"), ("charge_full", "100")])
+		     .supply("AC", &[("type", "Mains"), ("online", "0")]);
+
+		let reading = sysfs.battery(None).read().unwrap();
+		assert_eq!(reading.level, 0.4);
+		assert!(!reading.charging);
+
+		let reading = sysfs.battery(Some("BAT1")).read().unwrap();
+		assert_eq!(reading.level, 0.5);
+	}
+
+	#[test]
+	fn plugged_in_is_charging()
+	{
+		let sysfs = Sysfs::new("battery-mains");
+		sysfs.supply("BAT0", &[("type", "Battery"), ("capacity", "80"), ("status", "Not charging")])
+		     .supply("ADP1", &[("type", "Mains"), ("online", "1")]);
+
+		let reading = sysfs.battery(None).read().unwrap();
+		assert_eq!(reading.level, 0.8);
+		assert!(reading.charging);
+
+		// Charging draws quicker than a battery that's only running down
+		let (_, delay) = sysfs.battery(None).next_frame().unwrap();
+		assert_eq!(delay, Duration::from_millis(150));
+	}
+
+	#[test]
+	fn a_low_battery_flashes()
+	{
+		let sysfs = Sysfs::new("battery-low");
+		sysfs.supply("BAT0", &[("type", "Battery"), ("capacity", "15"), ("status", "Discharging")]);
+
+		let mut battery = sysfs.battery(None);
+		let (lit, delay) = battery.next_frame().unwrap();
+		let (blank, _) = battery.next_frame().unwrap();
+
+		assert_eq!(delay, Duration::from_millis(500));
+		assert!(lit.iter().filter(|p| **p > 0).count() > blank.iter().filter(|p| **p > 0).count());
+		// Only the gauge flashes, the outline stays
+		assert!(blank.iter().any(|p| *p > 0));
+	}
+
+	#[test]
+	fn missing_batteries_are_errors()
+	{
+		let sysfs = Sysfs::new("battery-missing");
+		sysfs.supply("AC", &[("type", "Mains"), ("online", "1")]);
+		assert!(sysfs.battery(None).read().is_err());
+
+		sysfs.supply("BAT0", &[("type", "Battery"), ("capacity", "50")]);
+		assert!(sysfs.battery(Some("BAT1")).read().is_err());
+
+		// A battery that doesn't say how full it is can't be drawn
+		sysfs.supply("BAT1", &[("type", "Battery")]);
+		assert!(sysfs.battery(Some("BAT1")).read().is_err());
+	}
+}
@@ -0,0 +1,279 @@
+use crate::widget::{font::*, Generator};
+use chrono::{Local, NaiveTime, Timelike};
+use shared::{
+	canvas::Canvas,
+	widget::{ClockOptions, ClockStyle},
+};
+use std::{error::Error, time::Duration};
+
+// Bits that are off are still dimly lit in the binary style, so it's clear where each column is
+const BIT_OFF: u8 = 0x18;
+
+pub struct Clock
+{
+	options: ClockOptions,
+	pair: bool,
+}
+
+impl Clock
+{
+	pub fn new(options: ClockOptions, pair: bool) -> Self
+	{
+		Clock { options, pair }
+	}
+
+	fn draw(&self, time: NaiveTime) -> Canvas
+	{
+		let mut canvas = Canvas::new(self.pair);
+
+		let (pm, hour) = match self.options.hour12
+		{
+			true => (time.hour() >= 12, time.hour12().1),
+			false => (false, time.hour()),
+		};
+
+		match self.options.style
+		{
+			ClockStyle::Digits => self.draw_digits(&mut canvas, hour, time.minute(), pm),
+			ClockStyle::Binary => self.draw_binary(&mut canvas, hour, time.minute(), time.second()),
+		}
+
+		// The bottom two rows fill up over the course of the minute, with the last lit pixel fading in
+		if self.options.seconds && self.options.style == ClockStyle::Digits
+		{
+			let progress = (time.second() as usize * 1000 + time.nanosecond() as usize / 1_000_000) * canvas.width();
+			let (full, partial) = (progress / 60_000, (progress % 60_000) * 255 / 60_000);
+
+			for x in 0..canvas.width()
+			{
+				let brightness = match x
+				{
+					x if x < full => 0xFF,
+					x if x == full => partial as u8,
+					_ => 0,
+				};
+
+				canvas.set(x, canvas.height() - 2, brightness);
+				canvas.set(x, canvas.height() - 1, brightness);
+			}
+		}
+
+		canvas
+	}
+
+	fn draw_digits(&self, canvas: &mut Canvas, hour: u32, minute: u32, pm: bool)
+	{
+		match self.pair
+		{
+			// Hours on the left matrix and minutes on the right, each digit drawn at twice the size
+			true =>
+			{
+				for (left, value) in [(0, hour), (9, minute)]
+				{
+					draw_glyph(canvas, &DIGITS[value as usize / 10], left, 2, 2);
+					draw_glyph(canvas, &DIGITS[value as usize % 10], left, 18, 2);
+				}
+
+				// There's no room for the letters, so a dot in the top right corner means it's the afternoon
+				if pm
+				{
+					canvas.set(17, 0, 0xFF);
+				}
+			},
+			false =>
+			{
+				for (top, value) in [(5, hour), (14, minute)]
+				{
+					draw_glyph(canvas, &DIGITS[value as usize / 10], 0, top, 1);
+					draw_glyph(canvas, &DIGITS[value as usize % 10], GLYPH_WIDTH + 1, top, 1);
+				}
+
+				if self.options.hour12
+				{
+					draw_glyph(canvas, if pm { &PM } else { &AM }, 2, 23, 1);
+				}
+			},
+		}
+	}
+
+	fn draw_binary(&self, canvas: &mut Canvas, hour: u32, minute: u32, second: u32)
+	{
+		let mut columns = vec![hour, minute];
+		if self.options.seconds
+		{
+			columns.push(second);
+		}
+
+		// Every column has room for 6 bits, the most significant one at the top, with a gap between the columns
+		let column_width = canvas.width() / columns.len();
+		let left = (canvas.width() - column_width * columns.len()).div_ceil(2);
+		let top = (canvas.height() - 6 * 4).div_ceil(2);
+
+		for (column, value) in columns.iter().enumerate()
+		{
+			for bit in 0..6
+			{
+				let brightness = match value & (1 << (5 - bit))
+				{
+					0 => BIT_OFF,
+					_ => 0xFF,
+				};
+
+				for x in 0..column_width - 1
+				{
+					for y in 0..3
+					{
+						canvas.set(left + column * column_width + x, top + bit * 4 + y, brightness);
+					}
+				}
+			}
+		}
+	}
+}
+
+impl Generator for Clock
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let now = Local::now().time();
+
+		// Wake up right as the display needs to change, which is on the next second or minute
+		let elapsed = match self.options.seconds
+		{
+			true => Duration::from_nanos(now.nanosecond() as u64 % 1_000_000_000),
+			false => Duration::new(now.second() as u64, now.nanosecond() % 1_000_000_000),
+		};
+		let period = match self.options.seconds
+		{
+			true => Duration::from_secs(1),
+			false => Duration::from_secs(60),
+		};
+
+		Ok((self.draw(now).pixels().to_vec(), period.saturating_sub(elapsed)))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn clock(hour12: bool, seconds: bool, style: ClockStyle, pair: bool) -> Clock
+	{
+		Clock::new(ClockOptions { hour12, seconds, style }, pair)
+	}
+
+	fn time(hour: u32, minute: u32, second: u32) -> NaiveTime
+	{
+		NaiveTime::from_hms_opt(hour, minute, second).unwrap()
+	}
+
+	#[test]
+	fn digits_are_stacked()
+	{
+		let mut expected = Canvas::new(false);
+		draw_glyph(&mut expected, &DIGITS[2], 0, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[1], GLYPH_WIDTH + 1, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[0], 0, 14, 1);
+		draw_glyph(&mut expected, &DIGITS[5], GLYPH_WIDTH + 1, 14, 1);
+
+		let canvas = clock(false, false, ClockStyle::Digits, false).draw(time(21, 5, 0));
+		assert_eq!(canvas.pixels(), expected.pixels());
+	}
+
+	#[test]
+	fn twelve_hour_digits_show_am_or_pm()
+	{
+		let mut expected = Canvas::new(false);
+		draw_glyph(&mut expected, &DIGITS[0], 0, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[9], GLYPH_WIDTH + 1, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[0], 0, 14, 1);
+		draw_glyph(&mut expected, &DIGITS[5], GLYPH_WIDTH + 1, 14, 1);
+		draw_glyph(&mut expected, &PM, 2, 23, 1);
+
+		let canvas = clock(true, false, ClockStyle::Digits, false).draw(time(21, 5, 0));
+		assert_eq!(canvas.pixels(), expected.pixels());
+
+		// Midnight is 12 AM
+		let mut expected = Canvas::new(false);
+		draw_glyph(&mut expected, &DIGITS[1], 0, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[2], GLYPH_WIDTH + 1, 5, 1);
+		draw_glyph(&mut expected, &DIGITS[3], 0, 14, 1);
+		draw_glyph(&mut expected, &DIGITS[0], GLYPH_WIDTH + 1, 14, 1);
+		draw_glyph(&mut expected, &AM, 2, 23, 1);
+
+		let canvas = clock(true, false, ClockStyle::Digits, false).draw(time(0, 30, 0));
+		assert_eq!(canvas.pixels(), expected.pixels());
+	}
+
+	#[test]
+	fn pairs_split_hours_and_minutes()
+	{
+		let mut expected = Canvas::new(true);
+		draw_glyph(&mut expected, &DIGITS[1], 0, 2, 2);
+		draw_glyph(&mut expected, &DIGITS[3], 0, 18, 2);
+		draw_glyph(&mut expected, &DIGITS[0], 9, 2, 2);
+		draw_glyph(&mut expected, &DIGITS[7], 9, 18, 2);
+
+		let canvas = clock(false, false, ClockStyle::Digits, true).draw(time(13, 7, 0));
+		assert_eq!(canvas.pixels(), expected.pixels());
+
+		// The afternoon is marked with a dot rather than letters
+		let mut expected = Canvas::new(true);
+		draw_glyph(&mut expected, &DIGITS[0], 0, 2, 2);
+		draw_glyph(&mut expected, &DIGITS[1], 0, 18, 2);
+		draw_glyph(&mut expected, &DIGITS[0], 9, 2, 2);
+		draw_glyph(&mut expected, &DIGITS[7], 9, 18, 2);
+		expected.set(17, 0, 0xFF);
+
+		let canvas = clock(true, false, ClockStyle::Digits, true).draw(time(13, 7, 0));
+		assert_eq!(canvas.pixels(), expected.pixels());
+	}
+
+	#[test]
+	fn binary_has_a_column_per_unit()
+	{
+		// 10 is 001010, 42 is 101010 and 37 is 100101, with 3 rows per bit and a row between them
+		let canvas = clock(false, true, ClockStyle::Binary, false).draw(time(10, 42, 37));
+		let bit = |column: usize, bit: usize| canvas.get(column * 3, 5 + bit * 4);
+
+		let bits: Vec<Vec<bool>> = (0..3).map(|c| (0..6).map(|b| bit(c, b) == 0xFF).collect()).collect();
+		assert_eq!(bits[0], [false, false, true, false, true, false]);
+		assert_eq!(bits[1], [true, false, true, false, true, false]);
+		assert_eq!(bits[2], [true, false, false, true, false, true]);
+
+		assert_eq!(bit(0, 0), BIT_OFF);
+		// Gaps between the columns and between the bits
+		assert_eq!(canvas.get(2, 5), 0);
+		assert_eq!(canvas.get(0, 8), 0);
+
+		// Without seconds, the hours and minutes share the width and the spare column is split around them
+		let canvas = clock(false, false, ClockStyle::Binary, false).draw(time(10, 42, 37));
+		assert_eq!(canvas.get(0, 5 + 2 * 4), 0);
+		assert_eq!(canvas.get(1, 5 + 2 * 4), 0xFF);
+		assert_eq!(canvas.get(1, 5), BIT_OFF);
+		assert_eq!(canvas.get(5, 5), 0xFF);
+		assert_eq!(canvas.get(8, 5), 0);
+	}
+
+	#[test]
+	fn the_seconds_bar_fills_over_the_minute()
+	{
+		let half = NaiveTime::from_hms_milli_opt(9, 41, 30, 0).unwrap();
+		let canvas = clock(false, true, ClockStyle::Digits, false).draw(half);
+
+		// Halfway through the minute, 4.5 of the 9 columns are lit
+		for y in [32, 33]
+		{
+			let row: Vec<u8> = (0..9).map(|x| canvas.get(x, y)).collect();
+			assert_eq!(row, [0xFF, 0xFF, 0xFF, 0xFF, 127, 0, 0, 0, 0]);
+		}
+
+		let canvas = clock(false, true, ClockStyle::Digits, false).draw(time(9, 41, 0));
+		assert!((0..9).all(|x| canvas.get(x, 33) == 0));
+
+		// Binary clocks show the seconds as a column instead
+		let canvas = clock(false, true, ClockStyle::Binary, false).draw(half);
+		assert!((0..9).all(|x| canvas.get(x, 33) == 0));
+	}
+}
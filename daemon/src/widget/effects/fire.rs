@@ -0,0 +1,58 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+
+pub struct Fire
+{
+	width: usize,
+	// Row-major heat from 0 to 1, with the fuel along the bottom row
+	heat: Vec<f64>,
+}
+
+impl Fire
+{
+	pub fn new(width: usize) -> Self
+	{
+		Fire { width, heat: vec![0.0; width * HEIGHT] }
+	}
+}
+
+impl Step for Fire
+{
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64)
+	{
+		let width = self.width;
+
+		// Denser fire has more fuel, so it burns hotter and more evenly
+		let fuel = (HEIGHT - 1) * width;
+		for x in 0..width
+		{
+			self.heat[fuel + x] = match rng.chance(0.3 + density * 0.6)
+			{
+				true => 1.0,
+				false => rng.float() * 0.3,
+			};
+		}
+
+		// Heat rises a row every frame, spreading out and cooling down as it goes, and bigger fires cool down slower
+		for y in 0..HEIGHT - 1
+		{
+			let below = (y + 1) * width;
+			for x in 0..width
+			{
+				let spread = self.heat[below + x.saturating_sub(1)]
+				             + self.heat[below + x] * 2.0
+				             + self.heat[below + (x + 1).min(width - 1)];
+				let cooling = rng.float() * (0.09 - density * 0.05);
+				self.heat[y * width + x] = (spread / 4.0 - cooling).max(0.0);
+			}
+		}
+
+		for y in 0..HEIGHT
+		{
+			for x in 0..width
+			{
+				canvas.set(x, y, (self.heat[y * width + x] * 255.0) as u8);
+			}
+		}
+	}
+}
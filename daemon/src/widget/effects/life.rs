@@ -0,0 +1,141 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+use std::time::Duration;
+
+// Patterns that never settle down, such as gliders going around, get replaced after this many generations
+const MAX_GENERATIONS: u32 = 1000;
+
+pub struct Life
+{
+	width: usize,
+	// Row-major cells, the edges wrap around so that a pair is one big board
+	cells: Vec<bool>,
+	previous: Vec<bool>,
+	// Cells that just died fade out instead of disappearing straight away
+	glow: Vec<f64>,
+	generation: u32,
+}
+
+impl Life
+{
+	pub fn new(width: usize) -> Self
+	{
+		Life { width, cells: vec![], previous: vec![], glow: vec![0.0; width * HEIGHT], generation: 0 }
+	}
+
+	/// Whether the cell is alive in the next generation, which is born with 3 neighbours and survives with 2 or 3
+	fn survives(&self, i: usize) -> bool
+	{
+		let (x, y) = (i % self.width, i / self.width);
+		let mut count = 0;
+		for dy in [HEIGHT - 1, 0, 1]
+		{
+			for dx in [self.width - 1, 0, 1]
+			{
+				if (dx, dy) != (0, 0) && self.cells[(y + dy) % HEIGHT * self.width + (x + dx) % self.width]
+				{
+					count += 1;
+				}
+			}
+		}
+
+		count == 3 || (count == 2 && self.cells[i])
+	}
+}
+
+impl Step for Life
+{
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64)
+	{
+		// Start over once the board is empty, stuck, or blinking back and forth
+		if self.generation == 0 || self.generation >= MAX_GENERATIONS
+		{
+			self.cells = (0..self.width * HEIGHT).map(|_| rng.chance(0.15 + density * 0.35)).collect();
+			self.previous = vec![];
+			self.generation = 1;
+		}
+		else
+		{
+			let next: Vec<bool> = (0..self.width * HEIGHT).map(|i| self.survives(i)).collect();
+
+			let settled = next == self.cells || next == self.previous;
+			self.previous = std::mem::replace(&mut self.cells, next);
+			self.generation = if settled { 0 } else { self.generation + 1 };
+		}
+
+		for (i, alive) in self.cells.iter().enumerate()
+		{
+			self.glow[i] = if *alive { 1.0 } else { self.glow[i] * 0.5 };
+			canvas.set(i % self.width, i / self.width, (self.glow[i] * 255.0) as u8);
+		}
+	}
+
+	fn delay(&self) -> Duration
+	{
+		Duration::from_millis(150)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	/// A board with only the given cells alive, past its first generation so that it isn't seeded again
+	fn board(width: usize, alive: &[(usize, usize)]) -> Life
+	{
+		let mut life = Life::new(width);
+		life.cells = vec![false; width * HEIGHT];
+		for (x, y) in alive
+		{
+			life.cells[y * width + x] = true;
+		}
+
+		life.generation = 1;
+		life
+	}
+
+	fn step(life: &mut Life)
+	{
+		life.step(&mut Canvas::new(false), &mut Rng::new(0), 0.5);
+	}
+
+	#[test]
+	fn blinkers_blink_then_start_over()
+	{
+		let mut life = board(9, &[(3, 5), (4, 5), (5, 5)]);
+
+		step(&mut life);
+		let vertical: Vec<usize> = life.cells.iter().enumerate().filter(|(_, c)| **c).map(|(i, _)| i).collect();
+		assert_eq!(vertical, [4 * 9 + 4, 5 * 9 + 4, 6 * 9 + 4]);
+
+		// Back where it started, which is stuck, so the next step seeds a new board
+		step(&mut life);
+		assert_eq!(life.generation, 0);
+	}
+
+	#[test]
+	fn the_edges_wrap_around()
+	{
+		// A blinker lying across the left and right edges
+		let mut life = board(9, &[(8, 5), (0, 5), (1, 5)]);
+		step(&mut life);
+		assert!(life.cells[4 * 9] && life.cells[5 * 9] && life.cells[6 * 9]);
+		assert_eq!(life.cells.iter().filter(|c| **c).count(), 3);
+	}
+
+	#[test]
+	fn dead_cells_fade_out()
+	{
+		// A lone cell that was drawn alive last frame, which dies straight away
+		let mut life = board(9, &[(0, 0)]);
+		life.glow[0] = 1.0;
+
+		let mut canvas = Canvas::new(false);
+		life.step(&mut canvas, &mut Rng::new(0), 0.5);
+		assert_eq!(canvas.get(0, 0), 127);
+
+		life.step(&mut canvas, &mut Rng::new(0), 0.5);
+		assert_eq!(canvas.get(0, 0), 63);
+	}
+}
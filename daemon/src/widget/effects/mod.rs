@@ -0,0 +1,238 @@
+mod fire;
+mod life;
+mod plasma;
+mod rain;
+mod ripple;
+mod starfield;
+
+use crate::widget::Generator;
+use shared::{
+	canvas::Canvas,
+	widget::{Effect, EffectOptions},
+};
+use std::{
+	error::Error,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HEIGHT: usize = 34;
+
+/// One of the effects, which keeps whatever it needs between frames and draws each one from scratch
+trait Step: Send
+{
+	/// Move the effect along and draw it, density is always between 0 and 1
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64);
+
+	/// How long each frame lasts at normal speed
+	fn delay(&self) -> Duration
+	{
+		Duration::from_millis(50)
+	}
+}
+
+/// Small xorshift generator, so that the same seed always draws the same effect
+pub struct Rng(u64);
+
+impl Rng
+{
+	pub fn new(seed: u64) -> Self
+	{
+		// Xorshift gets stuck on zero, and similar seeds should still look different
+		let mut state = seed.wrapping_add(0x9E3779B97F4A7C15);
+		state = (state ^ (state >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+		state = (state ^ (state >> 27)).wrapping_mul(0x94D049BB133111EB);
+		Rng((state ^ (state >> 31)).max(1))
+	}
+
+	pub fn next(&mut self) -> u64
+	{
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	/// Somewhere from 0 up to but not including 1
+	pub fn float(&mut self) -> f64
+	{
+		(self.next() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// Somewhere from 0 up to but not including the limit
+	pub fn below(&mut self, limit: usize) -> usize
+	{
+		(self.next() % limit.max(1) as u64) as usize
+	}
+
+	/// True with the given probability
+	pub fn chance(&mut self, probability: f64) -> bool
+	{
+		self.float() < probability
+	}
+}
+
+pub struct Effects
+{
+	step: Box<dyn Step>,
+	rng: Rng,
+	pair: bool,
+	speed: f64,
+	density: f64,
+}
+
+impl Effects
+{
+	pub fn new(options: &EffectOptions, pair: bool) -> Self
+	{
+		let width = Canvas::new(pair).width();
+		let step: Box<dyn Step> = match options.effect
+		{
+			Effect::Rain => Box::new(rain::Rain::default()),
+			Effect::Fire => Box::new(fire::Fire::new(width)),
+			Effect::Plasma => Box::new(plasma::Plasma::default()),
+			Effect::Starfield => Box::new(starfield::Starfield::default()),
+			Effect::Ripple => Box::new(ripple::Ripple::new(width)),
+			Effect::Life => Box::new(life::Life::new(width)),
+		};
+
+		let seed =
+			options.seed.unwrap_or_else(|| {
+				            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+			            });
+
+		// Clamping doesn't do anything about NaN, so anything that isn't a number gets the default
+		let defaults = EffectOptions::default();
+		let finite = |value: f64, default: f64| if value.is_finite() { value } else { default };
+
+		Effects { step,
+		          rng: Rng::new(seed),
+		          pair,
+		          speed: finite(options.speed, defaults.speed).clamp(0.1, 10.0),
+		          density: finite(options.density, defaults.density).clamp(0.0, 1.0) }
+	}
+}
+
+impl Generator for Effects
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let mut canvas = Canvas::new(self.pair);
+		self.step.step(&mut canvas, &mut self.rng, self.density);
+
+		Ok((canvas.pixels().to_vec(), self.step.delay().div_f64(self.speed)))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const EFFECTS: [Effect; 6] =
+		[Effect::Rain, Effect::Fire, Effect::Plasma, Effect::Starfield, Effect::Ripple, Effect::Life];
+
+	fn frames(effect: Effect, pair: bool, seed: u64, density: f64, count: usize) -> Vec<Vec<u8>>
+	{
+		let options = EffectOptions { effect, density, seed: Some(seed), ..Default::default() };
+		let mut effects = Effects::new(&options, pair);
+		(0..count).map(|_| effects.next_frame().unwrap().0).collect()
+	}
+
+	fn lit(frame: &[u8]) -> u64
+	{
+		frame.iter().map(|p| *p as u64).sum()
+	}
+
+	#[test]
+	fn the_same_seed_draws_the_same_effect()
+	{
+		for effect in EFFECTS
+		{
+			assert_eq!(frames(effect, false, 7, 0.5, 20), frames(effect, false, 7, 0.5, 20), "{:?}", effect);
+		}
+
+		// Plasma doesn't use the seed, everything random does
+		for effect in EFFECTS.into_iter().filter(|e| *e != Effect::Plasma)
+		{
+			assert_ne!(frames(effect, false, 7, 0.5, 20), frames(effect, false, 8, 0.5, 20), "{:?}", effect);
+		}
+	}
+
+	#[test]
+	fn every_effect_fills_single_matrixes_and_pairs()
+	{
+		for effect in EFFECTS
+		{
+			for pair in [false, true]
+			{
+				let frames = frames(effect, pair, 1, 0.5, 60);
+				assert!(frames.iter().all(|f| f.len() == Canvas::new(pair).pixels().len()));
+				assert!(frames.iter().any(|f| lit(f) > 0), "{:?} never drew anything", effect);
+
+				// Pairs are drawn across both matrixes, not just the first one
+				if pair
+				{
+					assert!(frames.iter().any(|f| lit(&f[f.len() / 2..]) > 0), "{:?} left half of a pair dark", effect);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn denser_effects_have_more_going_on()
+	{
+		for effect in [Effect::Rain, Effect::Fire, Effect::Starfield]
+		{
+			let sparse: u64 = frames(effect, false, 3, 0.0, 100).iter().map(|f| lit(f)).sum();
+			let dense: u64 = frames(effect, false, 3, 1.0, 100).iter().map(|f| lit(f)).sum();
+			assert!(dense > sparse, "{:?}", effect);
+		}
+	}
+
+	#[test]
+	fn speed_shortens_each_frame()
+	{
+		let delay = |speed| {
+			let options = EffectOptions { effect: Effect::Plasma, speed, ..Default::default() };
+			Effects::new(&options, false).next_frame().unwrap().1
+		};
+
+		assert_eq!(delay(2.0), delay(1.0) / 2);
+		// Anything outside of the range is brought back into it
+		assert_eq!(delay(1000.0), delay(10.0));
+		assert_eq!(delay(0.0), delay(0.1));
+	}
+
+	#[test]
+	fn speeds_and_densities_that_arent_numbers_are_the_defaults()
+	{
+		let delay = |speed| {
+			let options = EffectOptions { effect: Effect::Plasma, speed, ..Default::default() };
+			Effects::new(&options, false).next_frame().unwrap().1
+		};
+
+		assert_eq!(delay(f64::NAN), delay(1.0));
+		assert_eq!(delay(f64::INFINITY), delay(1.0));
+		assert_eq!(delay(f64::NEG_INFINITY), delay(1.0));
+
+		for density in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY]
+		{
+			assert_eq!(frames(Effect::Starfield, false, 5, density, 20), frames(Effect::Starfield, false, 5, 0.5, 20));
+		}
+	}
+
+	#[test]
+	fn rng_stays_in_range()
+	{
+		let mut rng = Rng::new(0);
+		for _ in 0..1000
+		{
+			assert!(rng.below(9) < 9);
+			assert!((0.0..1.0).contains(&rng.float()));
+		}
+
+		assert_eq!(rng.below(0), 0);
+		assert!(!rng.chance(0.0));
+		assert!(rng.chance(1.0));
+	}
+}
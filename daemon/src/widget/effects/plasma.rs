@@ -0,0 +1,37 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+use std::f64::consts::FRAC_PI_2;
+
+#[derive(Default)]
+pub struct Plasma
+{
+	time: f64,
+}
+
+impl Step for Plasma
+{
+	fn step(&mut self, canvas: &mut Canvas, _: &mut Rng, density: f64)
+	{
+		self.time += 0.08;
+		let t = self.time;
+
+		// Denser plasma has tighter waves, so more of them fit on the matrix
+		let scale = 3.0 + (1.0 - density) * 6.0;
+		let (cx, cy) = (canvas.width() as f64 / 2.0, HEIGHT as f64 / 2.0);
+
+		for x in 0..canvas.width()
+		{
+			for y in 0..HEIGHT
+			{
+				let (fx, fy) = (x as f64, y as f64);
+				let value = (fx / scale + t).sin()
+				            + (fy / scale * 0.8 - t * 1.3).sin()
+				            + ((fx + fy) / scale * 0.7 + t * 0.6).sin()
+				            + (((fx - cx).powi(2) + (fy - cy).powi(2)).sqrt() / scale - t * 1.1).sin();
+
+				// Going around a sine again turns the sum into bands, which stand out more than a smooth gradient
+				canvas.set(x, y, (((value * FRAC_PI_2).sin() + 1.0) / 2.0 * 255.0) as u8);
+			}
+		}
+	}
+}
@@ -0,0 +1,55 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+
+/// A streak falling down one of the columns, with its head at the bottom
+struct Drop
+{
+	x: usize,
+	y: f64,
+	speed: f64,
+	length: usize,
+}
+
+#[derive(Default)]
+pub struct Rain
+{
+	drops: Vec<Drop>,
+}
+
+impl Step for Rain
+{
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64)
+	{
+		// Denser rain starts more streaks, but a column waits until the top is clear before starting another one
+		for x in 0..canvas.width()
+		{
+			let clear = self.drops.iter().all(|d| d.x != x || d.y >= d.length as f64);
+			if clear && rng.chance(0.01 + density * 0.1)
+			{
+				self.drops.push(Drop { x, y: 0.0, speed: 0.3 + rng.float() * 0.7, length: 4 + rng.below(10) });
+			}
+		}
+
+		for drop in &mut self.drops
+		{
+			drop.y += drop.speed;
+			let head = drop.y as usize;
+
+			// The trail fades out behind the head
+			for i in 0..drop.length
+			{
+				match head.checked_sub(i)
+				{
+					Some(y) if y < HEIGHT =>
+					{
+						let brightness = (255 - i * 255 / drop.length) as u8;
+						canvas.set(drop.x, y, canvas.get(drop.x, y).max(brightness));
+					},
+					_ => (),
+				}
+			}
+		}
+
+		self.drops.retain(|d| (d.y as usize).saturating_sub(d.length) < HEIGHT);
+	}
+}
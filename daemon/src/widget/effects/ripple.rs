@@ -0,0 +1,71 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+
+// How much of a ripple is left after each frame
+const DAMPING: f64 = 0.96;
+
+pub struct Ripple
+{
+	width: usize,
+	// Row-major height of the water in this frame and the one before it
+	current: Vec<f64>,
+	previous: Vec<f64>,
+}
+
+impl Ripple
+{
+	pub fn new(width: usize) -> Self
+	{
+		Ripple { width, current: vec![0.0; width * HEIGHT], previous: vec![0.0; width * HEIGHT] }
+	}
+}
+
+impl Step for Ripple
+{
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64)
+	{
+		let width = self.width;
+
+		// Drops are a small round bump, since a single point makes a checkerboard instead of rings
+		if rng.chance(0.02 + density * 0.15)
+		{
+			let (cx, cy) = (rng.below(width) as isize, rng.below(HEIGHT) as isize);
+			for y in (cy - 2).max(0)..(cy + 3).min(HEIGHT as isize)
+			{
+				for x in (cx - 2).max(0)..(cx + 3).min(width as isize)
+				{
+					let distance = (((x - cx).pow(2) + (y - cy).pow(2)) as f64).sqrt();
+					self.current[y as usize * width + x as usize] += (1.0 - distance / 3.0).max(0.0) * 3.0;
+				}
+			}
+		}
+
+		// Each point moves towards the average of its neighbours, and past the edges the water doesn't move at all
+		let at = |x: isize, y: isize| match x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < HEIGHT
+		{
+			true => self.current[y as usize * width + x as usize],
+			false => 0.0,
+		};
+
+		let mut next = vec![0.0; width * HEIGHT];
+		for y in 0..HEIGHT as isize
+		{
+			for x in 0..width as isize
+			{
+				let i = y as usize * width + x as usize;
+				let neighbours = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1);
+				next[i] = (neighbours / 2.0 - self.previous[i]) * DAMPING;
+			}
+		}
+
+		self.previous = std::mem::replace(&mut self.current, next);
+
+		for y in 0..HEIGHT
+		{
+			for x in 0..width
+			{
+				canvas.set(x, y, ((self.current[y * width + x].abs() - 0.05).clamp(0.0, 1.0) * 255.0) as u8);
+			}
+		}
+	}
+}
@@ -0,0 +1,58 @@
+use crate::widget::effects::{Rng, Step, HEIGHT};
+use shared::canvas::Canvas;
+
+/// A star somewhere in front of the matrix, from -1 to 1 across and 0 to 1 away
+struct Star
+{
+	x: f64,
+	y: f64,
+	z: f64,
+}
+
+#[derive(Default)]
+pub struct Starfield
+{
+	stars: Vec<Star>,
+}
+
+impl Star
+{
+	fn new(rng: &mut Rng, z: f64) -> Self
+	{
+		Star { x: rng.float() * 2.0 - 1.0, y: rng.float() * 2.0 - 1.0, z }
+	}
+}
+
+impl Step for Starfield
+{
+	fn step(&mut self, canvas: &mut Canvas, rng: &mut Rng, density: f64)
+	{
+		let width = canvas.width();
+		let (cx, cy) = (width as f64 / 2.0, HEIGHT as f64 / 2.0);
+
+		// The first stars are spread out all the way back, so it doesn't start with a wall of them
+		let count = ((4.0 + density * 40.0) * width as f64 / 9.0) as usize;
+		while self.stars.len() < count
+		{
+			let z = rng.float();
+			self.stars.push(Star::new(rng, z));
+		}
+		self.stars.truncate(count);
+
+		for star in &mut self.stars
+		{
+			star.z -= 0.02;
+
+			let (x, y) = (cx + star.x / star.z * cx, cy + star.y / star.z * cy);
+			if star.z <= 0.02 || x < 0.0 || y < 0.0 || x >= width as f64 || y >= HEIGHT as f64
+			{
+				*star = Star::new(rng, 1.0);
+				continue;
+			}
+
+			// Closer stars are brighter
+			let brightness = ((1.0 - star.z) * 255.0) as u8;
+			canvas.set(x as usize, y as usize, canvas.get(x as usize, y as usize).max(brightness));
+		}
+	}
+}
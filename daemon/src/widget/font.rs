@@ -0,0 +1,56 @@
+use shared::canvas::Canvas;
+
+const HEIGHT: usize = 34;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 7;
+
+// Narrow font so that two digits fit side by side on a single matrix, one byte per row with the left column in bit 3
+pub const DIGITS: [[u8; GLYPH_HEIGHT]; 10] = [
+	[0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110], // '0'
+	[0b0010, 0b0110, 0b0010, 0b0010, 0b0010, 0b0010, 0b0111], // '1'
+	[0b0110, 0b1001, 0b0001, 0b0010, 0b0100, 0b1000, 0b1111], // '2'
+	[0b0110, 0b1001, 0b0001, 0b0110, 0b0001, 0b1001, 0b0110], // '3'
+	[0b0010, 0b0110, 0b1010, 0b1010, 0b1111, 0b0010, 0b0010], // '4'
+	[0b1111, 0b1000, 0b1110, 0b0001, 0b0001, 0b1001, 0b0110], // '5'
+	[0b0110, 0b1000, 0b1000, 0b1110, 0b1001, 0b1001, 0b0110], // '6'
+	[0b1111, 0b0001, 0b0010, 0b0010, 0b0100, 0b0100, 0b0100], // '7'
+	[0b0110, 0b1001, 0b1001, 0b0110, 0b1001, 0b1001, 0b0110], // '8'
+	[0b0110, 0b1001, 0b1001, 0b0111, 0b0001, 0b0001, 0b0110], // '9'
+];
+pub const AM: [u8; GLYPH_HEIGHT] = [0b0110, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001];
+pub const PM: [u8; GLYPH_HEIGHT] = [0b1110, 0b1001, 0b1001, 0b1110, 0b1000, 0b1000, 0b1000];
+
+/// Draw a glyph with its top left corner at the given position, scaled up by the given factor
+pub fn draw_glyph(canvas: &mut Canvas, glyph: &[u8; GLYPH_HEIGHT], left: usize, top: usize, scale: usize)
+{
+	for (y, row) in glyph.iter().enumerate()
+	{
+		for x in 0..GLYPH_WIDTH
+		{
+			if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0
+			{
+				for dx in 0..scale
+				{
+					for dy in 0..scale
+					{
+						canvas.set(left + x * scale + dx, top + y * scale + dy, 0xFF);
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Draw a number one digit above the other, centered vertically
+pub fn draw_number(canvas: &mut Canvas, left: usize, number: u64)
+{
+	let digits: Vec<usize> = number.to_string().bytes().map(|b| (b - b'0') as usize).collect();
+	let pitch = GLYPH_HEIGHT + 2;
+	let top = (HEIGHT + 2).saturating_sub(digits.len() * pitch) / 2;
+
+	for (i, digit) in digits.iter().enumerate()
+	{
+		draw_glyph(canvas, &DIGITS[*digit], left, top + i * pitch, 1);
+	}
+}
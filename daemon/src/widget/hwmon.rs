@@ -0,0 +1,340 @@
+use crate::{
+	config::{Sensor, SensorStyle},
+	widget::{font::*, vertical_bar, Generator},
+};
+use shared::{
+	canvas::Canvas,
+	hwmon::{hwmon_inputs, HwmonInput, HWMON},
+	widget::HwmonOptions,
+};
+use std::{error::Error, path::Path, time::Duration};
+
+const HEIGHT: usize = 34;
+
+// Numbers are stacked one digit above the other, so this many digits fit on a matrix
+const MAX_DIGITS: usize = 4;
+
+pub struct Hwmon
+{
+	sensors: Vec<(Sensor, HwmonInput)>,
+	pair: bool,
+	tick: u64,
+}
+
+impl Hwmon
+{
+	/// Look up the chosen sensors from the config, so that anything missing is caught before the widget starts
+	pub fn new(options: &HwmonOptions, configured: &[Sensor], pair: bool) -> Result<Self, Box<dyn Error>>
+	{
+		let mut chosen: Vec<&Sensor> = vec![];
+		for name in &options.sensors
+		{
+			chosen.push(configured.iter()
+			                      .find(|s| s.name == *name)
+			                      .ok_or(format!("there is no sensor named {} in the config", name))?);
+		}
+
+		if options.sensors.is_empty()
+		{
+			chosen = configured.iter().collect();
+		}
+
+		if chosen.is_empty()
+		{
+			return Err("there are no sensors in the config".into());
+		}
+
+		let inputs = hwmon_inputs(Path::new(options.sysfs.as_deref().unwrap_or(HWMON)))?;
+		let mut sensors = vec![];
+
+		for sensor in chosen
+		{
+			let input = inputs.iter()
+			                  .find(|i| matches(sensor, i))
+			                  .ok_or(format!("sensor {} doesn't match any input on the {} chip", sensor.name, sensor.chip))?;
+
+			sensors.push((sensor.clone(), input.clone()));
+		}
+
+		// Every sensor gets an equal share of the columns, and numbers need enough of them for a digit
+		let width = Canvas::new(pair).width() / sensors.len();
+		let needed = match sensors.iter().any(|(s, _)| s.style == Some(SensorStyle::Number))
+		{
+			true => GLYPH_WIDTH,
+			false => 1,
+		};
+
+		if width < needed
+		{
+			return Err(format!("there isn't enough room for {} sensors", sensors.len()).into());
+		}
+
+		Ok(Hwmon { sensors, pair, tick: 0 })
+	}
+}
+
+impl Generator for Hwmon
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let mut canvas = Canvas::new(self.pair);
+		let width = canvas.width() / self.sensors.len();
+		let mut danger = false;
+
+		for (i, (sensor, input)) in self.sensors.iter().enumerate()
+		{
+			let value = input.read()?;
+			let left = i * width;
+
+			match sensor.style.unwrap_or(SensorStyle::Bar)
+			{
+				// Leave a gap between the bars so that they don't run together
+				SensorStyle::Bar =>
+				{
+					let min = sensor.min.unwrap_or(0.0);
+					// A bar with no range is either empty or full, rather than dividing by zero
+					let fraction = match sensor.max - min
+					{
+						range if range > 0.0 => (value - min) / range,
+						_ if value >= sensor.max => 1.0,
+						_ => 0.0,
+					};
+					vertical_bar(&mut canvas, left, width.max(2) - 1, 0, HEIGHT, fraction);
+				},
+				SensorStyle::Number =>
+				{
+					let number = (value.max(0.0).round() as u64).min(10u64.pow(MAX_DIGITS as u32) - 1);
+					draw_number(&mut canvas, left + (width - GLYPH_WIDTH) / 2, number);
+				},
+			}
+
+			// Blinking is done by inverting the sensor every other frame
+			if sensor.danger.is_some_and(|danger| value >= danger)
+			{
+				danger = true;
+				if self.tick % 2 == 1
+				{
+					for x in left..left + width
+					{
+						for y in 0..HEIGHT
+						{
+							canvas.set(x, y, 0xFF - canvas.get(x, y));
+						}
+					}
+				}
+			}
+		}
+
+		self.tick += 1;
+		let delay = match danger
+		{
+			true => Duration::from_millis(500),
+			false => Duration::from_secs(1),
+		};
+
+		Ok((canvas.pixels().to_vec(), delay))
+	}
+}
+
+/// Whether an input is the one a sensor from the config refers to, by its file or by its label
+fn matches(sensor: &Sensor, input: &HwmonInput) -> bool
+{
+	input.chip == sensor.chip
+	&& match &sensor.input
+	{
+		Some(file) => input.input == *file,
+		None => input.label == sensor.label,
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		env::temp_dir,
+		fs::{create_dir_all, remove_dir_all, write},
+		path::PathBuf,
+		process,
+	};
+
+	/// A hwmon tree in a temporary directory, removed once the test is done with it
+	struct Sysfs(PathBuf);
+
+	impl Sysfs
+	{
+		fn new(name: &str) -> Self
+		{
+			let root = temp_dir().join(format!("fw16-led-matrixd-{}-{}", name, process::id()));
+			let _ = remove_dir_all(&root);
+			create_dir_all(&root).unwrap();
+			Sysfs(root)
+		}
+
+		fn chip(&self, dir: &str, name: &str, attributes: &[(&str, &str)]) -> &Self
+		{
+			let path = self.0.join(dir);
+			create_dir_all(&path).unwrap();
+			write(path.join("name"), format!("{}\n", name)).unwrap();
+
+			for (attribute, value) in attributes
+			{
+				write(path.join(attribute), format!("{}\n", value)).unwrap();
+			}
+
+			self
+		}
+
+		fn hwmon(&self, sensors: &[Sensor]) -> Result<Hwmon, Box<dyn Error>>
+		{
+			let options = HwmonOptions { sensors: vec![], sysfs: Some(self.0.to_string_lossy().to_string()) };
+			Hwmon::new(&options, sensors, false)
+		}
+	}
+
+	impl Drop for Sysfs
+	{
+		fn drop(&mut self)
+		{
+			let _ = remove_dir_all(&self.0);
+		}
+	}
+
+	fn sensor(input: Option<&str>, label: Option<&str>) -> Sensor
+	{
+		Sensor { name: "cpu".to_string(),
+		         chip: "k10temp".to_string(),
+		         input: input.map(str::to_string),
+		         label: label.map(str::to_string),
+		         min: None,
+		         max: 100.0,
+		         danger: None,
+		         style: None }
+	}
+
+	fn input(chip: &str, file: &str, label: Option<&str>) -> HwmonInput
+	{
+		HwmonInput { chip: chip.to_string(),
+		             input: file.to_string(),
+		             label: label.map(str::to_string),
+		             path: PathBuf::new() }
+	}
+
+	#[test]
+	fn sensors_match_by_file_or_label()
+	{
+		let tctl = input("k10temp", "temp1_input", Some("Tctl"));
+
+		assert!(matches(&sensor(Some("temp1_input"), None), &tctl));
+		assert!(!matches(&sensor(Some("temp2_input"), None), &tctl));
+		assert!(matches(&sensor(None, Some("Tctl")), &tctl));
+		assert!(!matches(&sensor(None, Some("Tccd1")), &tctl));
+		// The file wins over the label when both are given
+		assert!(matches(&sensor(Some("temp1_input"), Some("Tccd1")), &tctl));
+		// Without a file or a label, only an input without a label is chosen
+		assert!(!matches(&sensor(None, None), &tctl));
+		assert!(matches(&sensor(None, None), &input("k10temp", "temp1_input", None)));
+
+		assert!(!matches(&sensor(Some("temp1_input"), None), &input("amdgpu", "temp1_input", Some("Tctl"))));
+	}
+
+	#[test]
+	fn missing_sensors_are_errors()
+	{
+		let sysfs = Sysfs::new("hwmon-missing");
+		sysfs.chip("hwmon0", "k10temp", &[("temp1_input", "50000"), ("temp1_label", "Tctl")]);
+
+		assert!(sysfs.hwmon(&[]).is_err());
+		assert!(sysfs.hwmon(&[sensor(None, Some("Tccd1"))]).is_err());
+
+		let options =
+			HwmonOptions { sensors: vec!["gpu".to_string()], sysfs: Some(sysfs.0.to_string_lossy().to_string()) };
+		assert!(Hwmon::new(&options, &[sensor(None, Some("Tctl"))], false).is_err());
+	}
+
+	#[test]
+	fn bars_fill_from_the_bottom()
+	{
+		let sysfs = Sysfs::new("hwmon-bar");
+		sysfs.chip("hwmon0", "acpitz", &[("temp1_input", "20000")]).chip(
+		                                                                 "hwmon1",
+		                                                                 "k10temp",
+		                                                                 &[
+			("temp1_input", "50000"),
+			("temp1_label", "Tctl"),
+		],
+		);
+
+		let (frame, delay) = sysfs.hwmon(&[sensor(None, Some("Tctl"))]).unwrap().next_frame().unwrap();
+		assert_eq!(delay, Duration::from_secs(1));
+
+		// Half of the rows are lit, the rest are dim, and the last column is left as a gap
+		assert_eq!(frame[HEIGHT - 1], 0xFF);
+		assert_eq!(frame[HEIGHT / 2], 0xFF);
+		assert!(frame[HEIGHT / 2 - 1] < 0xFF);
+		assert!(frame[0] > 0 && frame[0] < 0xFF);
+		assert!(frame[8 * HEIGHT..].iter().all(|p| *p == 0));
+	}
+
+	#[test]
+	fn bars_without_a_range_are_empty_or_full()
+	{
+		let sysfs = Sysfs::new("hwmon-range");
+		sysfs.chip("hwmon0", "k10temp", &[("temp1_input", "50000"), ("temp2_input", "40000")]);
+
+		let mut full = sensor(Some("temp1_input"), None);
+		full.min = Some(50.0);
+		full.max = 50.0;
+		let mut empty = sensor(Some("temp2_input"), None);
+		empty.min = Some(50.0);
+		empty.max = 50.0;
+
+		let (frame, _) = sysfs.hwmon(&[full]).unwrap().next_frame().unwrap();
+		assert_eq!(frame[0], 0xFF);
+
+		let (frame, _) = sysfs.hwmon(&[empty]).unwrap().next_frame().unwrap();
+		assert!(frame[HEIGHT - 1] < 0xFF);
+	}
+
+	#[test]
+	fn numbers_are_drawn_in_the_middle()
+	{
+		let sysfs = Sysfs::new("hwmon-number");
+		sysfs.chip("hwmon0", "k10temp", &[("temp1_input", "42400")]);
+
+		let mut number = sensor(Some("temp1_input"), None);
+		number.style = Some(SensorStyle::Number);
+
+		let (frame, _) = sysfs.hwmon(&[number]).unwrap().next_frame().unwrap();
+
+		let mut expected = Canvas::new(false);
+		draw_number(&mut expected, (9 - GLYPH_WIDTH) / 2, 42);
+		assert_eq!(frame, expected.pixels());
+	}
+
+	#[test]
+	fn danger_blinks()
+	{
+		let sysfs = Sysfs::new("hwmon-danger");
+		sysfs.chip("hwmon0", "k10temp", &[("temp1_input", "90000"), ("temp2_input", "30000")]);
+
+		let mut hot = sensor(Some("temp1_input"), None);
+		hot.danger = Some(85.0);
+		let mut cool = sensor(Some("temp2_input"), None);
+		cool.name = "ssd".to_string();
+		cool.danger = Some(85.0);
+
+		let mut hwmon = sysfs.hwmon(&[hot, cool]).unwrap();
+		let (first, delay) = hwmon.next_frame().unwrap();
+		let (second, _) = hwmon.next_frame().unwrap();
+		assert_eq!(delay, Duration::from_millis(500));
+
+		// Only the sensor in danger is inverted on every other frame
+		let width = 9 / 2;
+		for i in 0..width * HEIGHT
+		{
+			assert_eq!(second[i], 0xFF - first[i]);
+		}
+		assert_eq!(first[width * HEIGHT..], second[width * HEIGHT..]);
+	}
+}
@@ -0,0 +1,92 @@
+mod battery;
+mod clock;
+mod effects;
+mod font;
+mod hwmon;
+mod network;
+#[cfg(feature = "dbus")]
+mod now_playing;
+mod sysmon;
+mod timer;
+
+use crate::config::Sensor;
+use shared::{canvas::Canvas, ipc::Target, widget::Widget};
+use std::{
+	error::Error,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+pub use timer::TimerControl;
+
+// The empty part of a bar is dimly lit, so it's clear how far the bar could go
+const BAR_OFF: f64 = 16.0;
+
+/// Draws the frames of a widget one at a time, as they're needed
+pub trait Generator: Send
+{
+	/// Draw the next frame in column-major order, along with how long to wait before drawing another one
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>;
+
+	/// Whether there's nothing left to show, so whatever was displayed before the widget should come back
+	fn finished(&self) -> bool
+	{
+		false
+	}
+}
+
+/// Set up a widget to draw on the left or right matrix, or across both of them for a pair
+pub fn generator(widget: &Widget,
+                 target: Target,
+                 sensors: &[Sensor],
+                 timer: &Arc<Mutex<TimerControl>>)
+                 -> Result<Box<dyn Generator>, Box<dyn Error>>
+{
+	Ok(match widget
+	{
+		Widget::Clock(options) => Box::new(clock::Clock::new(options.clone(), target == Target::Pair)),
+		Widget::Sysmon(options) => Box::new(sysmon::Sysmon::new(options.clone(), target)),
+		Widget::Battery(options) => Box::new(battery::Battery::new(options.clone(), target == Target::Pair)),
+		Widget::Network(options) => Box::new(network::Network::new(options.clone(), target)),
+		Widget::Hwmon(options) => Box::new(hwmon::Hwmon::new(options, sensors, target == Target::Pair)?),
+		#[cfg(feature = "dbus")]
+		Widget::NowPlaying(options) => Box::new(now_playing::NowPlaying::new(options.clone(), target == Target::Pair)),
+		Widget::Timer(options) => Box::new(timer::Timer::new(options.clone(), target == Target::Pair, timer)),
+		Widget::Effect(options) => Box::new(effects::Effects::new(options, target == Target::Pair)),
+		#[cfg(not(feature = "dbus"))]
+		Widget::NowPlaying(_) =>
+		{
+			return Err("the now playing widget needs the daemon to be built with the dbus feature".into())
+		},
+	})
+}
+
+/// Fill a bar from the bottom up, the last row fades in so that small changes are still visible
+fn vertical_bar(canvas: &mut Canvas, left: usize, width: usize, top: usize, height: usize, fraction: f64)
+{
+	let lit = fraction.clamp(0.0, 1.0) * height as f64;
+
+	for row in 0..height
+	{
+		let brightness = BAR_OFF + (lit - row as f64).clamp(0.0, 1.0) * (255.0 - BAR_OFF);
+		for x in left..left + width
+		{
+			canvas.set(x, top + height - 1 - row, brightness as u8);
+		}
+	}
+}
+
+/// Fill a bar from left to right, the last column fades in so that small changes are still visible
+fn horizontal_bar(canvas: &mut Canvas, left: usize, width: usize, top: usize, height: usize, fraction: f64)
+{
+	let lit = fraction.clamp(0.0, 1.0) * width as f64;
+
+	for column in 0..width
+	{
+		let brightness = BAR_OFF + (lit - column as f64).clamp(0.0, 1.0) * (255.0 - BAR_OFF);
+		for y in top..top + height
+		{
+			canvas.set(left + column, y, brightness as u8);
+		}
+	}
+}
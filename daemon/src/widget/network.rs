@@ -0,0 +1,190 @@
+use crate::widget::{horizontal_bar, Generator, BAR_OFF};
+use shared::{canvas::Canvas, ipc::Target, widget::NetworkOptions};
+use std::{
+	collections::VecDeque,
+	error::Error,
+	fs::read_to_string,
+	time::{Duration, Instant},
+};
+
+const WIDTH: usize = 9;
+const HEIGHT: usize = 34;
+
+// Automatic scaling never goes below this many bytes per second, so background chatter doesn't fill the rows
+const MINIMUM_SCALE: f64 = 1024.0;
+
+pub struct Network
+{
+	options: NetworkOptions,
+	target: Target,
+	previous: Option<(Instant, u64, u64)>,
+	// Received and transmitted bytes per second, the newest sample is at the back
+	history: VecDeque<(f64, f64)>,
+}
+
+impl Network
+{
+	pub fn new(options: NetworkOptions, target: Target) -> Self
+	{
+		Network { options, target, previous: None, history: VecDeque::with_capacity(HEIGHT) }
+	}
+}
+
+impl Generator for Network
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let now = Instant::now();
+		let (received, transmitted) =
+			parse_net_dev(&read_to_string("/proc/net/dev")?, self.options.interface.as_deref())?;
+
+		if let Some((then, previous_received, previous_transmitted)) = self.previous
+		{
+			let elapsed = now.duration_since(then).as_secs_f64().max(0.001);
+			if self.history.len() == HEIGHT
+			{
+				self.history.pop_front();
+			}
+
+			self.history.push_back((
+				received.saturating_sub(previous_received) as f64 / elapsed,
+				transmitted.saturating_sub(previous_transmitted) as f64 / elapsed,
+			));
+		}
+		self.previous = Some((now, received, transmitted));
+
+		// Received shows on the left and transmitted on the right, so only what's on screen decides the scale
+		let target = self.target;
+		let visible = |(rx, tx): &(f64, f64)| match target
+		{
+			Target::Left => *rx,
+			Target::Right => *tx,
+			_ => rx.max(*tx),
+		};
+
+		let scale = match self.options.scale
+		{
+			Some(scale) => scale.max(1) as f64,
+			None => self.history.iter().map(visible).fold(MINIMUM_SCALE, f64::max),
+		};
+
+		let mut canvas = Canvas::new(self.target == Target::Pair);
+		let top = HEIGHT - self.history.len();
+
+		for (row, (rx, tx)) in self.history.iter().enumerate()
+		{
+			match self.target
+			{
+				Target::Left => horizontal_bar(&mut canvas, 0, WIDTH, top + row, 1, rx / scale),
+				Target::Right => horizontal_bar(&mut canvas, 0, WIDTH, top + row, 1, tx / scale),
+				_ =>
+				{
+					// Both halves grow outwards from the middle, so the pair reads like a mirrored graph
+					mirrored_bar(&mut canvas, top + row, rx / scale);
+					horizontal_bar(&mut canvas, WIDTH, WIDTH, top + row, 1, tx / scale);
+				},
+			}
+		}
+
+		Ok((canvas.pixels().to_vec(), Duration::from_millis(self.options.interval.max(100))))
+	}
+}
+
+/// Total bytes received and transmitted on the given interface, or every interface besides loopback, from the contents
+/// of /proc/net/dev
+fn parse_net_dev(dev: &str, interface: Option<&str>) -> Result<(u64, u64), Box<dyn Error>>
+{
+	let (mut received, mut transmitted, mut found) = (0, 0, false);
+
+	// The first two lines are headers
+	for line in dev.lines().skip(2)
+	{
+		let (name, counters) = line.split_once(':').ok_or(format!("unexpected line in /proc/net/dev: {}", line))?;
+		let name = name.trim();
+
+		let wanted = match interface
+		{
+			Some(interface) => name == interface,
+			None => name != "lo",
+		};
+
+		if wanted
+		{
+			let counters: Vec<&str> = counters.split_whitespace().collect();
+			if counters.len() < 9
+			{
+				return Err(format!("unexpected line in /proc/net/dev: {}", line).into());
+			}
+
+			received += counters[0].parse::<u64>()?;
+			transmitted += counters[8].parse::<u64>()?;
+			found = true;
+		}
+	}
+
+	match (found, interface)
+	{
+		(true, _) => Ok((received, transmitted)),
+		(false, Some(interface)) => Err(format!("there is no network interface named {}", interface).into()),
+		(false, None) => Err("there are no network interfaces besides loopback".into()),
+	}
+}
+
+/// Fill the left matrix of a pair from right to left, matching the horizontal bars on the right matrix
+fn mirrored_bar(canvas: &mut Canvas, y: usize, fraction: f64)
+{
+	let lit = fraction.clamp(0.0, 1.0) * WIDTH as f64;
+
+	for column in 0..WIDTH
+	{
+		let brightness = BAR_OFF + (lit - column as f64).clamp(0.0, 1.0) * (255.0 - BAR_OFF);
+		canvas.set(WIDTH - 1 - column, y, brightness as u8);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 5000000   10000    0    0    0     0          0         0  5000000   10000    0    0    0     0       0          0
+enp1s0:  300000     400    0    0    0     0          0        12    20000     150    0    0    0     0       0          0
+ wlan0: 1200000     900    0    0    0     0          0         0    80000     600    0    0    0     0       0          0
+";
+
+	#[test]
+	fn every_interface_but_loopback_is_combined()
+	{
+		assert_eq!(parse_net_dev(DEV, None).unwrap(), (1_500_000, 100_000));
+	}
+
+	#[test]
+	fn one_interface_can_be_chosen()
+	{
+		assert_eq!(parse_net_dev(DEV, Some("wlan0")).unwrap(), (1_200_000, 80_000));
+		assert_eq!(parse_net_dev(DEV, Some("enp1s0")).unwrap(), (300_000, 20_000));
+		// Loopback is only left out when nothing is chosen
+		assert_eq!(parse_net_dev(DEV, Some("lo")).unwrap(), (5_000_000, 5_000_000));
+		assert!(parse_net_dev(DEV, Some("wlan1")).is_err());
+	}
+
+	#[test]
+	fn loopback_alone_is_an_error()
+	{
+		let dev: String = DEV.lines().take(3).map(|l| format!("{}\n", l)).collect();
+		assert!(parse_net_dev(&dev, None).is_err());
+		assert_eq!(parse_net_dev(&dev, Some("lo")).unwrap(), (5_000_000, 5_000_000));
+	}
+
+	#[test]
+	fn malformed_lines_are_errors()
+	{
+		let header: String = DEV.lines().take(2).map(|l| format!("{}\n", l)).collect();
+		assert!(parse_net_dev(&format!("{}eth0 300000 400\n", header), None).is_err());
+		assert!(parse_net_dev(&format!("{}eth0: 300000 400 0\n", header), None).is_err());
+		assert!(parse_net_dev(&format!("{}eth0: lots 400 0 0 0 0 0 0 20000\n", header), None).is_err());
+	}
+}
@@ -0,0 +1,529 @@
+use crate::widget::{vertical_bar, Generator};
+use shared::{canvas::Canvas, text::glyph, widget::NowPlayingOptions};
+use std::{
+	collections::HashMap,
+	error::Error,
+	time::{Duration, Instant},
+};
+use zbus::{
+	blocking::{connection::Builder, fdo::DBusProxy, Connection},
+	proxy,
+	zvariant::OwnedValue,
+};
+
+const HEIGHT: usize = 34;
+
+// Every MPRIS player owns a name starting with this, such as "org.mpris.MediaPlayer2.spotify"
+const PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+// Play and pause symbols, one row per entry with the left column in bit 4
+const PLAY: [u8; 7] = [0b10000, 0b11000, 0b11100, 0b11110, 0b11100, 0b11000, 0b10000];
+const PAUSE: [u8; 7] = [0b11011; 7];
+
+// Rows taken up by the marquee, when it's shown
+const MARQUEE_TOP: usize = 10;
+const MARQUEE_DELAY: Duration = Duration::from_millis(80);
+
+// The cached properties are kept up to date by PropertiesChanged, so looking at them often is cheap
+const DELAY: Duration = Duration::from_millis(250);
+
+// Players don't announce their position as it changes, so it's asked for again after this long
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+// How often the other players are looked at, in case a different one starts playing
+const CHOOSE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[proxy(interface = "org.mpris.MediaPlayer2.Player", default_path = "/org/mpris/MediaPlayer2")]
+trait Player
+{
+	#[zbus(property)]
+	fn playback_status(&self) -> zbus::Result<String>;
+
+	#[zbus(property)]
+	fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+	#[zbus(property(emits_changed_signal = "false"))]
+	fn position(&self) -> zbus::Result<i64>;
+}
+
+/// What a player says about the track it's on
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Track
+{
+	artist: String,
+	title: String,
+	// In microseconds, 0 when the player doesn't know
+	length: i64,
+}
+
+/// Somewhere to find MPRIS players, which is a D-Bus bus outside of tests
+trait Mpris: Send
+{
+	/// Names of every player, such as "org.mpris.MediaPlayer2.spotify"
+	fn players(&mut self) -> Result<Vec<String>, Box<dyn Error>>;
+
+	fn status(&mut self, player: &str) -> Result<String, Box<dyn Error>>;
+
+	fn track(&mut self, player: &str) -> Result<Track, Box<dyn Error>>;
+
+	/// Position in microseconds, players that won't say have none
+	fn position(&mut self, player: &str) -> Option<i64>;
+}
+
+/// Players on the session bus, or the bus named in the options
+/// Nothing is connected to until the first frame, so that starting the widget doesn't wait on D-Bus
+struct Bus
+{
+	address: Option<String>,
+	connection: Option<(Connection, DBusProxy<'static>)>,
+	// Proxies are kept so that their properties stay cached between frames
+	players: HashMap<String, PlayerProxyBlocking<'static>>,
+}
+
+impl Bus
+{
+	fn connect(&mut self) -> Result<&(Connection, DBusProxy<'static>), Box<dyn Error>>
+	{
+		if self.connection.is_none()
+		{
+			let connection = match &self.address
+			{
+				Some(address) => Builder::address(address.as_str())?.build()?,
+				None => Connection::session()?,
+			};
+
+			let bus = DBusProxy::new(&connection)?;
+			self.connection = Some((connection, bus));
+		}
+
+		Ok(self.connection.as_ref().unwrap())
+	}
+
+	fn player(&mut self, name: &str) -> Result<&PlayerProxyBlocking<'static>, Box<dyn Error>>
+	{
+		if !self.players.contains_key(name)
+		{
+			let connection = self.connect()?.0.clone();
+			let player = PlayerProxyBlocking::builder(&connection).destination(name.to_string())?.build()?;
+			self.players.insert(name.to_string(), player);
+		}
+
+		Ok(&self.players[name])
+	}
+}
+
+impl Mpris for Bus
+{
+	fn players(&mut self) -> Result<Vec<String>, Box<dyn Error>>
+	{
+		let names: Vec<String> = self.connect()?
+		                             .1
+		                             .list_names()?
+		                             .into_iter()
+		                             .map(|n| n.to_string())
+		                             .filter(|n| n.starts_with(PREFIX))
+		                             .collect();
+
+		// Players that have quit are forgotten, in case another one takes their name
+		self.players.retain(|name, _| names.contains(name));
+		Ok(names)
+	}
+
+	fn status(&mut self, player: &str) -> Result<String, Box<dyn Error>>
+	{
+		Ok(self.player(player)?.playback_status()?)
+	}
+
+	fn track(&mut self, player: &str) -> Result<Track, Box<dyn Error>>
+	{
+		let metadata = self.player(player)?.metadata()?;
+		Ok(Track { artist: artist(&metadata),
+		           title: string(&metadata, "xesam:title"),
+		           length: metadata.get("mpris:length").and_then(|l| l.downcast_ref::<i64>().ok()).unwrap_or(0) })
+	}
+
+	fn position(&mut self, player: &str) -> Option<i64>
+	{
+		self.player(player).ok()?.position().ok()
+	}
+}
+
+pub struct NowPlaying
+{
+	mpris: Box<dyn Mpris>,
+	// Only players whose names start with this are followed
+	wanted: String,
+	missing: String,
+	marquee: bool,
+	pair: bool,
+	// The player being followed, and when the players were last looked at to see if another one should be
+	player: Option<String>,
+	chosen: Option<Instant>,
+	// What's playing, so that a new track starts the marquee over and gets its position straight away
+	track: String,
+	// Position in microseconds the last time it was asked for, and when that was
+	position: Option<(i64, Instant)>,
+	offset: usize,
+	finished: bool,
+}
+
+impl NowPlaying
+{
+	pub fn new(options: NowPlayingOptions, pair: bool) -> Self
+	{
+		let bus = Bus { address: options.address.clone(), connection: None, players: HashMap::new() };
+		Self::with(Box::new(bus), options, pair)
+	}
+
+	fn with(mpris: Box<dyn Mpris>, options: NowPlayingOptions, pair: bool) -> Self
+	{
+		let missing = match &options.player
+		{
+			Some(player) => format!("{} isn't playing anything", player),
+			None => "nothing is playing on any MPRIS player".to_string(),
+		};
+
+		NowPlaying { mpris,
+		             wanted: format!("{}{}", PREFIX, options.player.as_deref().unwrap_or_default()),
+		             missing,
+		             marquee: options.marquee,
+		             pair,
+		             player: None,
+		             chosen: None,
+		             track: String::new(),
+		             position: None,
+		             offset: 0,
+		             finished: false }
+	}
+
+	/// Pick the player to follow, which stays the same one while it's playing
+	/// Otherwise it's the first one that's playing, and a paused one is only followed when nothing else is playing
+	fn choose(&mut self) -> Result<Option<(String, String)>, Box<dyn Error>>
+	{
+		let mut names: Vec<String> =
+			self.mpris.players()?.into_iter().filter(|n| n.starts_with(&self.wanted)).collect();
+		names.sort();
+
+		// Players that quit while they're being asked are left out
+		let statuses: Vec<(String, String)> =
+			names.into_iter().filter_map(|n| self.mpris.status(&n).ok().map(|s| (n, s))).collect();
+
+		let current = statuses.iter().find(|(name, _)| self.player.as_ref() == Some(name));
+		let first = |wanted: &str| statuses.iter().find(|(_, status)| status == wanted);
+
+		let chosen = current.filter(|(_, status)| status == "Playing")
+		                    .or_else(|| first("Playing"))
+		                    .or(current.filter(|(_, status)| status == "Paused"))
+		                    .or_else(|| first("Paused"));
+		Ok(chosen.cloned())
+	}
+
+	/// Status of the player to follow, looking for another one every so often and whenever it stops or goes away
+	fn follow(&mut self) -> Result<Option<String>, Box<dyn Error>>
+	{
+		let due = self.chosen.is_none_or(|at| at.elapsed() >= CHOOSE_INTERVAL);
+		if let Some(player) = self.player.clone().filter(|_| !due)
+		{
+			if let Some(status) = self.mpris.status(&player).ok().filter(|s| s != "Stopped")
+			{
+				return Ok(Some(status));
+			}
+		}
+
+		let chosen = self.choose()?;
+		self.chosen = Some(Instant::now());
+
+		let Some((player, status)) = chosen
+		else
+		{
+			return Ok(None);
+		};
+
+		if self.player.as_ref() != Some(&player)
+		{
+			self.player = Some(player);
+			self.track = String::new();
+			self.offset = 0;
+			self.position = None;
+		}
+
+		Ok(Some(status))
+	}
+}
+
+impl Generator for NowPlaying
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let mut canvas = Canvas::new(self.pair);
+		let width = canvas.width();
+
+		let Some(status) = self.follow()?
+		else
+		{
+			// Nothing to follow in the first place is an error, but once something was it means playback stopped
+			if self.player.is_none()
+			{
+				return Err(self.missing.clone().into());
+			}
+
+			self.finished = true;
+			return Ok((canvas.pixels().to_vec(), Duration::ZERO));
+		};
+
+		let player = self.player.clone().unwrap_or_default();
+		// A player that quits just now is replaced on the next frame
+		let track = self.mpris.track(&player).unwrap_or_default();
+		let title = format!("{} - {}", track.artist, track.title);
+
+		if title != self.track
+		{
+			self.track = title;
+			self.offset = 0;
+			self.position = None;
+		}
+
+		// The position keeps moving between updates while the track is playing
+		if self.position.is_none_or(|(_, at)| at.elapsed() >= POSITION_INTERVAL)
+		{
+			self.position = self.mpris.position(&player).map(|position| (position, Instant::now()));
+		}
+
+		let symbol = match status.as_str()
+		{
+			"Playing" => PLAY,
+			_ => PAUSE,
+		};
+
+		let left = (width - 5) / 2;
+		for (y, row) in symbol.iter().enumerate()
+		{
+			for x in 0..5
+			{
+				if row & (1 << (4 - x)) != 0
+				{
+					canvas.set(left + x, 1 + y, 0xFF);
+				}
+			}
+		}
+
+		let position = match self.position
+		{
+			Some((position, at)) if status == "Playing" => position + at.elapsed().as_micros() as i64,
+			Some((position, _)) => position,
+			None => 0,
+		};
+
+		let fraction = match track.length
+		{
+			0 => 0.0,
+			length => position as f64 / length as f64,
+		};
+
+		let top = match self.marquee
+		{
+			true => MARQUEE_TOP + 9,
+			false => MARQUEE_TOP,
+		};
+		vertical_bar(&mut canvas, 0, width, top, HEIGHT - top, fraction);
+
+		if !self.marquee
+		{
+			return Ok((canvas.pixels().to_vec(), DELAY));
+		}
+
+		// The text comes in from the right, goes all the way off to the left, and then starts over
+		let columns: Vec<u8> = self.track.chars().flat_map(|c| glyph(c).into_iter().chain([0])).collect();
+		for x in 0..width
+		{
+			let Some(column) = (self.offset + x).checked_sub(width).and_then(|i| columns.get(i))
+			else
+			{
+				continue;
+			};
+
+			for y in 0..7
+			{
+				if column & (1 << y) != 0
+				{
+					canvas.set(x, MARQUEE_TOP + y, 0xFF);
+				}
+			}
+		}
+
+		self.offset = (self.offset + 1) % (columns.len() + width);
+		Ok((canvas.pixels().to_vec(), MARQUEE_DELAY))
+	}
+
+	fn finished(&self) -> bool
+	{
+		self.finished
+	}
+}
+
+fn string(metadata: &HashMap<String, OwnedValue>, key: &str) -> String
+{
+	metadata.get(key).and_then(|v| v.downcast_ref::<String>().ok()).unwrap_or_default()
+}
+
+/// Tracks can have more than one artist, which are all shown
+fn artist(metadata: &HashMap<String, OwnedValue>) -> String
+{
+	metadata.get("xesam:artist")
+	        .and_then(|v| v.try_clone().ok())
+	        .and_then(|v| Vec::<String>::try_from(v).ok())
+	        .map(|artists| artists.join(", "))
+	        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::sync::{Arc, Mutex};
+
+	/// Players with their status and track, which the test can change while the widget follows them
+	#[derive(Clone, Default)]
+	struct Mock(Arc<Mutex<HashMap<String, (String, Track)>>>);
+
+	impl Mock
+	{
+		fn set(&self, player: &str, status: &str, title: &str)
+		{
+			let track = Track { artist: "Artist".to_string(), title: title.to_string(), length: 1_000_000 };
+			self.0.lock().unwrap().insert(format!("{}{}", PREFIX, player), (status.to_string(), track));
+		}
+
+		fn remove(&self, player: &str)
+		{
+			self.0.lock().unwrap().remove(&format!("{}{}", PREFIX, player));
+		}
+	}
+
+	impl Mpris for Mock
+	{
+		fn players(&mut self) -> Result<Vec<String>, Box<dyn Error>>
+		{
+			Ok(self.0.lock().unwrap().keys().cloned().collect())
+		}
+
+		fn status(&mut self, player: &str) -> Result<String, Box<dyn Error>>
+		{
+			Ok(self.0.lock().unwrap().get(player).ok_or("no such player")?.0.clone())
+		}
+
+		fn track(&mut self, player: &str) -> Result<Track, Box<dyn Error>>
+		{
+			Ok(self.0.lock().unwrap().get(player).ok_or("no such player")?.1.clone())
+		}
+
+		fn position(&mut self, _: &str) -> Option<i64>
+		{
+			Some(500_000)
+		}
+	}
+
+	fn widget(mock: &Mock, player: Option<&str>) -> NowPlaying
+	{
+		let options = NowPlayingOptions { player: player.map(str::to_string), ..Default::default() };
+		NowPlaying::with(Box::new(mock.clone()), options, false)
+	}
+
+	/// Whether the play symbol is shown rather than pause, going by the top row of each
+	fn shows_play(frame: &[u8]) -> bool
+	{
+		// Frames are column-major, and the symbol starts two columns in on a single matrix
+		frame[2 * HEIGHT + 1] == 0xFF && frame[3 * HEIGHT + 1] == 0
+	}
+
+	/// Let the widget look at the other players on its next frame
+	fn look_again(widget: &mut NowPlaying)
+	{
+		widget.chosen = Some(Instant::now() - CHOOSE_INTERVAL);
+	}
+
+	#[test]
+	fn playing_players_are_followed_over_paused_ones()
+	{
+		let mock = Mock::default();
+		mock.set("a", "Paused", "One");
+		mock.set("b", "Playing", "Two");
+
+		let mut widget = widget(&mock, None);
+		let (frame, _) = widget.next_frame().unwrap();
+		assert!(shows_play(&frame));
+		assert_eq!(widget.track, "Artist - Two");
+
+		// Pausing doesn't switch to another paused player
+		mock.set("b", "Paused", "Two");
+		look_again(&mut widget);
+		let (frame, _) = widget.next_frame().unwrap();
+		assert!(!shows_play(&frame));
+		assert_eq!(widget.track, "Artist - Two");
+	}
+
+	#[test]
+	fn a_change_of_player_is_followed()
+	{
+		let mock = Mock::default();
+		mock.set("a", "Playing", "One");
+		mock.set("b", "Paused", "Two");
+
+		let mut widget = widget(&mock, None);
+		widget.next_frame().unwrap();
+		assert_eq!(widget.track, "Artist - One");
+
+		// Another player starting doesn't take over from one that's still playing
+		mock.set("b", "Playing", "Two");
+		look_again(&mut widget);
+		widget.next_frame().unwrap();
+		assert_eq!(widget.track, "Artist - One");
+
+		// But it does once the first one pauses
+		mock.set("a", "Paused", "One");
+		look_again(&mut widget);
+		widget.next_frame().unwrap();
+		assert_eq!(widget.track, "Artist - Two");
+
+		// A player that quits is replaced straight away, without waiting to look again
+		mock.remove("b");
+		widget.next_frame().unwrap();
+		assert_eq!(widget.track, "Artist - One");
+		assert!(!widget.finished());
+	}
+
+	#[test]
+	fn finishes_once_nothing_is_playing()
+	{
+		let mock = Mock::default();
+		mock.set("a", "Playing", "One");
+
+		let mut widget = widget(&mock, None);
+		widget.next_frame().unwrap();
+
+		mock.set("a", "Stopped", "One");
+		widget.next_frame().unwrap();
+		assert!(widget.finished());
+	}
+
+	#[test]
+	fn nothing_to_follow_is_an_error()
+	{
+		let mock = Mock::default();
+		mock.set("a", "Stopped", "One");
+		mock.set("b", "Playing", "Two");
+
+		// Only the named player is followed
+		assert!(widget(&mock, Some("a")).next_frame().is_err());
+		assert!(widget(&mock, Some("b")).next_frame().is_ok());
+	}
+
+	#[test]
+	fn starting_does_not_connect()
+	{
+		// The bus is only connected to on the first frame, which is drawn without the lock held
+		let options = NowPlayingOptions { address: Some("unix:path=/nonexistent".to_string()), ..Default::default() };
+		let mut widget = NowPlaying::new(options, false);
+		assert!(widget.next_frame().is_err());
+	}
+}
@@ -0,0 +1,335 @@
+use crate::widget::{horizontal_bar, vertical_bar, Generator};
+use shared::{
+	canvas::Canvas,
+	ipc::Target,
+	widget::{SysmonOptions, SysmonPanel},
+};
+use std::{collections::VecDeque, error::Error, fs::read_to_string, thread::available_parallelism, time::Duration};
+
+const WIDTH: usize = 9;
+const HEIGHT: usize = 34;
+
+// Idle and total time of a CPU, in the units /proc/stat counts in
+#[derive(Clone, Copy)]
+struct CpuTimes
+{
+	idle: u64,
+	total: u64,
+}
+
+pub struct Sysmon
+{
+	interval: Duration,
+	// Where each panel starts on the canvas, a pair has both of them side by side
+	panels: Vec<(usize, SysmonPanel)>,
+	pair: bool,
+	// The first entry is every core combined, the rest are each core in order
+	previous: Option<Vec<CpuTimes>>,
+	history: VecDeque<f64>,
+}
+
+impl Sysmon
+{
+	pub fn new(options: SysmonOptions, target: Target) -> Self
+	{
+		let panels = match target
+		{
+			Target::Right => vec![(0, options.right)],
+			Target::Pair => vec![(0, options.left), (WIDTH, options.right)],
+			_ => vec![(0, options.left)],
+		};
+
+		Sysmon { interval: Duration::from_millis(options.interval.max(100)),
+		         panels,
+		         pair: target == Target::Pair,
+		         previous: None,
+		         history: VecDeque::with_capacity(HEIGHT) }
+	}
+
+	/// Usage of every core combined followed by each core, since the last time this was called
+	fn cpu_usage(&mut self) -> Result<Vec<f64>, Box<dyn Error>>
+	{
+		let current = parse_stat(&read_to_string("/proc/stat")?)?;
+		let usage = match &self.previous
+		{
+			Some(previous) => current.iter()
+			                         .zip(previous)
+			                         .map(|(now, before)| {
+				                         let total = now.total.saturating_sub(before.total);
+				                         let idle = now.idle.saturating_sub(before.idle);
+				                         match total
+				                         {
+					                         0 => 0.0,
+				                             _ => 1.0 - idle as f64 / total as f64,
+				                         }
+			                         })
+			                         .collect(),
+			None => vec![0.0; current.len()],
+		};
+
+		self.previous = Some(current);
+		Ok(usage)
+	}
+}
+
+impl Generator for Sysmon
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let mut canvas = Canvas::new(self.pair);
+
+		// CPU usage is measured between samples, so it has to be sampled every time even if only one panel shows it
+		let needs_cpu =
+			self.panels.iter().any(|(_, panel)| matches!(panel, SysmonPanel::Cpu | SysmonPanel::CpuHistory));
+		let usage = match needs_cpu
+		{
+			true => self.cpu_usage()?,
+			false => vec![],
+		};
+
+		if let Some(total) = usage.first()
+		{
+			if self.history.len() == HEIGHT
+			{
+				self.history.pop_front();
+			}
+			self.history.push_back(*total);
+		}
+
+		for &(left, panel) in &self.panels
+		{
+			match panel
+			{
+				SysmonPanel::Cpu => draw_cores(&mut canvas, left, &usage[1..]),
+				SysmonPanel::CpuHistory =>
+				{
+					// The newest sample is at the bottom, so everything scrolls upwards
+					let top = HEIGHT - self.history.len();
+					for (row, usage) in self.history.iter().enumerate()
+					{
+						horizontal_bar(&mut canvas, left, WIDTH, top + row, 1, *usage);
+					}
+				},
+				SysmonPanel::Memory =>
+				{
+					let (memory, swap) = parse_meminfo(&read_to_string("/proc/meminfo")?)?;
+					vertical_bar(&mut canvas, left, 4, 0, HEIGHT, memory);
+					vertical_bar(&mut canvas, left + 5, 4, 0, HEIGHT, swap);
+				},
+				SysmonPanel::Load =>
+				{
+					let cores = available_parallelism().map_or(1, |c| c.get()) as f64;
+					for (i, load) in parse_loadavg(&read_to_string("/proc/loadavg")?)?.iter().enumerate()
+					{
+						vertical_bar(&mut canvas, left + i * 3, 2, 0, HEIGHT, load / cores);
+					}
+				},
+			}
+		}
+
+		Ok((canvas.pixels().to_vec(), self.interval))
+	}
+}
+
+/// A bar for each core from top to bottom, cores are averaged together if there's more of them than rows
+fn draw_cores(canvas: &mut Canvas, left: usize, usage: &[f64])
+{
+	if usage.is_empty()
+	{
+		return;
+	}
+
+	let groups: Vec<f64> = usage.chunks(usage.len().div_ceil(HEIGHT))
+	                            .map(|cores| cores.iter().sum::<f64>() / cores.len() as f64)
+	                            .collect();
+
+	// Leave a gap between the bars when there's room for one
+	let pitch = HEIGHT / groups.len();
+	let height = if pitch > 1 { pitch - 1 } else { 1 };
+	let top = (HEIGHT - pitch * groups.len()) / 2;
+
+	for (i, usage) in groups.iter().enumerate()
+	{
+		horizontal_bar(canvas, left, WIDTH, top + i * pitch, height, *usage);
+	}
+}
+
+/// Time spent by every core combined followed by each core, from the contents of /proc/stat
+fn parse_stat(stat: &str) -> Result<Vec<CpuTimes>, Box<dyn Error>>
+{
+	let mut times = vec![];
+
+	for line in stat.lines().filter(|l| l.starts_with("cpu"))
+	{
+		// user, nice, system, idle, iowait, irq, softirq and steal, guest time is already part of user
+		let fields = line.split_whitespace().skip(1).take(8).map(|f| f.parse::<u64>()).collect::<Result<Vec<_>, _>>()?;
+
+		if fields.len() < 5
+		{
+			return Err(format!("unexpected line in /proc/stat: {}", line).into());
+		}
+
+		times.push(CpuTimes { idle: fields[3] + fields[4], total: fields.iter().sum() });
+	}
+
+	match times.is_empty()
+	{
+		true => Err("no CPUs found in /proc/stat".into()),
+		false => Ok(times),
+	}
+}
+
+/// How full memory and swap are, from the contents of /proc/meminfo
+fn parse_meminfo(meminfo: &str) -> Result<(f64, f64), Box<dyn Error>>
+{
+	let field = |name: &str| -> Result<f64, Box<dyn Error>> {
+		let line = meminfo.lines()
+		                  .find(|l| l.split(':').next() == Some(name))
+		                  .ok_or(format!("{} is missing from /proc/meminfo", name))?;
+		let value = line.split_whitespace().nth(1).ok_or(format!("{} has no value in /proc/meminfo", name))?;
+		Ok(value.parse()?)
+	};
+
+	let used = |total: f64, free: f64| if total > 0.0 { (total - free) / total } else { 0.0 };
+
+	Ok((used(field("MemTotal")?, field("MemAvailable")?), used(field("SwapTotal")?, field("SwapFree")?)))
+}
+
+/// The 1, 5 and 15 minute load averages, from the contents of /proc/loadavg
+fn parse_loadavg(loadavg: &str) -> Result<Vec<f64>, Box<dyn Error>>
+{
+	let load = loadavg.split_whitespace().take(3).map(|l| l.parse::<f64>()).collect::<Result<Vec<_>, _>>()?;
+
+	match load.len()
+	{
+		3 => Ok(load),
+		_ => Err(format!("unexpected contents in /proc/loadavg: {}", loadavg.trim()).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::widget::BAR_OFF;
+
+	#[test]
+	fn cpu_times_are_parsed()
+	{
+		let stat = "cpu  100 5 50 800 20 3 2 0 10 0\n\
+		            cpu0 60 5 30 400 10 3 2 0 10 0\n\
+		            cpu1 40 0 20 400 10 0 0 0 0 0\n\
+		            intr 12345 0 0\n\
+		            ctxt 67890\n";
+
+		let times = parse_stat(stat).unwrap();
+		assert_eq!(times.len(), 3);
+		// Guest time isn't counted twice
+		assert_eq!((times[0].idle, times[0].total), (820, 980));
+		assert_eq!((times[1].idle, times[1].total), (410, 510));
+		assert_eq!((times[2].idle, times[2].total), (410, 470));
+
+		assert!(parse_stat("cpu  100 5 50\n").is_err());
+		assert!(parse_stat("cpu  100 5 fifty 800 20\n").is_err());
+		assert!(parse_stat("intr 12345 0 0\n").is_err());
+	}
+
+	#[test]
+	fn memory_is_parsed()
+	{
+		let meminfo = "MemTotal:       16000000 kB\n\
+		               MemFree:         2000000 kB\n\
+		               MemAvailable:    4000000 kB\n\
+		               SwapCached:            0 kB\n\
+		               SwapTotal:       8000000 kB\n\
+		               SwapFree:        6000000 kB\n";
+		assert_eq!(parse_meminfo(meminfo).unwrap(), (0.75, 0.25));
+
+		// Without any swap, it's never full
+		let meminfo = "MemTotal: 1000 kB\nMemAvailable: 1000 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n";
+		assert_eq!(parse_meminfo(meminfo).unwrap(), (0.0, 0.0));
+
+		assert!(parse_meminfo("MemTotal: 1000 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n").is_err());
+		assert!(parse_meminfo("MemTotal:\nMemAvailable: 1000 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n").is_err());
+	}
+
+	#[test]
+	fn load_is_parsed()
+	{
+		assert_eq!(parse_loadavg("0.52 1.20 2.05 2/1234 5678\n").unwrap(), [0.52, 1.2, 2.05]);
+		assert!(parse_loadavg("0.52 1.20\n").is_err());
+		assert!(parse_loadavg("").is_err());
+	}
+
+	fn pixel_rows(canvas: &Canvas) -> Vec<Vec<u8>>
+	{
+		(0..HEIGHT).map(|y| (0..WIDTH).map(|x| canvas.get(x, y)).collect()).collect()
+	}
+
+	#[test]
+	fn a_single_core_fills_the_matrix()
+	{
+		let mut canvas = Canvas::new(false);
+		draw_cores(&mut canvas, 0, &[1.0]);
+
+		let rows = pixel_rows(&canvas);
+		assert!(rows[..HEIGHT - 1].iter().all(|row| row.iter().all(|p| *p == 0xFF)));
+		assert!(rows[HEIGHT - 1].iter().all(|p| *p == 0));
+	}
+
+	#[test]
+	fn cores_get_a_bar_each()
+	{
+		let mut canvas = Canvas::new(false);
+		let usage = [1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0];
+		draw_cores(&mut canvas, 0, &usage);
+
+		// 8 bars of 3 rows with a gap after each, centred with one spare row above and below
+		let rows = pixel_rows(&canvas);
+		assert!(rows[0].iter().all(|p| *p == 0));
+		assert!(rows[1..4].iter().all(|row| row.iter().all(|p| *p == 0xFF)));
+		assert!(rows[4].iter().all(|p| *p == 0));
+		assert!(rows[5..8].iter().all(|row| row.iter().all(|p| *p == BAR_OFF as u8)));
+		assert_eq!(rows[9][0], 0xFF);
+		assert_eq!(rows[9][WIDTH - 1], BAR_OFF as u8);
+		assert!(rows[32..].iter().all(|row| row.iter().all(|p| *p == 0)));
+	}
+
+	#[test]
+	fn cores_beyond_the_rows_are_averaged()
+	{
+		// 40 cores are averaged in pairs, one row each with no room for gaps
+		let usage: Vec<f64> = (0..40).map(|i| {
+			                             if i % 2 == 0
+			                             {
+				                             1.0
+			                             }
+			                             else
+			                             {
+				                             0.0
+			                             }
+		                             })
+		                             .collect();
+		let mut canvas = Canvas::new(false);
+		draw_cores(&mut canvas, 0, &usage);
+
+		let rows = pixel_rows(&canvas);
+		assert!(rows[..7].iter().all(|row| row.iter().all(|p| *p == 0)));
+		for row in &rows[7..27]
+		{
+			assert_eq!(row[0], 0xFF);
+			assert!(row[4] > BAR_OFF as u8 && row[4] < 0xFF);
+			assert_eq!(row[WIDTH - 1], BAR_OFF as u8);
+		}
+		assert!(rows[27..].iter().all(|row| row.iter().all(|p| *p == 0)));
+
+		// With 8 cores to a row, every row is used
+		let mut canvas = Canvas::new(false);
+		draw_cores(&mut canvas, 0, &[1.0; 8 * HEIGHT]);
+		assert!(pixel_rows(&canvas).iter().all(|row| row.iter().all(|p| *p == 0xFF)));
+
+		let mut canvas = Canvas::new(false);
+		draw_cores(&mut canvas, 0, &[]);
+		assert!(canvas.pixels().iter().all(|p| *p == 0));
+	}
+}
@@ -0,0 +1,340 @@
+use crate::widget::{font::*, vertical_bar, Generator};
+use shared::{
+	canvas::Canvas,
+	widget::{TimerMode, TimerOptions, MAX_TIMER_CYCLES, MAX_TIMER_SECONDS},
+};
+use std::{
+	error::Error,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const WIDTH: usize = 9;
+const HEIGHT: usize = 34;
+
+// How long a finished timer flashes before going back to what was displayed before it
+const DONE_FLASH: Duration = Duration::from_secs(10);
+// Every pomodoro session after the first starts with a shorter flash
+const SESSION_FLASH: Duration = Duration::from_secs(3);
+const FLASH_DELAY: Duration = Duration::from_millis(250);
+const DELAY: Duration = Duration::from_millis(500);
+
+// Stacked digits only have room for this many
+const MAX_MINUTES: u64 = 999;
+
+/// How long the timer has been running, which is shared with the handler so that it can be paused, resumed, and cancelled
+#[derive(Default)]
+pub struct TimerControl
+{
+	options: Option<TimerOptions>,
+	elapsed: Duration,
+	// When the timer was last started or resumed, none while it's paused
+	resumed: Option<Instant>,
+	cancelled: bool,
+}
+
+impl TimerControl
+{
+	/// Start a new timer, the same timer being displayed again, such as after a notification, carries on where it was
+	pub fn start(&mut self, options: &TimerOptions)
+	{
+		if self.options.as_ref() == Some(options)
+		{
+			return;
+		}
+
+		// Timers that were started before the daemon restarted have been running all along
+		let elapsed = options.started
+		                     .and_then(|s| UNIX_EPOCH.checked_add(Duration::from_secs(s)))
+		                     .and_then(|started| SystemTime::now().duration_since(started).ok())
+		                     .unwrap_or_default();
+
+		*self =
+			TimerControl { options: Some(options.clone()), elapsed, resumed: Some(Instant::now()), cancelled: false };
+	}
+
+	pub fn pause(&mut self)
+	{
+		self.elapsed = self.elapsed();
+		self.resumed = None;
+	}
+
+	pub fn resume(&mut self)
+	{
+		self.resumed.get_or_insert_with(Instant::now);
+	}
+
+	pub fn cancel(&mut self)
+	{
+		self.cancelled = true;
+	}
+
+	pub fn paused(&self) -> bool
+	{
+		self.resumed.is_none()
+	}
+
+	fn elapsed(&self) -> Duration
+	{
+		self.elapsed + self.resumed.map(|r| r.elapsed()).unwrap_or_default()
+	}
+}
+
+/// Where a timer is, worked out from how long it's been running
+enum Phase
+{
+	/// Counting down, with how long is left out of how long the session is
+	Draining(Duration, Duration),
+	/// Resting between focus sessions, with how long has gone by out of how long the rest is
+	Filling(Duration, Duration),
+	/// Counting up from when the stopwatch started
+	Counting(Duration),
+	/// How long ago the timer ran out
+	Done(Duration),
+}
+
+pub struct Timer
+{
+	options: TimerOptions,
+	pair: bool,
+	control: Arc<Mutex<TimerControl>>,
+	tick: u64,
+	finished: bool,
+}
+
+impl Timer
+{
+	pub fn new(mut options: TimerOptions, pair: bool, control: &Arc<Mutex<TimerControl>>) -> Self
+	{
+		// Saved state and other sources skip validation, so the limits are kept here too
+		options.duration = options.duration.min(MAX_TIMER_SECONDS);
+		options.rest = options.rest.min(MAX_TIMER_SECONDS);
+		options.cycles = options.cycles.min(MAX_TIMER_CYCLES);
+
+		control.lock().unwrap().start(&options);
+		Timer { options, pair, control: Arc::clone(control), tick: 0, finished: false }
+	}
+
+	fn phase(&self, elapsed: Duration) -> Phase
+	{
+		let duration = Duration::from_secs(self.options.duration.max(1));
+		let rest = Duration::from_secs(self.options.rest);
+
+		match self.options.mode
+		{
+			TimerMode::Stopwatch => Phase::Counting(elapsed),
+			TimerMode::Countdown => match elapsed.checked_sub(duration)
+			{
+				Some(over) => Phase::Done(over),
+				None => Phase::Draining(duration - elapsed, duration),
+			},
+			// There's no rest after the last focus session, since the timer is done by then
+			TimerMode::Pomodoro =>
+			{
+				let cycles = self.options.cycles.max(1);
+				let mut start = Duration::ZERO;
+
+				for cycle in 1..=cycles
+				{
+					let end = start.saturating_add(duration);
+					if elapsed < end
+					{
+						return Phase::Draining(end - elapsed, duration);
+					}
+					start = end;
+
+					if cycle == cycles
+					{
+						break;
+					}
+
+					let end = start.saturating_add(rest);
+					if elapsed < end
+					{
+						return Phase::Filling(elapsed - start, rest);
+					}
+					start = end;
+				}
+
+				Phase::Done(elapsed - start)
+			},
+		}
+	}
+}
+
+impl Generator for Timer
+{
+	fn next_frame(&mut self) -> Result<(Vec<u8>, Duration), Box<dyn Error>>
+	{
+		let (elapsed, paused, cancelled) = {
+			let control = self.control.lock().unwrap();
+			(control.elapsed(), control.paused(), control.cancelled)
+		};
+
+		let mut canvas = Canvas::new(self.pair);
+		self.tick += 1;
+
+		if cancelled
+		{
+			self.finished = true;
+			return Ok((canvas.pixels().to_vec(), Duration::ZERO));
+		}
+
+		// Fraction of the bar and minutes to show, along with how long ago the current session started
+		let (fraction, minutes, since) = match self.phase(elapsed)
+		{
+			Phase::Done(over) =>
+			{
+				self.finished = over >= DONE_FLASH;
+				if flashing(over)
+				{
+					fill(&mut canvas);
+				}
+
+				return Ok((canvas.pixels().to_vec(), FLASH_DELAY));
+			},
+			Phase::Draining(left, total) =>
+			{
+				(left.as_secs_f64() / total.as_secs_f64(), left.as_secs().div_ceil(60), total - left)
+			},
+			Phase::Filling(gone, total) =>
+			{
+				(gone.as_secs_f64() / total.as_secs_f64(), (total - gone).as_secs().div_ceil(60), gone)
+			},
+			Phase::Counting(elapsed) => ((elapsed.as_secs() % 60) as f64 / 60.0, elapsed.as_secs() / 60, elapsed),
+		};
+
+		// The other half of a pair shows the minutes, and so does a stopwatch on its own next to a narrower bar
+		let (bar_left, bar_width, digits) = match (self.pair, self.options.mode)
+		{
+			(true, _) => (0, WIDTH, Some(WIDTH + 3)),
+			(false, TimerMode::Stopwatch) => (GLYPH_WIDTH + 1, WIDTH - GLYPH_WIDTH - 1, Some(0)),
+			(false, _) => (0, WIDTH, None),
+		};
+
+		// Paused timers blink, so it's clear they aren't moving
+		if !paused || self.tick.is_multiple_of(2)
+		{
+			vertical_bar(&mut canvas, bar_left, bar_width, 0, HEIGHT, fraction);
+		}
+
+		if let Some(left) = digits
+		{
+			draw_number(&mut canvas, left, minutes.min(MAX_MINUTES));
+		}
+
+		// Anything that started after the timer did is a new session
+		let session = self.options.mode == TimerMode::Pomodoro && since < SESSION_FLASH && elapsed > since;
+		if session && !paused
+		{
+			if flashing(since)
+			{
+				fill(&mut canvas);
+			}
+
+			return Ok((canvas.pixels().to_vec(), FLASH_DELAY));
+		}
+
+		Ok((canvas.pixels().to_vec(), DELAY))
+	}
+
+	fn finished(&self) -> bool
+	{
+		self.finished
+	}
+}
+
+fn flashing(since: Duration) -> bool
+{
+	(since.as_millis() / FLASH_DELAY.as_millis()).is_multiple_of(2)
+}
+
+fn fill(canvas: &mut Canvas)
+{
+	for x in 0..canvas.width()
+	{
+		for y in 0..HEIGHT
+		{
+			canvas.set(x, y, 0xFF);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn timer(mode: TimerMode, duration: u64, rest: u64, cycles: u32) -> Timer
+	{
+		let options = TimerOptions { mode, duration, rest, cycles, started: None };
+		Timer::new(options, false, &Arc::default())
+	}
+
+	fn secs(secs: u64) -> Duration
+	{
+		Duration::from_secs(secs)
+	}
+
+	#[test]
+	fn countdowns_drain_then_finish()
+	{
+		let timer = timer(TimerMode::Countdown, 60, 0, 1);
+		assert!(matches!(timer.phase(secs(0)), Phase::Draining(left, total) if left == secs(60) && total == secs(60)));
+		assert!(matches!(timer.phase(secs(45)), Phase::Draining(left, _) if left == secs(15)));
+		assert!(matches!(timer.phase(secs(60)), Phase::Done(over) if over.is_zero()));
+		assert!(matches!(timer.phase(secs(70)), Phase::Done(over) if over == secs(10)));
+	}
+
+	#[test]
+	fn stopwatches_keep_counting()
+	{
+		let timer = timer(TimerMode::Stopwatch, 0, 0, 1);
+		assert!(matches!(timer.phase(secs(100_000)), Phase::Counting(elapsed) if elapsed == secs(100_000)));
+	}
+
+	#[test]
+	fn pomodoros_rest_between_sessions_but_not_after_the_last()
+	{
+		let timer = timer(TimerMode::Pomodoro, 25, 5, 2);
+		assert!(matches!(timer.phase(secs(10)), Phase::Draining(left, _) if left == secs(15)));
+		assert!(matches!(timer.phase(secs(27)), Phase::Filling(gone, total) if gone == secs(2) && total == secs(5)));
+		assert!(matches!(timer.phase(secs(30)), Phase::Draining(left, _) if left == secs(25)));
+		assert!(matches!(timer.phase(secs(55)), Phase::Done(over) if over.is_zero()));
+	}
+
+	#[test]
+	fn huge_timers_are_capped()
+	{
+		let options = TimerOptions { mode: TimerMode::Pomodoro,
+		                             duration: u64::MAX,
+		                             rest: u64::MAX,
+		                             cycles: u32::MAX,
+		                             started: Some(u64::MAX) };
+		assert!(options.validate().is_err());
+
+		// Options that get past validation, such as from the state file, still can't overflow
+		let timer = Timer::new(options, false, &Arc::default());
+		assert!(matches!(timer.phase(Duration::MAX), Phase::Done(_)));
+		assert!(matches!(timer.phase(secs(0)), Phase::Draining(left, _) if left == secs(MAX_TIMER_SECONDS)));
+	}
+
+	#[test]
+	fn paused_timers_stay_put()
+	{
+		let control = Arc::new(Mutex::new(TimerControl::default()));
+		let options = TimerOptions { mode: TimerMode::Countdown, duration: 60, rest: 0, cycles: 1, started: Some(0) };
+		Timer::new(options.clone(), false, &control);
+
+		// A timer started long ago has been running all along, and is done
+		let mut control = control.lock().unwrap();
+		control.pause();
+		let elapsed = control.elapsed();
+		assert!(elapsed > secs(60));
+		assert_eq!(control.elapsed(), elapsed);
+
+		// Showing the same timer again doesn't start it over
+		control.start(&options);
+		assert_eq!(control.elapsed(), elapsed);
+	}
+}
@@ -56,7 +56,7 @@ fn run_service() -> windows_service::Result<()>
 	let args = Args::parse();
 
 	let error_tx = tx.clone();
-	spawn(move || match daemon_main(args)
+	spawn(move || match daemon_main(args, None)
 	{
 		Ok(_) => error_tx.send(ServiceExitCode::Win32(0)).unwrap(),
 		Err(e) if e.is::<SharedError>() =>
@@ -0,0 +1,142 @@
+use crate::{protocol::*, response::*, Error};
+use interprocess::local_socket::tokio::{prelude::*, Stream};
+use shared::{ipc::*, version::Version};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Connection to the daemon for use with tokio, otherwise the same as [`Client`](crate::Client)
+pub struct AsyncClient
+{
+	conn: BufReader<Stream>,
+	capabilities: Vec<String>,
+	next_id: u64,
+}
+
+impl AsyncClient
+{
+	/// Connect to the daemon on its usual socket
+	pub async fn connect() -> Result<Self, Error>
+	{
+		Self::connect_to(SOCKET_NAME).await
+	}
+
+	/// Connect to a daemon listening on a different socket, which is a path on Linux
+	pub async fn connect_to(name: &str) -> Result<Self, Error>
+	{
+		let mut client = AsyncClient { conn: BufReader::new(Stream::connect(socket_name(name)?).await?),
+		                               capabilities: vec![],
+		                               next_id: HELLO_ID };
+
+		let response = client.request(hello()).await?;
+		client.capabilities = check_hello(response)?;
+
+		Ok(client)
+	}
+
+	/// Whether the daemon knows about a command or capability, such as "RenderFile" or "BinaryFrames"
+	pub fn supports(&self, name: &str) -> bool
+	{
+		self.capabilities.iter().any(|c| c == name)
+	}
+
+	/// Send a command and wait for the daemon to carry it out, the typed methods below are easier for the usual commands
+	pub async fn call(&mut self, command: IpcCommand) -> Result<IpcResponse, Error>
+	{
+		check_command(&command, &self.capabilities)?;
+		self.request(command).await
+	}
+
+	/// Send a command that doesn't answer with anything, such as one that draws or changes a setting
+	pub async fn send(&mut self, command: IpcCommand) -> Result<(), Error>
+	{
+		done(self.call(command).await?)
+	}
+
+	pub async fn brightness(&mut self, left: bool, right: bool) -> Result<PerMatrix<u8>, Error>
+	{
+		brightness(self.call(IpcCommand::GetBrightness(left, right)).await?)
+	}
+
+	/// Whether the matrixes are asleep, which is known without waking them
+	pub async fn sleeping(&mut self, left: bool, right: bool) -> Result<PerMatrix<bool>, Error>
+	{
+		sleeping(self.call(IpcCommand::GetSleep(left, right)).await?)
+	}
+
+	pub async fn scrolling(&mut self, left: bool, right: bool) -> Result<PerMatrix<bool>, Error>
+	{
+		scrolling(self.call(IpcCommand::GetAnimate(left, right)).await?)
+	}
+
+	/// Firmware versions of the matrixes
+	pub async fn versions(&mut self, left: bool, right: bool) -> Result<PerMatrix<Version>, Error>
+	{
+		versions(self.call(IpcCommand::Version(left, right)).await?)
+	}
+
+	/// Everything the daemon knows about each matrix
+	pub async fn status(&mut self) -> Result<Status, Error>
+	{
+		match self.call(IpcCommand::Status).await?
+		{
+			IpcResponse::Status(status) => Ok(*status),
+			r => Err(unsupported(r)),
+		}
+	}
+
+	/// Turn the connection into a stream of events, every change to the matrixes is reported from here on
+	pub async fn subscribe(mut self) -> Result<AsyncEvents, Error>
+	{
+		let id = self.next_id;
+		self.call(IpcCommand::Subscribe).await?;
+
+		Ok(AsyncEvents { conn: self.conn, id })
+	}
+
+	async fn request(&mut self, command: IpcCommand) -> Result<IpcResponse, Error>
+	{
+		let request = IpcRequest { id: self.next_id, command };
+		self.next_id += 1;
+
+		self.conn.get_mut().write_all(&encode(&request, &self.capabilities)?).await?;
+		decode(read_line(&mut self.conn).await?.ok_or(Error::Closed)?, request.id)
+	}
+}
+
+/// Events from a subscription, which ends when the daemon goes away
+pub struct AsyncEvents
+{
+	conn: BufReader<Stream>,
+	id: u64,
+}
+
+impl AsyncEvents
+{
+	/// Wait for the next event, or `None` once the daemon has gone away
+	pub async fn next(&mut self) -> Option<Result<Event, Error>>
+	{
+		let line = match read_line(&mut self.conn).await
+		{
+			Ok(Some(line)) => line,
+			Ok(None) => return None,
+			Err(e) => return Some(Err(e)),
+		};
+
+		Some(match decode(line, self.id)
+		{
+			Ok(IpcResponse::Event(event)) => Ok(event),
+			Ok(r) => Err(unsupported(r)),
+			Err(e) => Err(e),
+		})
+	}
+}
+
+/// An empty read means the daemon closed the connection
+async fn read_line(conn: &mut BufReader<Stream>) -> Result<Option<String>, Error>
+{
+	let mut buffer = String::new();
+	match conn.read_line(&mut buffer).await?
+	{
+		0 => Ok(None),
+		_ => Ok(Some(buffer)),
+	}
+}
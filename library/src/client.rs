@@ -0,0 +1,143 @@
+use crate::{protocol::*, response::*, Error};
+use interprocess::local_socket::{prelude::*, Stream};
+use shared::{ipc::*, version::Version};
+use std::io::{BufRead, BufReader, Write};
+
+/// Blocking connection to the daemon
+pub struct Client
+{
+	conn: BufReader<Stream>,
+	capabilities: Vec<String>,
+	next_id: u64,
+}
+
+impl Client
+{
+	/// Connect to the daemon on its usual socket
+	pub fn connect() -> Result<Self, Error>
+	{
+		Self::connect_to(SOCKET_NAME)
+	}
+
+	/// Connect to a daemon listening on a different socket, which is a path on Linux
+	pub fn connect_to(name: &str) -> Result<Self, Error>
+	{
+		let mut client = Client { conn: BufReader::new(Stream::connect(socket_name(name)?)?),
+		                          capabilities: vec![],
+		                          next_id: HELLO_ID };
+
+		let response = client.request(hello())?;
+		client.capabilities = check_hello(response)?;
+
+		Ok(client)
+	}
+
+	/// Whether the daemon knows about a command or capability, such as "RenderFile" or "BinaryFrames"
+	pub fn supports(&self, name: &str) -> bool
+	{
+		self.capabilities.iter().any(|c| c == name)
+	}
+
+	/// Send a command and wait for the daemon to carry it out, the typed methods below are easier for the usual commands
+	pub fn call(&mut self, command: IpcCommand) -> Result<IpcResponse, Error>
+	{
+		check_command(&command, &self.capabilities)?;
+		self.request(command)
+	}
+
+	/// Send a command that doesn't answer with anything, such as one that draws or changes a setting
+	pub fn send(&mut self, command: IpcCommand) -> Result<(), Error>
+	{
+		done(self.call(command)?)
+	}
+
+	pub fn brightness(&mut self, left: bool, right: bool) -> Result<PerMatrix<u8>, Error>
+	{
+		brightness(self.call(IpcCommand::GetBrightness(left, right))?)
+	}
+
+	/// Whether the matrixes are asleep, which is known without waking them
+	pub fn sleeping(&mut self, left: bool, right: bool) -> Result<PerMatrix<bool>, Error>
+	{
+		sleeping(self.call(IpcCommand::GetSleep(left, right))?)
+	}
+
+	pub fn scrolling(&mut self, left: bool, right: bool) -> Result<PerMatrix<bool>, Error>
+	{
+		scrolling(self.call(IpcCommand::GetAnimate(left, right))?)
+	}
+
+	/// Firmware versions of the matrixes
+	pub fn versions(&mut self, left: bool, right: bool) -> Result<PerMatrix<Version>, Error>
+	{
+		versions(self.call(IpcCommand::Version(left, right))?)
+	}
+
+	/// Everything the daemon knows about each matrix
+	pub fn status(&mut self) -> Result<Status, Error>
+	{
+		match self.call(IpcCommand::Status)?
+		{
+			IpcResponse::Status(status) => Ok(*status),
+			r => Err(unsupported(r)),
+		}
+	}
+
+	/// Turn the connection into a stream of events, every change to the matrixes is reported from here on
+	pub fn subscribe(mut self) -> Result<Events, Error>
+	{
+		let id = self.next_id;
+		self.call(IpcCommand::Subscribe)?;
+
+		Ok(Events { conn: self.conn, id })
+	}
+
+	fn request(&mut self, command: IpcCommand) -> Result<IpcResponse, Error>
+	{
+		let request = IpcRequest { id: self.next_id, command };
+		self.next_id += 1;
+
+		self.conn.get_mut().write_all(&encode(&request, &self.capabilities)?)?;
+		decode(read_line(&mut self.conn)?.ok_or(Error::Closed)?, request.id)
+	}
+}
+
+/// Events from a subscription, which ends when the daemon goes away
+pub struct Events
+{
+	conn: BufReader<Stream>,
+	id: u64,
+}
+
+impl Iterator for Events
+{
+	type Item = Result<Event, Error>;
+
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		let line = match read_line(&mut self.conn)
+		{
+			Ok(Some(line)) => line,
+			Ok(None) => return None,
+			Err(e) => return Some(Err(e)),
+		};
+
+		Some(match decode(line, self.id)
+		{
+			Ok(IpcResponse::Event(event)) => Ok(event),
+			Ok(r) => Err(unsupported(r)),
+			Err(e) => Err(e),
+		})
+	}
+}
+
+/// An empty read means the daemon closed the connection
+fn read_line(conn: &mut BufReader<Stream>) -> Result<Option<String>, Error>
+{
+	let mut buffer = String::new();
+	match conn.read_line(&mut buffer)?
+	{
+		0 => Ok(None),
+		_ => Ok(Some(buffer)),
+	}
+}
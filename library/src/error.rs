@@ -0,0 +1,45 @@
+use shared::error::Error as SharedError;
+use std::{
+	fmt::{self, Display, Formatter},
+	io,
+};
+
+#[derive(Debug)]
+pub enum Error
+{
+	/// Talking to the daemon over the socket failed
+	Io(io::Error),
+	/// The daemon went away without replying
+	Closed,
+	/// A request couldn't be encoded
+	Encoding(String),
+	/// The daemon's reply couldn't be understood, or the daemon doesn't support what was asked of it
+	Protocol(SharedError),
+	/// The daemon couldn't carry out the command
+	Daemon(SharedError),
+}
+
+impl std::error::Error for Error {}
+
+impl Display for Error
+{
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error>
+	{
+		match self
+		{
+			Self::Io(e) => write!(f, "{}", e),
+			Self::Closed => write!(f, "the daemon closed the connection without replying"),
+			Self::Encoding(e) => write!(f, "failed to encode the request with error: {}", e),
+			Self::Protocol(e) => write!(f, "{}", e),
+			Self::Daemon(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl From<io::Error> for Error
+{
+	fn from(e: io::Error) -> Self
+	{
+		Self::Io(e)
+	}
+}
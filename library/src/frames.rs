@@ -0,0 +1,81 @@
+use shared::canvas::Canvas;
+use std::time::Duration;
+
+const HEIGHT: usize = 34;
+const WIDTH: usize = 9;
+
+/// Frames built up one canvas at a time
+#[derive(Debug, Clone, Default)]
+pub struct Animation
+{
+	frames: Vec<(Vec<u8>, Duration)>,
+}
+
+impl Animation
+{
+	pub fn new() -> Self
+	{
+		Self::default()
+	}
+
+	/// Add a snapshot of the canvas that's displayed for the given duration
+	pub fn push(&mut self, canvas: &Canvas, duration: Duration) -> &mut Self
+	{
+		self.frames.push((canvas.pixels().to_vec(), duration));
+		self
+	}
+
+	pub fn into_frames(self) -> Vec<(Vec<u8>, Duration)>
+	{
+		self.frames
+	}
+}
+
+/// Black and white image for `DrawBW`, which is cheaper to send than a grayscale frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap
+{
+	// One bit per LED in row-major order, starting from the least significant bit
+	bits: [u8; 39],
+}
+
+impl Default for Bitmap
+{
+	fn default() -> Self
+	{
+		Bitmap { bits: [0; 39] }
+	}
+}
+
+impl Bitmap
+{
+	pub fn new() -> Self
+	{
+		Self::default()
+	}
+
+	/// Turn an LED on or off, anything outside of the matrix is ignored
+	pub fn set(&mut self, x: usize, y: usize, on: bool)
+	{
+		if x < WIDTH && y < HEIGHT
+		{
+			let index = y * WIDTH + x;
+			match on
+			{
+				true => self.bits[index / 8] |= 1 << (index % 8),
+				false => self.bits[index / 8] &= !(1 << (index % 8)),
+			}
+		}
+	}
+
+	pub fn get(&self, x: usize, y: usize) -> bool
+	{
+		let index = y * WIDTH + x;
+		x < WIDTH && y < HEIGHT && self.bits[index / 8] & (1 << (index % 8)) != 0
+	}
+
+	pub fn to_vec(&self) -> Vec<u8>
+	{
+		self.bits.to_vec()
+	}
+}
@@ -0,0 +1,50 @@
+//! Client library for fw16-led-matrixd, which is what `ledcli` uses to talk to the daemon
+//!
+//! ```no_run
+//! use fw16_led_matrix::{Canvas, Client, IpcCommand};
+//!
+//! let mut client = Client::connect()?;
+//!
+//! let mut canvas = Canvas::single();
+//! canvas.set(4, 17, 255);
+//! client.send(IpcCommand::RenderSingle(Some(canvas.to_frames()), None))?;
+//! println!("{:?}", client.brightness(true, false)?.left);
+//!
+//! for event in client.subscribe()?
+//! {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok::<(), fw16_led_matrix::Error>(())
+//! ```
+//!
+//! An [`AsyncClient`] for tokio is available with the `tokio` feature.
+
+#[cfg(feature = "tokio")]
+mod async_client;
+mod client;
+mod error;
+mod frames;
+mod protocol;
+mod response;
+
+#[cfg(feature = "tokio")]
+pub use async_client::{AsyncClient, AsyncEvents};
+pub use client::{Client, Events};
+pub use error::Error;
+pub use frames::{Animation, Bitmap};
+pub use response::PerMatrix;
+pub use shared::{
+	canvas::Canvas,
+	error::Error as DaemonError,
+	image::{decode_image, read_image, ImageOptions, Rotation},
+	ipc::{
+		ContentKind, Event, IpcCommand, IpcResponse, MatrixStatus, Side, Status, Target, TimerAction, Urgency,
+		SOCKET_NAME,
+	},
+	text::render_text,
+	version::Version,
+	widget::{
+		BatteryOptions, ClockOptions, ClockStyle, Effect, EffectOptions, HwmonOptions, NetworkOptions,
+		NowPlayingOptions, SysmonOptions, SysmonPanel, TimerMode, TimerOptions, Widget,
+	},
+};
@@ -0,0 +1,107 @@
+use crate::Error;
+use shared::{error::Error as SharedError, ipc::*};
+
+/// ID of the hello exchange, commands are numbered after it
+pub const HELLO_ID: u64 = 1;
+
+pub fn hello() -> IpcCommand
+{
+	IpcCommand::Hello(PROTOCOL_VERSION, capabilities())
+}
+
+/// Make sure the daemon speaks the same protocol, and hand back what it's capable of
+pub fn check_hello(response: IpcResponse) -> Result<Vec<String>, Error>
+{
+	match response
+	{
+		IpcResponse::Hello(version, capabilities) =>
+		{
+			if version < MIN_PROTOCOL_VERSION
+			{
+				return Err(Error::Protocol(SharedError::DaemonTooOld(version, MIN_PROTOCOL_VERSION)));
+			}
+
+			Ok(capabilities)
+		},
+		r => Err(unsupported(r)),
+	}
+}
+
+/// Encode a request, frames are sent in the binary encoding when the daemon supports it
+pub fn encode(request: &IpcRequest, capabilities: &[String]) -> Result<Vec<u8>, Error>
+{
+	let binary = request.command.has_frames() && capabilities.iter().any(|c| c == BINARY_FRAMES);
+
+	match binary
+	{
+		true => request.to_binary(),
+		false => request.to_json().map(String::into_bytes),
+	}.map_err(|e| Error::Encoding(e.to_string()))
+}
+
+/// Make sure the command can be sent before anything goes over the socket
+pub fn check_command(command: &IpcCommand, capabilities: &[String]) -> Result<(), Error>
+{
+	command.validate().map_err(Error::Protocol)?;
+
+	match capabilities.iter().any(|c| c == command.name())
+	{
+		true => Ok(()),
+		false => Err(Error::Protocol(SharedError::UnsupportedCommand(command.name().to_string()))),
+	}
+}
+
+/// Decode a line from the daemon, which has to be the reply to the given request
+pub fn decode(line: String, id: u64) -> Result<IpcResponse, Error>
+{
+	// Daemons from before the request IDs reply without one, and wouldn't understand anything that's sent to them
+	if let Ok(legacy) = LegacyReply::from_json(&line)
+	{
+		let version = match legacy
+		{
+			LegacyReply::Hello(version, _) => version,
+			LegacyReply::InvalidCommand(_) => LEGACY_PROTOCOL_VERSION,
+		};
+		return Err(Error::Protocol(SharedError::DaemonTooOld(version, MIN_PROTOCOL_VERSION)));
+	}
+
+	let reply =
+		IpcReply::from_json(line).map_err(|e| Error::Protocol(SharedError::UnsupportedResponse(e.to_string())))?;
+	if reply.id != id
+	{
+		return Err(Error::Protocol(SharedError::UnexpectedReply(reply.id, id)));
+	}
+
+	reply.result.map_err(Error::Daemon)
+}
+
+pub fn unsupported(response: IpcResponse) -> Error
+{
+	Error::Protocol(SharedError::UnsupportedResponse(format!("{:?}", response)))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn frames_are_binary_only_when_the_daemon_supports_it()
+	{
+		let request = IpcRequest { id: 2, command: IpcCommand::RenderPair(vec![(vec![0; 612], Duration::ZERO)]) };
+
+		let binary = encode(&request, &[BINARY_FRAMES.to_string()]).unwrap();
+		assert_eq!(binary[0], BINARY_MARKER);
+
+		let json = encode(&request, &[]).unwrap();
+		assert_eq!(json[0], b'{');
+	}
+
+	#[test]
+	fn commands_without_frames_stay_json()
+	{
+		let request = IpcRequest { id: 2, command: IpcCommand::Status };
+		assert_eq!(encode(&request, &[BINARY_FRAMES.to_string()]).unwrap()[0], b'{');
+	}
+}
@@ -0,0 +1,89 @@
+use crate::{protocol::unsupported, Error};
+use shared::{error::Error as SharedError, ipc::IpcResponse, version::Version};
+
+/// Something the daemon answered for each matrix, a matrix that wasn't asked about is left out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PerMatrix<T>
+{
+	pub left: Option<T>,
+	pub right: Option<T>,
+}
+
+/// The daemon carried out the command and has nothing else to say
+pub fn done(response: IpcResponse) -> Result<(), Error>
+{
+	match response
+	{
+		IpcResponse::Done => Ok(()),
+		r => Err(unsupported(r)),
+	}
+}
+
+pub fn brightness(response: IpcResponse) -> Result<PerMatrix<u8>, Error>
+{
+	match response
+	{
+		IpcResponse::Brightness(left, right) => Ok(PerMatrix { left, right }),
+		r => Err(unsupported(r)),
+	}
+}
+
+pub fn sleeping(response: IpcResponse) -> Result<PerMatrix<bool>, Error>
+{
+	match response
+	{
+		IpcResponse::Sleeping(left, right) => Ok(PerMatrix { left, right }),
+		r => Err(unsupported(r)),
+	}
+}
+
+pub fn scrolling(response: IpcResponse) -> Result<PerMatrix<bool>, Error>
+{
+	match response
+	{
+		IpcResponse::Animated(left, right) => Ok(PerMatrix { left, right }),
+		r => Err(unsupported(r)),
+	}
+}
+
+pub fn versions(response: IpcResponse) -> Result<PerMatrix<Version>, Error>
+{
+	let version = |v: Option<Vec<u8>>| {
+		v.map(Version::try_from)
+		 .transpose()
+		 .map_err(|e| Error::Protocol(SharedError::UnsupportedResponse(format!("bad firmware version: {}", e))))
+	};
+
+	match response
+	{
+		IpcResponse::Version(left, right) => Ok(PerMatrix { left: version(left)?, right: version(right)? }),
+		r => Err(unsupported(r)),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn answers_are_split_by_matrix()
+	{
+		assert_eq!(
+		           brightness(IpcResponse::Brightness(Some(10), None)).unwrap(),
+		           PerMatrix { left: Some(10), right: None }
+		);
+
+		let versions = versions(IpcResponse::Version(None, Some(vec![0, 0x11, 1]))).unwrap();
+		assert!(versions.left.is_none());
+		assert_eq!(versions.right.map(|v| v.to_string()), Some(Version::from([0, 0x11, 1]).to_string()));
+	}
+
+	#[test]
+	fn the_wrong_answer_is_an_error()
+	{
+		assert!(sleeping(IpcResponse::Animated(Some(true), None)).is_err());
+		assert!(done(IpcResponse::Brightness(None, None)).is_err());
+		assert!(versions(IpcResponse::Version(Some(vec![1]), None)).is_err());
+	}
+}
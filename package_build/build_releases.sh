@@ -72,6 +72,8 @@ package()
 	install -Dm755 target/release/ledcli "\${pkgdir}/usr/bin/ledcli"
 	install -Dm644 sample-posix-config.toml "\${pkgdir}/etc/fw16-led-matrixd/config.toml"
 	install -Dm644 daemon/fw16-led-matrixd.service "\${pkgdir}/usr/lib/systemd/system/fw16-led-matrixd.service"
+	install -Dm644 daemon/fw16-led-matrixd.socket "\${pkgdir}/usr/lib/systemd/system/fw16-led-matrixd.socket"
+	install -Dm644 daemon/io.github.NukingDragons.LedMatrix1.conf "\${pkgdir}/usr/share/dbus-1/system.d/io.github.NukingDragons.LedMatrix1.conf"
 	install -Dm644 LICENSE "\${pkgdir}/usr/share/licenses/\${pkgname}/LICENSE"
 }
 EOF
@@ -86,8 +88,8 @@ makepkg --printsrcinfo > .SRCINFO
 cd ../..
 
 echo "Creating x86_64 deb package for version ${version}"
-mkdir -p deb/DEBIAN deb/etc/fw16-led-matrixd deb/usr/bin deb/usr/lib/systemd/system
-rm deb/DEBIAN/control deb/usr/bin/fw16-led-matrixd deb/usr/bin/ledcli deb/etc/fw16-led-matrixd/config.toml deb/usr/lib/systemd/system/fw16-led-matrixd.service 2>/dev/null
+mkdir -p deb/DEBIAN deb/etc/fw16-led-matrixd deb/usr/bin deb/usr/lib/systemd/system deb/usr/share/dbus-1/system.d
+rm deb/DEBIAN/control deb/usr/bin/fw16-led-matrixd deb/usr/bin/ledcli deb/etc/fw16-led-matrixd/config.toml deb/usr/lib/systemd/system/fw16-led-matrixd.service deb/usr/lib/systemd/system/fw16-led-matrixd.socket deb/usr/share/dbus-1/system.d/io.github.NukingDragons.LedMatrix1.conf 2>/dev/null
 cat > deb/DEBIAN/control << EOF
 Package: fw16-led-matrixd
 Version: ${version}
@@ -102,6 +104,8 @@ cp ../target/release/fw16-led-matrixd deb/usr/bin/
 cp ../target/release/ledcli deb/usr/bin/
 cp ../sample-posix-config.toml deb/etc/fw16-led-matrixd/config.toml
 cp ../daemon/fw16-led-matrixd.service deb/usr/lib/systemd/system/
+cp ../daemon/fw16-led-matrixd.socket deb/usr/lib/systemd/system/
+cp ../daemon/io.github.NukingDragons.LedMatrix1.conf deb/usr/share/dbus-1/system.d/
 dpkg-deb --build --root-owner-group deb
 mv deb.deb ${debname}-1_amd64.deb
 
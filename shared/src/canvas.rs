@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+const HEIGHT: usize = 34;
+const WIDTH: usize = 9;
+
+/// Grayscale drawing surface for one matrix, or both of them side by side when they're treated as a pair
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas
+{
+	width: usize,
+	// Column-major, which is the order the matrixes are drawn in
+	pixels: Vec<u8>,
+}
+
+impl Canvas
+{
+	/// A blank 9x34 canvas for a single matrix
+	pub fn single() -> Self
+	{
+		Canvas { width: WIDTH, pixels: vec![0; WIDTH * HEIGHT] }
+	}
+
+	/// A blank 18x34 canvas spanning both matrixes
+	pub fn pair() -> Self
+	{
+		Canvas { width: WIDTH * 2, pixels: vec![0; WIDTH * 2 * HEIGHT] }
+	}
+
+	pub fn new(pair: bool) -> Self
+	{
+		match pair
+		{
+			true => Self::pair(),
+			false => Self::single(),
+		}
+	}
+
+	pub fn width(&self) -> usize
+	{
+		self.width
+	}
+
+	pub fn height(&self) -> usize
+	{
+		HEIGHT
+	}
+
+	/// Set the brightness of a pixel, anything outside of the canvas is ignored
+	pub fn set(&mut self, x: usize, y: usize, brightness: u8)
+	{
+		if x < self.width && y < HEIGHT
+		{
+			self.pixels[x * HEIGHT + y] = brightness;
+		}
+	}
+
+	/// Brightness of a pixel, anything outside of the canvas is dark
+	pub fn get(&self, x: usize, y: usize) -> u8
+	{
+		match x < self.width && y < HEIGHT
+		{
+			true => self.pixels[x * HEIGHT + y],
+			false => 0,
+		}
+	}
+
+	pub fn fill(&mut self, brightness: u8)
+	{
+		self.pixels.fill(brightness);
+	}
+
+	pub fn clear(&mut self)
+	{
+		self.fill(0);
+	}
+
+	/// Pixels in column-major order, which is how a single frame is sent to the matrixes
+	pub fn pixels(&self) -> &[u8]
+	{
+		&self.pixels
+	}
+
+	/// The canvas as a single frame, ready for `RenderSingle` or `RenderPair`
+	pub fn to_frames(&self) -> Vec<(Vec<u8>, Duration)>
+	{
+		vec![(self.pixels.clone(), Duration::default())]
+	}
+}
@@ -14,6 +14,12 @@ pub enum Error
 	InvalidVecSizeFrame(String, usize, usize),
 	InvalidColNumber(u8),
 	Handler(String),
+	ClientTooOld(u32, u32),
+	DaemonTooOld(u32, u32),
+	UnsupportedCommand(String),
+	UnsupportedResponse(String),
+	UnexpectedReply(u64, u64),
+	RelativePath(String),
 	#[cfg(windows)]
 	WindowsError(i32),
 }
@@ -46,6 +52,27 @@ impl Display for Error
 			},
 			Self::InvalidColNumber(col) => write!(f, "invalid column number {} (must be between 0 and 8)", col),
 			Self::Handler(msg) => write!(f, "{}", msg),
+			Self::ClientTooOld(client, min) =>
+			{
+				write!(f, "client protocol version {} is too old, the daemon needs at least version {}", client, min)
+			},
+			Self::DaemonTooOld(daemon, min) =>
+			{
+				write!(f, "daemon protocol version {} is too old, the client needs at least version {}", daemon, min)
+			},
+			Self::UnsupportedCommand(e) =>
+			{
+				write!(f, "the daemon doesn't support this command ({}), is it out of date?", e)
+			},
+			Self::UnsupportedResponse(e) =>
+			{
+				write!(f, "the client doesn't understand the daemon's response ({}), is it out of date?", e)
+			},
+			Self::UnexpectedReply(got, expected) =>
+			{
+				write!(f, "the daemon replied to request {} while waiting on request {}", got, expected)
+			},
+			Self::RelativePath(path) => write!(f, "path \"{}\" must be absolute for the daemon to find it", path),
 			#[cfg(windows)]
 			Self::WindowsError(status) => write!(f, "Win32 API error: {:x}", status),
 		}
@@ -66,6 +93,12 @@ impl Into<ServiceExitCode> for Error
 			Self::InvalidVecSizeFrame(_, _, _) => ServiceExitCode::ServiceSpecific(0xDEAD0005),
 			Self::InvalidColNumber(_) => ServiceExitCode::ServiceSpecific(0xDEAD0006),
 			Self::Handler(_) => ServiceExitCode::ServiceSpecific(0xDEAD0007),
+			Self::ClientTooOld(_, _) => ServiceExitCode::ServiceSpecific(0xDEAD0008),
+			Self::DaemonTooOld(_, _) => ServiceExitCode::ServiceSpecific(0xDEAD0009),
+			Self::UnsupportedCommand(_) => ServiceExitCode::ServiceSpecific(0xDEAD000A),
+			Self::UnsupportedResponse(_) => ServiceExitCode::ServiceSpecific(0xDEAD000B),
+			Self::UnexpectedReply(_, _) => ServiceExitCode::ServiceSpecific(0xDEAD000C),
+			Self::RelativePath(_) => ServiceExitCode::ServiceSpecific(0xDEAD000D),
 			Self::WindowsError(e) => ServiceExitCode::Win32(e as u32),
 		}
 	}
@@ -0,0 +1,72 @@
+use std::{
+	error::Error,
+	fs::{read_dir, read_to_string},
+	path::{Path, PathBuf},
+};
+
+/// Where the kernel lists hardware monitoring chips
+pub const HWMON: &str = "/sys/class/hwmon";
+
+/// A single reading from a hardware monitoring chip, such as a temperature or a fan
+#[derive(Debug, Clone)]
+pub struct HwmonInput
+{
+	/// Name the chip gives itself, such as "k10temp" or "coretemp"
+	pub chip: String,
+	/// File the reading comes from, such as "temp1_input"
+	pub input: String,
+	/// What the chip calls the reading, such as "Tctl" or "Package id 0"
+	pub label: Option<String>,
+	pub path: PathBuf,
+}
+
+impl HwmonInput
+{
+	/// The reading in degrees Celsius, RPM, volts, amps, or watts, depending on what kind of input it is
+	pub fn read(&self) -> Result<f64, Box<dyn Error>>
+	{
+		let raw: f64 = read_to_string(&self.path)?.trim().parse()?;
+
+		// Temperatures, voltages, and currents are reported in thousandths, and power in millionths
+		let scale = match self.input.split(|c: char| c.is_ascii_digit()).next()
+		{
+			Some("temp") | Some("in") | Some("curr") => 1000.0,
+			Some("power") | Some("energy") => 1_000_000.0,
+			_ => 1.0,
+		};
+
+		Ok(raw / scale)
+	}
+}
+
+/// Every input of every chip under the given root, in the order the chips were found
+pub fn hwmon_inputs(root: &Path) -> Result<Vec<HwmonInput>, Box<dyn Error>>
+{
+	let mut chips: Vec<PathBuf> = read_dir(root)?.map(|e| e.map(|e| e.path())).collect::<Result<_, _>>()?;
+	chips.sort();
+
+	let mut inputs = vec![];
+	for chip in chips
+	{
+		let name = match read_to_string(chip.join("name"))
+		{
+			Ok(name) => name.trim().to_string(),
+			// Not every entry is a chip
+			Err(_) => continue,
+		};
+
+		let mut files: Vec<String> =
+			read_dir(&chip)?.filter_map(|e| e.ok().map(|e| e.file_name().to_string_lossy().to_string()))
+			                .filter(|f| f.ends_with("_input"))
+			                .collect();
+		files.sort();
+
+		for input in files
+		{
+			let label = read_to_string(chip.join(input.replace("_input", "_label"))).ok().map(|l| l.trim().to_string());
+			inputs.push(HwmonInput { chip: name.clone(), path: chip.join(&input), input, label });
+		}
+	}
+
+	Ok(inputs)
+}
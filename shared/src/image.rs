@@ -0,0 +1,240 @@
+// Frames are passed around as plain vectors of columns and durations, the same as the IPC commands that carry them
+#![allow(clippy::type_complexity)]
+
+use image::{
+	codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+	imageops::FilterType,
+	AnimationDecoder, DynamicImage, ImageFormat, ImageReader,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	error::Error,
+	fs::File,
+	io::{BufRead, BufReader, Seek},
+	time::Duration,
+};
+
+/// Clockwise rotation applied before the image is scaled to fit the matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Rotation
+{
+	#[default]
+	None,
+	Cw90,
+	Cw180,
+	Cw270,
+}
+
+impl TryFrom<u16> for Rotation
+{
+	type Error = String;
+
+	fn try_from(degrees: u16) -> Result<Self, Self::Error>
+	{
+		match degrees
+		{
+			0 => Ok(Rotation::None),
+			90 => Ok(Rotation::Cw90),
+			180 => Ok(Rotation::Cw180),
+			270 => Ok(Rotation::Cw270),
+			_ => Err(format!("can't rotate by {} degrees, only 0, 90, 180 and 270 are supported", degrees)),
+		}
+	}
+}
+
+/// Adjustments made to an image while it's being read
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageOptions
+{
+	pub rotate: Rotation,
+	pub flip_horizontal: bool,
+	pub flip_vertical: bool,
+	pub invert: bool,
+}
+
+impl ImageOptions
+{
+	fn apply(&self, image: DynamicImage) -> DynamicImage
+	{
+		let mut image = match self.rotate
+		{
+			Rotation::None => image,
+			Rotation::Cw90 => image.rotate90(),
+			Rotation::Cw180 => image.rotate180(),
+			Rotation::Cw270 => image.rotate270(),
+		};
+
+		if self.flip_horizontal
+		{
+			image = image.fliph();
+		}
+
+		if self.flip_vertical
+		{
+			image = image.flipv();
+		}
+
+		if self.invert
+		{
+			image.invert();
+		}
+
+		image
+	}
+}
+
+pub fn check_format<T: BufRead + Seek>(filedata: T) -> Result<ImageFormat, Box<dyn Error>>
+{
+	ImageReader::new(filedata).with_guessed_format()?.format().ok_or("failed to determine image type".into())
+}
+
+pub fn read_image(filename: String,
+                  pair: bool,
+                  options: &ImageOptions)
+                  -> Result<Vec<(Vec<u8>, Duration)>, Box<dyn Error>>
+{
+	decode_image(BufReader::new(File::open(&filename)?), pair, options)
+}
+
+/// Decode an image that isn't in a file, such as one that was uploaded
+pub fn decode_image<T: BufRead + Seek>(mut filedata: T,
+                                       pair: bool,
+                                       options: &ImageOptions)
+                                       -> Result<Vec<(Vec<u8>, Duration)>, Box<dyn Error>>
+{
+	match check_format(&mut filedata)?
+	{
+		ImageFormat::Png =>
+		{
+			let decoder = PngDecoder::new(&mut filedata)?;
+
+			if decoder.is_apng().unwrap_or_default()
+			{
+				read_animated(decoder.apng()?, pair, options)
+			}
+			else
+			{
+				filedata.rewind()?;
+				read_unanimated(filedata, pair, options)
+			}
+		},
+		ImageFormat::WebP => read_animated(WebPDecoder::new(filedata)?, pair, options),
+		ImageFormat::Gif => read_animated(GifDecoder::new(filedata)?, pair, options),
+		_ => read_unanimated(filedata, pair, options),
+	}
+}
+
+fn read_unanimated<T: BufRead + Seek>(filedata: T,
+                                      pair: bool,
+                                      options: &ImageOptions)
+                                      -> Result<Vec<(Vec<u8>, Duration)>, Box<dyn Error>>
+{
+	let width = match pair
+	{
+		true => 18,
+		false => 9,
+	};
+
+	Ok(vec![(
+		to_column_major(
+			options.apply(ImageReader::new(filedata).with_guessed_format()?.decode()?)
+			       .resize_exact(width, 34, FilterType::Nearest)
+			       .grayscale()
+			       .to_luma8()
+			       .to_vec(),
+			width as usize,
+			34,
+		),
+		Duration::default(),
+	)])
+}
+
+fn read_animated<'a, T: AnimationDecoder<'a>>(decoder: T,
+                                              pair: bool,
+                                              options: &ImageOptions)
+                                              -> Result<Vec<(Vec<u8>, Duration)>, Box<dyn Error>>
+{
+	let width = match pair
+	{
+		true => 18,
+		false => 9,
+	};
+
+	let frames = decoder.into_frames().collect_frames()?;
+	let mut result: Vec<(Vec<u8>, Duration)> = Vec::with_capacity(frames.len());
+	for frame in frames
+	{
+		let (numerator, denominator) = frame.delay().numer_denom_ms();
+		let duration = Duration::from_millis((numerator as u64) / (denominator as u64));
+
+		result.push((
+			to_column_major(
+				options.apply(DynamicImage::ImageRgba8(frame.into_buffer()))
+				       .resize_exact(width, 34, FilterType::Nearest)
+				       .grayscale()
+				       .to_luma8()
+				       .to_vec(),
+				width as usize,
+				34,
+			),
+			duration,
+		));
+	}
+
+	Ok(result)
+}
+
+fn to_column_major(frame: Vec<u8>, width: usize, height: usize) -> Vec<u8>
+{
+	let mut result: Vec<u8> = vec![0; width * 34];
+
+	for (index, val) in frame.iter().enumerate()
+	{
+		let col = index % width;
+		let row = index / width;
+
+		result[(col * height) + row] = *val;
+	}
+
+	result
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use image::GrayImage;
+
+	// Two pixels side by side, black on the left and white on the right
+	fn image() -> DynamicImage
+	{
+		DynamicImage::ImageLuma8(GrayImage::from_raw(2, 1, vec![0, 255]).unwrap())
+	}
+
+	#[test]
+	fn rotation_turns_the_image_clockwise()
+	{
+		let options = ImageOptions { rotate: Rotation::Cw90, ..Default::default() };
+		assert_eq!(options.apply(image()).to_luma8().into_raw(), vec![0, 255]);
+
+		let options = ImageOptions { rotate: Rotation::Cw270, ..Default::default() };
+		assert_eq!(options.apply(image()).to_luma8().into_raw(), vec![255, 0]);
+	}
+
+	#[test]
+	fn flipping_and_inverting()
+	{
+		let options = ImageOptions { flip_horizontal: true, ..Default::default() };
+		assert_eq!(options.apply(image()).to_luma8().into_raw(), vec![255, 0]);
+
+		let options = ImageOptions { invert: true, ..Default::default() };
+		assert_eq!(options.apply(image()).to_luma8().into_raw(), vec![255, 0]);
+	}
+
+	#[test]
+	fn only_right_angles()
+	{
+		assert_eq!(Rotation::try_from(180), Ok(Rotation::Cw180));
+		assert!(Rotation::try_from(45).is_err());
+	}
+}
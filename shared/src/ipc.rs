@@ -1,11 +1,95 @@
-use crate::error::Error as CrateError;
+use crate::{error::Error as CrateError, image::ImageOptions, widget::Widget};
+use interprocess::local_socket::Name;
+#[cfg(unix)]
+use interprocess::local_socket::{GenericFilePath, ToFsName};
+#[cfg(windows)]
+use interprocess::local_socket::{GenericNamespaced, ToNsName};
+use postcard::{from_bytes, take_from_bytes, to_allocvec};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string};
-use std::{error::Error, time::Duration};
+use std::{error::Error, path::Path, time::Duration};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Bumped whenever the commands or responses change in a way that an older client or daemon can't understand
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The oldest protocol version that is still spoken
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// Capability for sending requests in the binary encoding
+pub const BINARY_FRAMES: &str = "BinaryFrames";
+
+/// Starts off every binary request, JSON requests can't be mistaken for one since they always start with a brace
+pub const BINARY_MARKER: u8 = 0x00;
+
+/// Largest binary request that will be read, which is far more than any animation should need
+pub const MAX_BINARY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Socket the daemon listens on, which is a path so that a systemd socket unit can manage its permissions
+#[cfg(unix)]
+pub const SOCKET_NAME: &str = "/run/fw16-led-matrixd.socket";
+
+/// Named pipe the daemon listens on
+#[cfg(windows)]
+pub const SOCKET_NAME: &str = "fw16-led-matrixd.socket";
+
+/// Turn a socket name into something that can be listened on or connected to
+#[cfg(unix)]
+pub fn socket_name(name: &str) -> std::io::Result<Name<'_>>
+{
+	name.to_fs_name::<GenericFilePath>()
+}
+
+/// Turn a socket name into something that can be listened on or connected to
+#[cfg(windows)]
+pub fn socket_name(name: &str) -> std::io::Result<Name<'_>>
+{
+	name.to_ns_name::<GenericNamespaced>()
+}
+
+/// Every command and encoding this build understands, which is advertised as the capabilities during the hello exchange
+pub const CAPABILITIES: &[&str] = &[BINARY_FRAMES,
+                                    "Hello",
+                                    "SetBrightness",
+                                    "GetBrightness",
+                                    "PatternPercentage",
+                                    "PatternGradient",
+                                    "PatternDoubleGradient",
+                                    "PatternLotusHorizontal",
+                                    "PatternLotusVertical",
+                                    "PatternZigzag",
+                                    "PatternFullbright",
+                                    "PatternPanic",
+                                    "Bootloader",
+                                    "SetSleep",
+                                    "GetSleep",
+                                    "SetAnimate",
+                                    "GetAnimate",
+                                    "Crash",
+                                    "DrawBW",
+                                    "StageCol",
+                                    "FlushCols",
+                                    "Version",
+                                    "RenderSingle",
+                                    "RenderPair",
+                                    "RenderText",
+                                    "RenderTextPair",
+                                    "Status",
+                                    "Subscribe",
+                                    "RenderFile",
+                                    "StartWidget",
+                                    "Notify",
+                                    "Timer"];
+
+pub fn capabilities() -> Vec<String>
+{
+	CAPABILITIES.iter().map(|c| c.to_string()).collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IpcCommand
 {
+	/// Protocol version and capabilities of the client
+	Hello(u32, Vec<String>),
 	SetBrightness(Option<u8>, Option<u8>),
 	GetBrightness(bool, bool),
 	PatternPercentage(Option<u8>, Option<u8>),
@@ -28,6 +112,83 @@ pub enum IpcCommand
 	Version(bool, bool),
 	RenderSingle(Option<Vec<(Vec<u8>, Duration)>>, Option<Vec<(Vec<u8>, Duration)>>),
 	RenderPair(Vec<(Vec<u8>, Duration)>),
+	RenderText(Option<String>, Option<String>),
+	RenderTextPair(String),
+	Status,
+	/// Keep the connection open and receive events as they happen
+	Subscribe,
+	/// Have the daemon read the image itself, the path must be readable by the daemon
+	RenderFile
+	{
+		path: String,
+		target: Target,
+		options: ImageOptions,
+	},
+	/// Have the daemon draw a widget until something else is displayed
+	StartWidget
+	{
+		target: Target,
+		widget: Widget,
+	},
+	/// Briefly show a desktop notification, the daemon's config decides what's shown and what's ignored
+	Notify
+	{
+		app: String,
+		summary: String,
+		urgency: Urgency,
+	},
+	/// Control the timer widget that's displayed on the target, each matrix and the pair have their own timer
+	Timer
+	{
+		target: Target,
+		action: TimerAction,
+	},
+}
+
+/// Which matrixes a command should display on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target
+{
+	Left,
+	Right,
+	Both,
+	Pair,
+}
+
+/// What to do with the timer widget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerAction
+{
+	Pause,
+	Resume,
+	/// Stop the timer and go back to what was displayed before it
+	Cancel,
+}
+
+/// How urgent a desktop notification is, as given in its hints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency
+{
+	Low,
+	#[default]
+	Normal,
+	Critical,
+}
+
+impl From<u8> for Urgency
+{
+	fn from(urgency: u8) -> Self
+	{
+		match urgency
+		{
+			0 => Urgency::Low,
+			1 => Urgency::Normal,
+			_ => Urgency::Critical,
+		}
+	}
 }
 
 impl IpcCommand
@@ -37,6 +198,17 @@ impl IpcCommand
 		let mut matrix = "";
 		match self
 		{
+			IpcCommand::Hello(version, _) =>
+			{
+				if *version < MIN_PROTOCOL_VERSION
+				{
+					Err(CrateError::ClientTooOld(*version, MIN_PROTOCOL_VERSION))
+				}
+				else
+				{
+					Ok(())
+				}
+			},
 			IpcCommand::DrawBW(left, right) =>
 			{
 				if let Some(l) = left
@@ -147,37 +319,269 @@ impl IpcCommand
 
 				Ok(())
 			},
+			// The daemon doesn't share the client's working directory
+			IpcCommand::RenderFile { path, .. } =>
+			{
+				if Path::new(path).is_absolute()
+				{
+					Ok(())
+				}
+				else
+				{
+					Err(CrateError::RelativePath(path.clone()))
+				}
+			},
+			IpcCommand::StartWidget { widget: Widget::Timer(options), .. } =>
+			{
+				options.validate().map_err(CrateError::Handler)
+			},
 			_ => Ok(()),
 		}
 	}
 
 	pub fn needs_response(&self) -> bool
 	{
-		matches!(self, Self::GetBrightness(_, _) | Self::GetSleep(_, _) | Self::GetAnimate(_, _) | Self::Version(_, _))
+		matches!(
+		         self,
+		         Self::Hello(_, _)
+		         | Self::GetBrightness(_, _)
+		         | Self::GetSleep(_, _)
+		         | Self::GetAnimate(_, _)
+		         | Self::Version(_, _)
+		         | Self::Status
+		         | Self::Subscribe
+		)
 	}
 
-	pub fn to_json(&self) -> Result<String, Box<dyn Error>>
+	/// Whether the command carries frames, which are much smaller in the binary encoding
+	pub fn has_frames(&self) -> bool
 	{
-		Ok(format!("{}\n", to_string(self)?))
+		matches!(self, Self::RenderSingle(_, _) | Self::RenderPair(_))
 	}
 
-	pub fn from_json(json: String) -> Result<Self, Box<dyn Error>>
+	/// Name of the command as it appears in the capabilities
+	pub fn name(&self) -> &'static str
 	{
-		Ok(from_str(&json)?)
+		match self
+		{
+			Self::Hello(_, _) => "Hello",
+			Self::SetBrightness(_, _) => "SetBrightness",
+			Self::GetBrightness(_, _) => "GetBrightness",
+			Self::PatternPercentage(_, _) => "PatternPercentage",
+			Self::PatternGradient(_, _) => "PatternGradient",
+			Self::PatternDoubleGradient(_, _) => "PatternDoubleGradient",
+			Self::PatternLotusHorizontal(_, _) => "PatternLotusHorizontal",
+			Self::PatternLotusVertical(_, _) => "PatternLotusVertical",
+			Self::PatternZigzag(_, _) => "PatternZigzag",
+			Self::PatternFullbright(_, _) => "PatternFullbright",
+			Self::PatternPanic(_, _) => "PatternPanic",
+			Self::Bootloader(_, _) => "Bootloader",
+			Self::SetSleep(_, _) => "SetSleep",
+			Self::GetSleep(_, _) => "GetSleep",
+			Self::SetAnimate(_, _) => "SetAnimate",
+			Self::GetAnimate(_, _) => "GetAnimate",
+			Self::Crash(_, _) => "Crash",
+			Self::DrawBW(_, _) => "DrawBW",
+			Self::StageCol(_, _) => "StageCol",
+			Self::FlushCols(_, _) => "FlushCols",
+			Self::Version(_, _) => "Version",
+			Self::RenderSingle(_, _) => "RenderSingle",
+			Self::RenderPair(_) => "RenderPair",
+			Self::RenderText(_, _) => "RenderText",
+			Self::RenderTextPair(_) => "RenderTextPair",
+			Self::Status => "Status",
+			Self::Subscribe => "Subscribe",
+			Self::RenderFile { .. } => "RenderFile",
+			Self::StartWidget { .. } => "StartWidget",
+			Self::Notify { .. } => "Notify",
+			Self::Timer { .. } => "Timer",
+		}
 	}
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum IpcResponse
 {
+	/// Protocol version and capabilities of the daemon
+	Hello(u32, Vec<String>),
+	/// The command was carried out and has nothing else to report
+	Done,
 	Brightness(Option<u8>, Option<u8>),
 	Sleeping(Option<bool>, Option<bool>),
 	Animated(Option<bool>, Option<bool>),
 	Version(Option<Vec<u8>>, Option<Vec<u8>>),
-	InvalidCommand(CrateError),
+	Status(Box<Status>),
+	Event(Event),
+}
+
+/// What kind of content a matrix is displaying
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind
+{
+	Pattern(String),
+	Bitmap,
+	Image,
+	Animation,
+	Text(String),
+	/// Read by the daemon from the given path
+	File(String),
+	/// Drawn by the daemon, such as a clock
+	Widget(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixStatus
+{
+	pub port: String,
+	/// Whether the port is currently open
+	pub connected: bool,
+	pub version: Option<Vec<u8>>,
+	pub brightness: Option<u8>,
+	pub sleeping: bool,
+	pub scroll: Option<bool>,
+	pub content: Option<ContentKind>,
+	/// Whether the content spans both matrixes
+	pub pair: bool,
+	/// Index of the frame being displayed, only set while animating
+	pub frame: Option<usize>,
+	/// How many times the animation has looped, only set while animating
+	pub loops: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status
+{
+	pub uptime: Duration,
+	/// Only the matrixes in the config are reported
+	pub left: Option<MatrixStatus>,
+	pub right: Option<MatrixStatus>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side
+{
+	Left,
+	Right,
+}
+
+/// Sent to subscribers whenever something about the matrixes changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "event")]
+pub enum Event
+{
+	ContentChanged
+	{
+		matrix: Side, content: Option<ContentKind>
+	},
+	AnimationFinished
+	{
+		matrix: Side
+	},
+	BrightnessChanged
+	{
+		matrix: Side, brightness: u8
+	},
+	SleepChanged
+	{
+		matrix: Side, sleeping: bool
+	},
+	ScrollChanged
+	{
+		matrix: Side, scrolling: bool
+	},
+	Connected
+	{
+		matrix: Side
+	},
+	Disconnected
+	{
+		matrix: Side
+	},
+	RenderError
+	{
+		message: String
+	},
+}
+
+impl Event
+{
+	pub fn to_json(&self) -> Result<String, Box<dyn Error>>
+	{
+		Ok(to_string(self)?)
+	}
+}
+
+/// A command along with an ID that the daemon echoes back in its reply
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpcRequest
+{
+	pub id: u64,
+	pub command: IpcCommand,
 }
 
-impl IpcResponse
+// Just enough of a request to reply to one that can't be parsed
+#[derive(Deserialize)]
+struct RequestId
+{
+	id: u64,
+}
+
+impl IpcRequest
+{
+	pub fn to_json(&self) -> Result<String, Box<dyn Error>>
+	{
+		Ok(format!("{}\n", to_string(self)?))
+	}
+
+	/// Parse a request, on failure the ID is still returned if it could be found so the error can be replied to
+	pub fn from_json(json: String) -> Result<Self, (u64, CrateError)>
+	{
+		// Commands from a newer client might not exist here, so say so instead of just failing to parse
+		from_str(&json).map_err(|e| {
+			               let id = from_str::<RequestId>(&json).map(|r| r.id).unwrap_or_default();
+			               (id, CrateError::UnsupportedCommand(e.to_string()))
+		               })
+	}
+
+	/// Encode the request as the marker, the length of the payload as a little endian u32, and then the payload
+	pub fn to_binary(&self) -> Result<Vec<u8>, Box<dyn Error>>
+	{
+		let payload = to_allocvec(self)?;
+		if payload.len() > MAX_BINARY_SIZE
+		{
+			return Err(format!("binary request of {} bytes is too large", payload.len()).into());
+		}
+
+		let mut binary = Vec::with_capacity(payload.len() + 5);
+		binary.push(BINARY_MARKER);
+		binary.extend((payload.len() as u32).to_le_bytes());
+		binary.extend(payload);
+
+		Ok(binary)
+	}
+
+	/// Parse the payload of a binary request, on failure the ID is still returned if it could be found
+	pub fn from_binary(payload: &[u8]) -> Result<Self, (u64, CrateError)>
+	{
+		from_bytes(payload).map_err(|e| {
+			                   // The ID is the first thing in the payload
+			                   let id = take_from_bytes::<u64>(payload).map(|(id, _)| id).unwrap_or_default();
+			                   (id, CrateError::UnsupportedCommand(e.to_string()))
+		                   })
+	}
+}
+
+/// The daemon's reply to every request, either a response or the reason the command failed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpcReply
+{
+	pub id: u64,
+	pub result: Result<IpcResponse, CrateError>,
+}
+
+impl IpcReply
 {
 	pub fn to_json(&self) -> Result<String, Box<dyn Error>>
 	{
@@ -186,6 +590,130 @@ impl IpcResponse
 
 	pub fn from_json(json: String) -> Result<Self, Box<dyn Error>>
 	{
-		Ok(from_str(&json)?)
+		// Responses from a newer daemon might not exist here, so say so instead of just failing to parse
+		from_str(&json).map_err(|e| CrateError::UnsupportedResponse(e.to_string()).into())
+	}
+}
+
+/// The only command older clients send before anything else, from before requests had an ID
+#[derive(Debug, Serialize, Deserialize)]
+pub enum LegacyCommand
+{
+	Hello(u32, Vec<String>),
+}
+
+/// Replies as older clients and daemons understand them, from before replies were tied to a request
+#[derive(Debug, Serialize, Deserialize)]
+pub enum LegacyReply
+{
+	Hello(u32, Vec<String>),
+	InvalidCommand(CrateError),
+}
+
+/// Daemons that reply without an ID speak this version at most
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+impl LegacyReply
+{
+	/// Reply to a line that isn't a request, which comes from a client older than the request IDs
+	pub fn for_line(json: &str) -> Option<Self>
+	{
+		if from_str::<RequestId>(json).is_ok()
+		{
+			return None;
+		}
+
+		Some(match from_str::<LegacyCommand>(json)
+		{
+			Ok(LegacyCommand::Hello(version, _)) if version < MIN_PROTOCOL_VERSION =>
+			{
+				Self::InvalidCommand(CrateError::ClientTooOld(version, MIN_PROTOCOL_VERSION))
+			},
+			Ok(LegacyCommand::Hello(_, _)) => Self::Hello(PROTOCOL_VERSION, capabilities()),
+			// Clients from before the hello exchange only know about handler errors
+			Err(_) => Self::InvalidCommand(CrateError::Handler(CrateError::ClientTooOld(0, MIN_PROTOCOL_VERSION).to_string())),
+		})
+	}
+
+	pub fn to_json(&self) -> Result<String, Box<dyn Error>>
+	{
+		Ok(format!("{}\n", to_string(self)?))
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>>
+	{
+		Ok(from_str(json)?)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn old_hello_is_told_the_client_is_too_old()
+	{
+		let reply = LegacyReply::for_line("{\"Hello\":[1,[\"Hello\"]]}\n");
+		assert!(matches!(reply, Some(LegacyReply::InvalidCommand(CrateError::ClientTooOld(1, MIN_PROTOCOL_VERSION)))));
+	}
+
+	#[test]
+	fn command_from_before_the_hello_gets_a_handler_error()
+	{
+		let reply = LegacyReply::for_line("{\"SetBrightness\":[100,null]}\n");
+		assert!(matches!(reply, Some(LegacyReply::InvalidCommand(CrateError::Handler(_)))));
+	}
+
+	#[test]
+	fn requests_are_not_legacy()
+	{
+		let request = IpcRequest { id: 4, command: IpcCommand::Status }.to_json().unwrap();
+		assert!(LegacyReply::for_line(&request).is_none());
+
+		// Even a request for a command that doesn't exist here is answered the usual way
+		assert!(LegacyReply::for_line("{\"id\":4,\"command\":\"Teleport\"}").is_none());
+	}
+
+	#[test]
+	fn frames_survive_the_binary_encoding()
+	{
+		let frames = vec![(vec![0x00; 612], Duration::from_millis(40)), (vec![0xFF; 612], Duration::from_millis(1500))];
+		let request = IpcRequest { id: 7, command: IpcCommand::RenderPair(frames.clone()) };
+
+		let binary = request.to_binary().unwrap();
+		assert_eq!(binary[0], BINARY_MARKER);
+		let size = u32::from_le_bytes(binary[1..5].try_into().unwrap()) as usize;
+		assert_eq!(size, binary.len() - 5);
+
+		match IpcRequest::from_binary(&binary[5..])
+		{
+			Ok(IpcRequest { id: 7, command: IpcCommand::RenderPair(decoded) }) => assert_eq!(decoded, frames),
+			r => panic!("decoded to {:?}", r),
+		}
+	}
+
+	#[test]
+	fn broken_binary_request_still_has_its_id()
+	{
+		let binary = IpcRequest { id: 9, command: IpcCommand::Status }.to_binary().unwrap();
+
+		// Cutting the command off leaves just the ID
+		let id_size = binary.len() - 5 - 1;
+		assert!(matches!(
+			IpcRequest::from_binary(&binary[5..5 + id_size]),
+			Err((9, CrateError::UnsupportedCommand(_)))
+		));
+	}
+
+	#[test]
+	fn timers_out_of_range_are_rejected()
+	{
+		let timer = |options| IpcCommand::StartWidget { target: Target::Left, widget: Widget::Timer(options) };
+		let options = crate::widget::TimerOptions::default();
+
+		assert!(timer(options.clone()).validate().is_ok());
+		assert!(timer(crate::widget::TimerOptions { duration: u64::MAX, ..options.clone() }).validate().is_err());
+		assert!(timer(crate::widget::TimerOptions { started: Some(u64::MAX), ..options }).validate().is_err());
 	}
 }
@@ -1,6 +1,11 @@
+pub mod canvas;
 pub mod error;
+pub mod hwmon;
+pub mod image;
 pub mod ipc;
+pub mod text;
 pub mod version;
+pub mod widget;
 
 #[cfg(unix)]
 mod posix;
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Longest a timer session or rest can be, which is as many minutes as its digits can show
+pub const MAX_TIMER_SECONDS: u64 = 999 * 60;
+pub const MAX_TIMER_CYCLES: u32 = 99;
+
+/// Something the daemon draws by itself and keeps up to date, even after the client that started it is gone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Widget
+{
+	Clock(ClockOptions),
+	Sysmon(SysmonOptions),
+	Battery(BatteryOptions),
+	Network(NetworkOptions),
+	Hwmon(HwmonOptions),
+	NowPlaying(NowPlayingOptions),
+	Timer(TimerOptions),
+	Effect(EffectOptions),
+}
+
+impl Widget
+{
+	pub fn name(&self) -> &'static str
+	{
+		match self
+		{
+			Widget::Clock(_) => "clock",
+			Widget::Sysmon(_) => "sysmon",
+			Widget::Battery(_) => "battery",
+			Widget::Network(_) => "network",
+			Widget::Hwmon(_) => "hwmon",
+			Widget::NowPlaying(_) => "now playing",
+			Widget::Timer(_) => "timer",
+			Widget::Effect(_) => "effect",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClockOptions
+{
+	/// Use a 12 hour clock instead of a 24 hour one
+	pub hour12: bool,
+	/// Update every second and show how far into the minute it is
+	pub seconds: bool,
+	pub style: ClockStyle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockStyle
+{
+	/// Hours stacked on top of the minutes, or hours on the left and minutes on the right on a pair
+	#[default]
+	Digits,
+	/// A column of bits for each of the hours, minutes and seconds
+	Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SysmonOptions
+{
+	/// What to show on the left matrix, or the left half of a pair
+	pub left: SysmonPanel,
+	/// What to show on the right matrix, or the right half of a pair
+	pub right: SysmonPanel,
+	/// How often to sample, in milliseconds
+	pub interval: u64,
+}
+
+impl Default for SysmonOptions
+{
+	fn default() -> Self
+	{
+		SysmonOptions { left: SysmonPanel::Cpu, right: SysmonPanel::Memory, interval: 1000 }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SysmonPanel
+{
+	/// A horizontal bar for each core
+	Cpu,
+	/// Overall CPU usage scrolling upwards, with the newest sample at the bottom
+	CpuHistory,
+	/// Memory and swap usage side by side
+	Memory,
+	/// The 1, 5 and 15 minute load averages, relative to the number of cores
+	Load,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryOptions
+{
+	/// Percentage at or below which the gauge flashes
+	pub low: u8,
+	/// Percentage at or below which the gauge flashes faster, along with its outline
+	pub critical: u8,
+	/// Only show this battery, such as "BAT1", instead of every battery combined
+	pub battery: Option<String>,
+	/// Where to look for power supplies instead of /sys/class/power_supply
+	pub sysfs: Option<String>,
+}
+
+impl Default for BatteryOptions
+{
+	fn default() -> Self
+	{
+		BatteryOptions { low: 20, critical: 5, battery: None, sysfs: None }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkOptions
+{
+	/// Only show this interface, such as "wlan0", instead of every interface besides loopback combined
+	pub interface: Option<String>,
+	/// How often to sample, in milliseconds
+	pub interval: u64,
+	/// Bytes per second that fill a whole row, otherwise it scales to the busiest sample on screen
+	pub scale: Option<u64>,
+}
+
+impl Default for NetworkOptions
+{
+	fn default() -> Self
+	{
+		NetworkOptions { interface: None, interval: 1000, scale: None }
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HwmonOptions
+{
+	/// Names of the sensors from the daemon's config to show, in order, or all of them when empty
+	pub sensors: Vec<String>,
+	/// Where to look for hardware monitoring chips instead of /sys/class/hwmon
+	pub sysfs: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NowPlayingOptions
+{
+	/// Follow this MPRIS player, such as "spotify", instead of whichever one is playing
+	pub player: Option<String>,
+	/// Scroll the artist and title across the middle
+	pub marquee: bool,
+	/// Session bus the player is on, instead of the one the daemon was started with
+	pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimerOptions
+{
+	pub mode: TimerMode,
+	/// Seconds to count down from, or the length of each focus session
+	pub duration: u64,
+	/// Seconds of rest between focus sessions
+	pub rest: u64,
+	/// Focus sessions before the timer is done
+	pub cycles: u32,
+	/// When the timer was started in seconds since the Unix epoch, so that it carries on from there when it's displayed again
+	pub started: Option<u64>,
+}
+
+impl TimerOptions
+{
+	pub fn validate(&self) -> Result<(), String>
+	{
+		if self.duration > MAX_TIMER_SECONDS || self.rest > MAX_TIMER_SECONDS
+		{
+			return Err(format!("timers can't be longer than {} minutes", MAX_TIMER_SECONDS / 60));
+		}
+
+		if self.cycles > MAX_TIMER_CYCLES
+		{
+			return Err(format!("timers can't have more than {} focus sessions", MAX_TIMER_CYCLES));
+		}
+
+		if self.started.is_some_and(|s| UNIX_EPOCH.checked_add(Duration::from_secs(s)).is_none())
+		{
+			return Err("the timer's start time is out of range".to_string());
+		}
+
+		Ok(())
+	}
+}
+
+impl Default for TimerOptions
+{
+	fn default() -> Self
+	{
+		TimerOptions { mode: TimerMode::Countdown, duration: 25 * 60, rest: 5 * 60, cycles: 4, started: None }
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerMode
+{
+	/// A bar that drains over the duration, and flashes once it's empty
+	#[default]
+	Countdown,
+	/// The minutes that have gone by, with a bar that fills over each minute
+	Stopwatch,
+	/// Focus sessions that drain like a countdown, with rests in between that fill back up
+	Pomodoro,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectOptions
+{
+	pub effect: Effect,
+	/// How fast the effect moves, 1 is its normal speed
+	pub speed: f64,
+	/// How much is going on at once, from 0 to 1
+	pub density: f64,
+	/// Start from this seed, so that the same effect can be seen again, otherwise it's different every time
+	pub seed: Option<u64>,
+}
+
+impl Default for EffectOptions
+{
+	fn default() -> Self
+	{
+		EffectOptions { effect: Effect::Rain, speed: 1.0, density: 0.5, seed: None }
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect
+{
+	/// Streaks of digital rain falling down the columns
+	#[default]
+	Rain,
+	/// Flames rising from the bottom
+	Fire,
+	/// Waves of light flowing over each other
+	Plasma,
+	/// Stars flying past from the middle
+	Starfield,
+	/// Drops landing on water, with the ripples bouncing off the edges
+	Ripple,
+	/// Conway's Game of Life, starting over whenever it settles down
+	Life,
+}